@@ -0,0 +1,192 @@
+//! Request bodies, either in memory or streamed from a reader.
+
+use core::fmt;
+use std::io::{self, Read, Write};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Error;
+
+/// The size of the buffer streamed bodies are read into, and thus the maximum size of the chunks
+/// they are sent in.
+const CHUNK_LENGTH: usize = 16 * 1024;
+
+type Shared<T> = Arc<Mutex<Option<T>>>;
+
+/// The body of a [`Request`](crate::Request).
+///
+/// Streamed bodies are shared between clones of the request they were set on, and can only be
+/// sent once.
+#[derive(Clone)]
+pub(crate) enum Body {
+    Bytes(Vec<u8>),
+    Reader(Shared<Box<dyn Read + Send>>, Option<u64>),
+    #[cfg(feature = "async")]
+    Stream(Shared<Pin<Box<dyn AsyncRead + Send>>>, Option<u64>),
+}
+
+impl Body {
+    pub(crate) fn reader<R: Read + Send + 'static>(reader: R, length: Option<u64>) -> Body {
+        Body::Reader(Arc::new(Mutex::new(Some(Box::new(reader)))), length)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn stream<S: AsyncRead + Send + 'static>(stream: S, length: Option<u64>) -> Body {
+        Body::Stream(Arc::new(Mutex::new(Some(Box::pin(stream)))), length)
+    }
+
+    /// Returns whether this body can be sent again, ie. it isn't streamed.
+    pub(crate) fn is_replayable(&self) -> bool { matches!(self, Body::Bytes(_)) }
+
+    /// Returns the header framing this body, if it is streamed.
+    pub(crate) fn framing_header(&self) -> Option<(&'static str, String)> {
+        let length = match self {
+            Body::Bytes(_) => return None,
+            Body::Reader(_, length) => length,
+            #[cfg(feature = "async")]
+            Body::Stream(_, length) => length,
+        };
+        Some(match length {
+            Some(length) => ("Content-Length", length.to_string()),
+            None => ("Transfer-Encoding", "chunked".to_string()),
+        })
+    }
+
+    /// Writes `head` followed by this body to `stream`.
+    pub(crate) fn write_to<W: Write>(&self, head: &[u8], stream: &mut W) -> Result<(), Error> {
+        let (reader, length) = match self {
+            Body::Bytes(bytes) => {
+                let mut request = Vec::with_capacity(head.len() + bytes.len());
+                request.extend_from_slice(head);
+                request.extend_from_slice(bytes);
+                return Ok(stream.write_all(&request)?);
+            }
+            Body::Reader(reader, length) => (take(reader)?, *length),
+            #[cfg(feature = "async")]
+            Body::Stream(..) =>
+                return Err(unsupported(
+                    "bodies set with with_body_stream can only be sent asynchronously",
+                )),
+        };
+        let mut reader = reader;
+        stream.write_all(head)?;
+        let mut buf = vec![0; CHUNK_LENGTH];
+        let mut remaining = length;
+        loop {
+            let limit = remaining.map_or(CHUNK_LENGTH, |r| r.min(CHUNK_LENGTH as u64) as usize);
+            let read = if limit == 0 { 0 } else { read_retrying(&mut reader, &mut buf[..limit])? };
+            if let Some(remaining) = &mut remaining {
+                if read == 0 && *remaining > 0 {
+                    return Err(too_short());
+                }
+                *remaining -= read as u64;
+                stream.write_all(&buf[..read])?;
+            } else {
+                write!(stream, "{:x}\r\n", read)?;
+                stream.write_all(&buf[..read])?;
+                stream.write_all(b"\r\n")?;
+            }
+            if read == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Writes `head` followed by this body to `stream` asynchronously.
+    #[cfg(feature = "async")]
+    pub(crate) async fn write_to_async<W: AsyncWrite + Unpin>(
+        &self,
+        head: &[u8],
+        stream: &mut W,
+    ) -> Result<(), Error> {
+        let (reader, length) = match self {
+            Body::Bytes(bytes) => {
+                let mut request = Vec::with_capacity(head.len() + bytes.len());
+                request.extend_from_slice(head);
+                request.extend_from_slice(bytes);
+                return Ok(stream.write_all(&request).await?);
+            }
+            Body::Reader(..) =>
+                return Err(unsupported(
+                    "bodies set with with_body_reader can only be sent synchronously",
+                )),
+            Body::Stream(reader, length) => (take(reader)?, *length),
+        };
+        let mut reader = reader;
+        stream.write_all(head).await?;
+        let mut buf = vec![0; CHUNK_LENGTH];
+        let mut remaining = length;
+        loop {
+            let limit = remaining.map_or(CHUNK_LENGTH, |r| r.min(CHUNK_LENGTH as u64) as usize);
+            let read = if limit == 0 { 0 } else { reader.read(&mut buf[..limit]).await? };
+            if let Some(remaining) = &mut remaining {
+                if read == 0 && *remaining > 0 {
+                    return Err(too_short());
+                }
+                *remaining -= read as u64;
+                stream.write_all(&buf[..read]).await?;
+            } else {
+                stream.write_all(format!("{:x}\r\n", read).as_bytes()).await?;
+                stream.write_all(&buf[..read]).await?;
+                stream.write_all(b"\r\n").await?;
+            }
+            if read == 0 {
+                return Ok(stream.flush().await?);
+            }
+        }
+    }
+}
+
+fn take<T>(reader: &Shared<T>) -> Result<T, Error> {
+    reader.lock().unwrap().take().ok_or(Error::RequestBodyConsumed)
+}
+
+fn read_retrying<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match reader.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => return res,
+        }
+    }
+}
+
+fn too_short() -> Error {
+    Error::IoError(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the request body ended before its given length",
+    ))
+}
+
+#[cfg(feature = "async")]
+fn unsupported(msg: &'static str) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::Other, msg))
+}
+
+impl PartialEq for Body {
+    fn eq(&self, other: &Body) -> bool {
+        match (self, other) {
+            (Body::Bytes(a), Body::Bytes(b)) => a == b,
+            (Body::Reader(a, a_len), Body::Reader(b, b_len)) => Arc::ptr_eq(a, b) && a_len == b_len,
+            #[cfg(feature = "async")]
+            (Body::Stream(a, a_len), Body::Stream(b, b_len)) => Arc::ptr_eq(a, b) && a_len == b_len,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Body {}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Body::Reader(_, length) => f.debug_tuple("Reader").field(length).finish(),
+            #[cfg(feature = "async")]
+            Body::Stream(_, length) => f.debug_tuple("Stream").field(length).finish(),
+        }
+    }
+}
@@ -2,17 +2,28 @@
 //!
 //! The `Client` caches connections to avoid repeated TCP handshakes and TLS negotiations.
 //!
-//! Due to std limitations, `Client` currently only supports async requests.
+//! Blocking requests check connections out of a pool of idle keep-alive connections and return
+//! them once the response was read, async requests (with the `async` feature) share cached
+//! connections.
 
-#![cfg(feature = "async")]
-
-use std::collections::{hash_map, HashMap, VecDeque};
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
 
+#[cfg(feature = "async")]
 use crate::connection::AsyncConnection;
+use crate::connection::{self, Connection};
 use crate::request::{OwnedConnectionParams as ConnectionKey, ParsedRequest};
+#[cfg(feature = "rustls")]
+use crate::TlsConfig;
 use crate::{Error, Request, Response};
 
+/// How long an idle connection is kept by default, less than the 30 seconds bitcoind and many
+/// other servers close idle connections after.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// A client that caches connections for reuse.
 ///
 /// The client maintains a pool of up to `capacity` connections, evicting
@@ -21,10 +32,20 @@ use crate::{Error, Request, Response};
 /// # Example
 ///
 /// ```no_run
-/// # async fn request() {
 /// use bitreq::{Client, RequestExt};
 ///
 /// let client = Client::new(10); // Cache up to 10 connections
+/// let response = bitreq::get("http://example.com").send_with_client(&client);
+/// ```
+///
+/// With the `async` feature, requests can be sent asynchronously too:
+///
+/// ```no_run
+/// # #[cfg(feature = "async")]
+/// # async fn request() {
+/// use bitreq::{Client, RequestExt};
+///
+/// let client = Client::new(10);
 /// let response = bitreq::get("https://example.com")
 ///     .send_async_with_client(&client)
 ///     .await;
@@ -32,15 +53,286 @@ use crate::{Error, Request, Response};
 /// ```
 #[derive(Clone)]
 pub struct Client {
-    r#async: Arc<Mutex<ClientImpl<AsyncConnection>>>,
+    config: PoolConfig,
+    /// The TLS configuration of requests that don't have their own.
+    #[cfg(feature = "rustls")]
+    tls: Option<TlsConfig>,
+    stats: Arc<Stats>,
+    blocking: Arc<BlockingPool>,
+    #[cfg(feature = "async")]
+    r#async: Arc<AsyncPool>,
+}
+
+/// The limits of the pooled connections, set with the `Client` builder methods.
+#[derive(Clone, Copy, Debug)]
+struct PoolConfig {
+    max_per_host: usize,
+    idle_timeout: Duration,
+    max_lifetime: Option<Duration>,
 }
 
+impl PoolConfig {
+    fn is_expired(&self, created_at: Instant, last_used: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(last_used) >= self.idle_timeout
+            || self.max_lifetime.is_some_and(|max| now.saturating_duration_since(created_at) >= max)
+    }
+}
+
+/// Statistics about the connections of a [`Client`], see [`Client::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of requests sent on a pooled connection.
+    pub hits: u64,
+    /// The number of requests for which a new connection was opened.
+    pub misses: u64,
+    /// The number of pooled connections closed because the pool was full, they were idle or too
+    /// old, or the server closed them.
+    pub evictions: u64,
+}
+
+#[derive(Default)]
+struct Stats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Stats {
+    fn hit(&self) { self.hits.fetch_add(1, Ordering::Relaxed); }
+    fn miss(&self) { self.misses.fetch_add(1, Ordering::Relaxed); }
+    fn evict(&self) { self.evictions.fetch_add(1, Ordering::Relaxed); }
+}
+
+/// The pool of the async connections of a [`Client`].
+#[cfg(feature = "async")]
+struct AsyncPool {
+    state: Mutex<ClientImpl<AsyncConnection>>,
+    /// Notified when a connection was opened, or failed to be.
+    opened: tokio::sync::Notify,
+}
+
+#[cfg(feature = "async")]
+impl AsyncPool {
+    fn lock(&self) -> MutexGuard<'_, ClientImpl<AsyncConnection>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "async")]
 struct ClientImpl<T> {
-    connections: HashMap<ConnectionKey, Arc<T>>,
-    lru_order: VecDeque<ConnectionKey>,
+    /// The cached connections, least recently used first.
+    connections: VecDeque<Pooled<T>>,
+    /// The hosts to which connections are being opened, once per connection.
+    opening: Vec<ConnectionKey>,
+    capacity: usize,
+}
+
+/// How a request gets an async connection.
+#[cfg(feature = "async")]
+enum Checkout<T> {
+    /// Uses a cached connection, to check for having been closed if it's unused.
+    Cached(Arc<T>, bool),
+    /// Opens a new connection.
+    Open,
+    /// Waits for a connection to be opened, the host having too many.
+    Wait,
+}
+
+#[cfg(feature = "async")]
+struct Pooled<T> {
+    key: ConnectionKey,
+    conn: Arc<T>,
+    created_at: Instant,
+    last_used: Instant,
+}
+
+#[cfg(feature = "async")]
+impl<T> Pooled<T> {
+    /// Returns whether a request is using this connection, the pool holding one reference.
+    fn in_use(&self) -> bool { Arc::strong_count(&self.conn) > 1 }
+}
+
+#[cfg(feature = "async")]
+impl<T> ClientImpl<T> {
+    /// Drops the connections too old, or idle for too long, to be used.
+    fn close_expired(&mut self, config: &PoolConfig, stats: &Stats) {
+        let now = Instant::now();
+        self.connections.retain(|pooled| {
+            let expired =
+                !pooled.in_use() && config.is_expired(pooled.created_at, pooled.last_used, now);
+            if expired {
+                stats.evict();
+            }
+            !expired
+        });
+    }
+
+    fn position(&self, conn: &Arc<T>) -> Option<usize> {
+        self.connections.iter().position(|pooled| Arc::ptr_eq(&pooled.conn, conn))
+    }
+
+    /// Marks `conn` as the most recently used connection.
+    fn touch(&mut self, conn: &Arc<T>) {
+        if let Some(index) = self.position(conn) {
+            let mut pooled = self.connections.remove(index).unwrap();
+            pooled.last_used = Instant::now();
+            self.connections.push_back(pooled);
+        }
+    }
+
+    /// Drops `conn` from the pool, if it's still there.
+    fn remove(&mut self, conn: &Arc<T>, stats: &Stats) {
+        if let Some(index) = self.position(conn) {
+            self.connections.remove(index);
+            stats.evict();
+        }
+    }
+}
+
+/// The pool of the blocking connections of a [`Client`].
+struct BlockingPool {
+    state: Mutex<PoolState>,
+    /// Notified when a connection is returned to the pool or closed.
+    released: Condvar,
+}
+
+struct PoolState {
+    /// The idle connections, least recently used first.
+    idle: VecDeque<Idle>,
+    /// The number of connections, idle or in use, open to each host.
+    open: HashMap<ConnectionKey, usize>,
     capacity: usize,
 }
 
+struct Idle {
+    key: ConnectionKey,
+    conn: Connection,
+    created_at: Instant,
+    returned_at: Instant,
+}
+
+impl PoolState {
+    fn close(&mut self, key: &ConnectionKey) {
+        if let Some(count) = self.open.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                self.open.remove(key);
+            }
+        }
+    }
+
+    /// Closes the connections too old, or idle for too long, to be used.
+    fn close_expired(&mut self, config: &PoolConfig, stats: &Stats) {
+        let now = Instant::now();
+        let mut i = 0;
+        while i < self.idle.len() {
+            let idle = &self.idle[i];
+            if config.is_expired(idle.created_at, idle.returned_at, now) {
+                let idle = self.idle.remove(i).unwrap();
+                self.close(&idle.key);
+                stats.evict();
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Returns when the next idle connection expires.
+    fn next_expiry(&self, config: &PoolConfig) -> Option<Instant> {
+        self.idle
+            .iter()
+            .map(|idle| {
+                let idle_expiry = idle.returned_at + config.idle_timeout;
+                match config.max_lifetime {
+                    Some(max) => idle_expiry.min(idle.created_at + max),
+                    None => idle_expiry,
+                }
+            })
+            .min()
+    }
+}
+
+impl BlockingPool {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Takes the most recently used idle connection to `key` and when it was opened, or returns
+    /// `None` if a new one may be opened, waiting until `timeout_at` for one if there are
+    /// already too many.
+    fn checkout(
+        &self,
+        key: &ConnectionKey,
+        config: &PoolConfig,
+        stats: &Stats,
+        timeout_at: Option<Instant>,
+    ) -> Result<Option<(Connection, Instant)>, Error> {
+        let mut state = self.lock();
+        loop {
+            state.close_expired(config, stats);
+            if let Some(index) = state.idle.iter().rposition(|idle| idle.key == *key) {
+                let idle = state.idle.remove(index).unwrap();
+                return Ok(Some((idle.conn, idle.created_at)));
+            }
+            let count = state.open.entry(key.clone()).or_insert(0);
+            if *count < config.max_per_host {
+                *count += 1;
+                return Ok(None);
+            }
+            // Wake up before the next idle connection expires, it may be the one to close.
+            let wake_at = match (timeout_at, state.next_expiry(config)) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            state = match wake_at {
+                Some(wake_at) => {
+                    let now = Instant::now();
+                    if timeout_at.is_some_and(|t| t <= now) {
+                        return Err(Error::TotalTimeout);
+                    }
+                    let wait = wake_at.saturating_duration_since(now);
+                    self.released.wait_timeout(state, wait).unwrap_or_else(|e| e.into_inner()).0
+                }
+                None => self.released.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    /// Returns a connection to `key` opened at `created_at` to the pool, closing the least
+    /// recently used idle connection if the pool is full.
+    fn checkin(&self, key: ConnectionKey, conn: Connection, created_at: Instant, stats: &Stats) {
+        let mut state = self.lock();
+        state.idle.push_back(Idle { key, conn, created_at, returned_at: Instant::now() });
+        if state.idle.len() > state.capacity {
+            if let Some(oldest) = state.idle.pop_front() {
+                state.close(&oldest.key);
+                stats.evict();
+            }
+        }
+        self.released.notify_all();
+    }
+
+    /// Records that a connection to `key` checked out of the pool was closed.
+    fn discard(&self, key: &ConnectionKey) {
+        self.lock().close(key);
+        self.released.notify_all();
+    }
+}
+
+/// Returns whether `err` is how a reused connection closed by the server fails.
+fn is_stale_connection(err: &Error) -> bool {
+    match err {
+        Error::IoError(e) => matches!(
+            e.kind(),
+            io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+        ),
+        _ => false,
+    }
+}
+
 impl Client {
     /// Creates a new `Client` with the specified connection cache capacity.
     ///
@@ -50,57 +342,295 @@ impl Client {
     ///   reached, the least recently used connection is evicted.
     pub fn new(capacity: usize) -> Self {
         Client {
-            r#async: Arc::new(Mutex::new(ClientImpl {
-                connections: HashMap::new(),
-                lru_order: VecDeque::new(),
-                capacity,
-            })),
+            config: PoolConfig {
+                max_per_host: usize::MAX,
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                max_lifetime: None,
+            },
+            #[cfg(feature = "rustls")]
+            tls: None,
+            stats: Arc::new(Stats::default()),
+            blocking: Arc::new(BlockingPool {
+                state: Mutex::new(PoolState {
+                    idle: VecDeque::new(),
+                    open: HashMap::new(),
+                    capacity,
+                }),
+                released: Condvar::new(),
+            }),
+            #[cfg(feature = "async")]
+            r#async: Arc::new(AsyncPool {
+                state: Mutex::new(ClientImpl {
+                    connections: VecDeque::new(),
+                    opening: Vec::new(),
+                    capacity,
+                }),
+                opened: tokio::sync::Notify::new(),
+            }),
         }
     }
 
-    /// Sends a request asynchronously using a cached connection if available.
-    pub async fn send_async(&self, request: Request) -> Result<Response, Error> {
-        let parsed_request = ParsedRequest::new(request)?;
-        let key = parsed_request.connection_params();
-        let owned_key = key.into();
+    /// Sets the maximum number of connections open to the same host, unlimited by default.
+    ///
+    /// Once the limit is reached, blocking requests wait for a connection to the host to be
+    /// returned to the pool, until their timeout, while async requests share the least busy
+    /// connection, or wait for one being opened.
+    pub fn with_max_connections_per_host(mut self, max: usize) -> Self {
+        self.config.max_per_host = max.max(1);
+        self
+    }
 
-        // Try to get cached connection
-        let conn_opt = {
-            let state = self.r#async.lock().unwrap();
+    /// Sets how long an idle connection is kept before being closed, 20 seconds by default.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.idle_timeout = timeout;
+        self
+    }
 
-            if let Some(conn) = state.connections.get(&owned_key) {
-                Some(Arc::clone(conn))
-            } else {
-                None
+    /// Sets how long after being opened a connection is no longer used for new requests,
+    /// unlimited by default.
+    pub fn with_max_lifetime(mut self, lifetime: Duration) -> Self {
+        self.config.max_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Sets the TLS configuration of the requests sent with this client which don't have one set
+    /// with [`Request::with_tls_config`].
+    #[cfg(feature = "rustls")]
+    pub fn with_tls_config(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Returns the statistics of the connection pool, shared by the clones of this client.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sends a request using an idle pooled connection if available.
+    ///
+    /// The connection is returned to the pool once the response was read, unless the server
+    /// closes it. If a reused connection turns out to have been closed by the server before
+    /// it responded to an idempotent request, the request is retried on a new connection,
+    /// unless its body was streamed.
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send`].
+    pub fn send(&self, request: Request) -> Result<Response, Error> {
+        let mut request = self.parse(request)?;
+        loop {
+            let mut response = self.send_once(&request)?;
+            match connection::next_hop(request, &mut response)? {
+                Some(next) => request = next,
+                None => return Ok(response),
             }
-        };
-        let conn = if let Some(conn) = conn_opt {
-            conn
-        } else {
-            let connection = AsyncConnection::new(key, parsed_request.timeout_at).await?;
-            let connection = Arc::new(connection);
-
-            let mut state = self.r#async.lock().unwrap();
-            if let hash_map::Entry::Vacant(entry) = state.connections.entry(owned_key) {
-                entry.insert(Arc::clone(&connection));
-                state.lru_order.push_back(key.into());
-                if state.connections.len() > state.capacity {
-                    if let Some(oldest_key) = state.lru_order.pop_front() {
-                        state.connections.remove(&oldest_key);
+        }
+    }
+
+    /// Parses `request`, with the client's TLS configuration unless it has its own.
+    #[allow(unused_mut)]
+    fn parse(&self, mut request: Request) -> Result<ParsedRequest, Error> {
+        #[cfg(feature = "rustls")]
+        if request.tls.is_none() {
+            request.tls = self.tls.clone();
+        }
+        ParsedRequest::new(request)
+    }
+
+    /// Sends a request without following redirects.
+    fn send_once(&self, request: &ParsedRequest) -> Result<Response, Error> {
+        let key: ConnectionKey = request.connection_params().into();
+        loop {
+            let checkout =
+                self.blocking.checkout(&key, &self.config, &self.stats, request.timeout_at)?;
+            let (conn, created_at, reused) = match checkout {
+                Some((conn, created_at)) => {
+                    self.stats.hit();
+                    (conn, created_at, true)
+                }
+                None => {
+                    self.stats.miss();
+                    match Connection::new(request.connection_params(), request.timeouts()) {
+                        Ok(conn) => (conn, Instant::now(), false),
+                        Err(e) => {
+                            self.blocking.discard(&key);
+                            return Err(e);
+                        }
                     }
                 }
+            };
+            match conn.send_keep_alive(request) {
+                Ok(Ok((response, Some(conn)))) => {
+                    self.blocking.checkin(key, conn, created_at, &self.stats);
+                    return Ok(response);
+                }
+                Ok(Ok((response, None))) => {
+                    self.blocking.discard(&key);
+                    return Ok(response);
+                }
+                Ok(Err(e)) => {
+                    self.blocking.discard(&key);
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.blocking.discard(&key);
+                    if !reused
+                        || !is_stale_connection(&e)
+                        || !request.config.method.is_idempotent()
+                        || !request.is_replayable()
+                    {
+                        return Err(e);
+                    }
+                    self.stats.evict();
+                    #[cfg(feature = "log")]
+                    log::debug!("Pooled connection was closed by the server, retrying: {}", e);
+                }
             }
-            connection
+        }
+    }
+
+    /// Sends a request asynchronously using a cached connection if available.
+    ///
+    /// Cached connections the server closed are detected and replaced before being used. If a
+    /// cached connection is closed while sending an idempotent request, the request is retried
+    /// on a new connection.
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, request: Request) -> Result<Response, Error> {
+        let parsed_request = self.parse(request)?;
+        let key: ConnectionKey = parsed_request.connection_params().into();
+        let (conn, reused) = self.async_connection(&key, &parsed_request).await?;
+
+        let retry = if reused
+            && parsed_request.config.method.is_idempotent()
+            && parsed_request.is_replayable()
+        {
+            Some(parsed_request.clone())
+        } else {
+            None
         };
+        let response = conn.send(parsed_request).await;
+        match (response, retry) {
+            (Err(e), Some(request)) if is_stale_connection(&e) => {
+                self.r#async.lock().remove(&conn, &self.stats);
+                #[cfg(feature = "log")]
+                log::debug!("Cached connection was closed by the server, retrying: {}", e);
+                let (conn, _) = self.async_connection(&key, &request).await?;
+                let response = conn.send(request).await;
+                self.r#async.lock().touch(&conn);
+                response
+            }
+            (response, _) => {
+                self.r#async.lock().touch(&conn);
+                response
+            }
+        }
+    }
+
+    /// Returns a cached connection to `key`, and whether it was reused, or opens a new one.
+    #[cfg(feature = "async")]
+    async fn async_connection(
+        &self,
+        key: &ConnectionKey,
+        request: &ParsedRequest,
+    ) -> Result<(Arc<AsyncConnection>, bool), Error> {
+        loop {
+            // Created before looking at the pool to not miss a connection opened meanwhile.
+            let opened = self.r#async.opened.notified();
+            let checkout = {
+                let mut guard = self.r#async.lock();
+                let state = &mut *guard;
+                state.close_expired(&self.config, &self.stats);
+                let opening = state.opening.iter().filter(|k| *k == key).count();
+                let mut connections = state.connections.iter().filter(|p| p.key == *key);
+                let count = connections.clone().count() + opening;
+                // Prefer an unused connection, then share one with pipelined requests, then
+                // open a new one if allowed, or else share the least busy one.
+                let idle = connections.clone().rev().find(|p| !p.in_use());
+                let shared = if request.config.pipelining {
+                    connections.next_back()
+                } else {
+                    connections.min_by_key(|p| Arc::strong_count(&p.conn))
+                };
+                match (idle, shared) {
+                    (Some(pooled), _) => Checkout::Cached(Arc::clone(&pooled.conn), true),
+                    (None, Some(pooled)) if request.config.pipelining =>
+                        Checkout::Cached(Arc::clone(&pooled.conn), false),
+                    _ if count < self.config.max_per_host => {
+                        state.opening.push(key.clone());
+                        Checkout::Open
+                    }
+                    (None, Some(pooled)) => Checkout::Cached(Arc::clone(&pooled.conn), false),
+                    (None, None) => Checkout::Wait,
+                }
+            };
+            match checkout {
+                // Only unused connections can be checked for having been closed by the server.
+                Checkout::Cached(conn, true) if conn.is_closed().await => {
+                    self.r#async.lock().remove(&conn, &self.stats);
+                }
+                Checkout::Cached(conn, _) => {
+                    self.stats.hit();
+                    self.r#async.lock().touch(&conn);
+                    return Ok((conn, true));
+                }
+                Checkout::Open => break,
+                Checkout::Wait => match request.timeout_at {
+                    Some(timeout_at) => tokio::time::timeout_at(timeout_at.into(), opened)
+                        .await
+                        .map_err(|_| Error::TotalTimeout)?,
+                    None => opened.await,
+                },
+            }
+        }
 
-        // Send the request
-        conn.send(parsed_request).await
+        self.stats.miss();
+        let conn = AsyncConnection::new(request.connection_params(), request.timeouts()).await;
+        let mut state = self.r#async.lock();
+        if let Some(index) = state.opening.iter().position(|k| k == key) {
+            state.opening.swap_remove(index);
+        }
+        let conn = conn.map(Arc::new);
+        if let Ok(ref conn) = conn {
+            let now = Instant::now();
+            state.connections.push_back(Pooled {
+                key: key.clone(),
+                conn: Arc::clone(conn),
+                created_at: now,
+                last_used: now,
+            });
+            while state.connections.len() > state.capacity {
+                state.connections.pop_front();
+                self.stats.evict();
+            }
+        }
+        self.r#async.opened.notify_waiters();
+        Ok((conn?, false))
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.blocking.lock();
+        f.debug_struct("Client")
+            .field("capacity", &state.capacity)
+            .field("idle", &state.idle.len())
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish()
     }
 }
 
 /// Extension trait for `Request` to use with `Client`.
 pub trait RequestExt {
+    /// Sends this request using the provided client's connection pool.
+    fn send_with_client(self, client: &Client) -> Result<Response, Error>;
+
     /// Sends this request asynchronously using the provided client's connection pool.
+    #[cfg(feature = "async")]
     fn send_async_with_client(
         self,
         client: &Client,
@@ -108,6 +638,9 @@ pub trait RequestExt {
 }
 
 impl RequestExt for Request {
+    fn send_with_client(self, client: &Client) -> Result<Response, Error> { client.send(self) }
+
+    #[cfg(feature = "async")]
     fn send_async_with_client(
         self,
         client: &Client,
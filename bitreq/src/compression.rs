@@ -0,0 +1,113 @@
+//! Decoding of gzip and deflate compressed response bodies.
+
+use alloc::collections::BTreeMap;
+use std::io::{self, Write};
+
+use flate2::write::{GzDecoder, ZlibDecoder};
+
+use crate::Error;
+
+/// The `Accept-Encoding` header sent with requests.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+enum Inner {
+    Gzip(GzDecoder<Vec<u8>>),
+    /// HTTP's deflate is the zlib format (RFC 9110).
+    Deflate(ZlibDecoder<Vec<u8>>),
+}
+
+/// Decodes a body fed to it as it's read.
+pub(crate) struct Decoder {
+    inner: Inner,
+    /// How many of the decoded bytes were taken.
+    taken: usize,
+    /// Whether a part of the body was fed, an empty body being left as is.
+    fed: bool,
+    finished: bool,
+}
+
+impl Decoder {
+    /// Returns the decoder of a body with the given headers, if it's compressed, and removes the
+    /// headers describing the compressed body.
+    pub(crate) fn from_headers(headers: &mut BTreeMap<String, String>) -> Option<Decoder> {
+        let inner = match headers.get("content-encoding")?.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Inner::Gzip(GzDecoder::new(Vec::new())),
+            "deflate" => Inner::Deflate(ZlibDecoder::new(Vec::new())),
+            _ => return None,
+        };
+        headers.remove("content-encoding");
+        headers.remove("content-length");
+        Some(Decoder { inner, taken: 0, fed: false, finished: false })
+    }
+
+    fn decoded(&mut self) -> &mut Vec<u8> {
+        match &mut self.inner {
+            Inner::Gzip(decoder) => decoder.get_mut(),
+            Inner::Deflate(decoder) => decoder.get_mut(),
+        }
+    }
+
+    /// Returns whether the whole body was fed and decoded.
+    pub(crate) fn is_finished(&self) -> bool { self.finished }
+
+    /// Decodes the next bytes of the body.
+    pub(crate) fn feed(&mut self, mut encoded: &[u8]) -> Result<(), Error> {
+        self.fed |= !encoded.is_empty();
+        while !encoded.is_empty() {
+            let written = match &mut self.inner {
+                Inner::Gzip(decoder) => decoder.write(encoded),
+                Inner::Deflate(decoder) => decoder.write(encoded),
+            }
+            .map_err(invalid_data)?;
+            if written == 0 {
+                // Anything after the end of the compressed stream is ignored.
+                break;
+            }
+            encoded = &encoded[written..];
+        }
+        Ok(())
+    }
+
+    /// Decodes what's left once the whole body was fed.
+    pub(crate) fn finish(&mut self) -> Result<(), Error> {
+        if self.fed {
+            match &mut self.inner {
+                Inner::Gzip(decoder) => decoder.try_finish(),
+                Inner::Deflate(decoder) => decoder.try_finish(),
+            }
+            .map_err(invalid_data)?;
+        }
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Takes the next decoded byte, with the number of decoded bytes available.
+    pub(crate) fn take(&mut self) -> Option<(u8, usize)> {
+        let taken = self.taken;
+        let decoded = self.decoded();
+        if taken == decoded.len() {
+            if taken > 0 {
+                decoded.clear();
+                self.taken = 0;
+            }
+            return None;
+        }
+        let byte = decoded[taken];
+        let available = decoded.len() - taken;
+        self.taken += 1;
+        Some((byte, available))
+    }
+
+    /// Takes all the decoded bytes which weren't taken yet.
+    #[cfg(feature = "async")]
+    pub(crate) fn take_all(&mut self) -> Vec<u8> {
+        let taken = core::mem::take(&mut self.taken);
+        let mut decoded = core::mem::take(self.decoded());
+        decoded.drain(..taken);
+        decoded
+    }
+}
+
+fn invalid_data(e: io::Error) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, e))
+}
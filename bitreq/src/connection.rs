@@ -1,8 +1,9 @@
 use core::time::Duration;
+use std::collections::VecDeque;
 #[cfg(feature = "async")]
 use std::future::Future;
 use std::io::{self, Read, Write};
-use std::net::{TcpStream, ToSocketAddrs};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 #[cfg(feature = "async")]
 use std::pin::Pin;
 #[cfg(feature = "async")]
@@ -16,148 +17,232 @@ use std::time::Instant;
 #[cfg(all(feature = "async", feature = "proxy"))]
 use tokio::io::AsyncReadExt;
 #[cfg(feature = "async")]
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
 #[cfg(feature = "async")]
 use tokio::net::TcpStream as AsyncTcpStream;
 #[cfg(feature = "async")]
 use tokio::sync::Mutex as AsyncMutex;
 
+#[cfg(feature = "proxy")]
+use crate::proxy::{Proxy, ProxyKind};
 use crate::request::{ConnectionParams, OwnedConnectionParams, ParsedRequest};
 #[cfg(feature = "async")]
-use crate::Response;
-use crate::{Error, Method, ResponseLazy};
+use crate::AsyncResponseLazy;
+use crate::{Error, Method, Response, ResponseLazy};
 
 #[cfg(feature = "async")]
 const BACKING_READ_BUFFER_LENGTH: usize = 16 * 1024;
 
+/// The delay after which the next address is tried while earlier connection attempts are still
+/// pending, the "Connection Attempt Delay" of RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// The maximum number of pending connection attempts of a blocking [`Connection`], which each
+/// take up a thread.
+const MAX_BLOCKING_CONNECTION_ATTEMPTS: usize = 2;
+
 type UnsecuredStream = TcpStream;
 
 #[cfg(feature = "rustls")]
 mod rustls_stream;
 #[cfg(feature = "rustls")]
+pub(crate) use rustls_stream::default_root_certificates;
+#[cfg(feature = "rustls")]
 type SecuredStream = rustls_stream::SecuredStream;
 
 pub(crate) enum HttpStream {
-    Unsecured(UnsecuredStream, Option<Instant>),
+    Unsecured(UnsecuredStream, Timeouts),
     #[cfg(feature = "rustls")]
-    Secured(Box<SecuredStream>, Option<Instant>),
+    Secured(Box<SecuredStream>, Timeouts),
     #[cfg(feature = "async")]
     Buffer(std::io::Cursor<Vec<u8>>),
 }
 
 impl HttpStream {
-    fn create_unsecured(reader: UnsecuredStream, timeout_at: Option<Instant>) -> HttpStream {
-        HttpStream::Unsecured(reader, timeout_at)
+    fn create_unsecured(reader: UnsecuredStream, timeouts: Timeouts) -> HttpStream {
+        HttpStream::Unsecured(reader, timeouts)
     }
 
     #[cfg(feature = "async")]
     pub(crate) fn create_buffer(buffer: Vec<u8>) -> HttpStream {
         HttpStream::Buffer(std::io::Cursor::new(buffer))
     }
+
+    /// Sets the timeouts of the reads and writes, eg. for the next request on a reused stream.
+    fn set_timeouts(&mut self, new_timeouts: Timeouts) {
+        match self {
+            HttpStream::Unsecured(_, timeouts) => *timeouts = new_timeouts,
+            #[cfg(feature = "rustls")]
+            HttpStream::Secured(_, timeouts) => *timeouts = new_timeouts,
+            #[cfg(feature = "async")]
+            HttpStream::Buffer(_) => {}
+        }
+    }
 }
 
-fn timeout_err() -> io::Error {
-    io::Error::new(io::ErrorKind::TimedOut, "the timeout of the request was reached")
+/// The timeouts of a request, see [`Request::with_total_timeout`](crate::Request::with_total_timeout),
+/// [`Request::with_connect_timeout`](crate::Request::with_connect_timeout) and
+/// [`Request::with_read_timeout`](crate::Request::with_read_timeout).
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timeouts {
+    /// The deadline of the whole request.
+    pub(crate) timeout_at: Option<Instant>,
+    pub(crate) connect: Option<Duration>,
+    pub(crate) read: Option<Duration>,
 }
 
-fn timeout_at_to_duration(timeout_at: Option<Instant>) -> Result<Option<Duration>, io::Error> {
-    if let Some(timeout_at) = timeout_at {
-        if let Some(duration) = timeout_at.checked_duration_since(Instant::now()) {
-            Ok(Some(duration))
-        } else {
-            Err(timeout_err())
+impl Timeouts {
+    /// Returns the deadline of the whole request.
+    pub(crate) fn total(&self) -> Option<Deadline> {
+        self.timeout_at.map(|at| Deadline { at, timeout: Timeout::Total })
+    }
+
+    /// Returns the deadline of connecting to the server, starting now.
+    fn connect(&self) -> Option<Deadline> { self.earliest(self.connect, Timeout::Connect) }
+
+    /// Returns the deadline of the next read from the server, starting now.
+    fn read(&self) -> Option<Deadline> { self.earliest(self.read, Timeout::Read) }
+
+    fn earliest(&self, duration: Option<Duration>, timeout: Timeout) -> Option<Deadline> {
+        let deadline = duration
+            .and_then(|duration| Instant::now().checked_add(duration))
+            .map(|at| Deadline { at, timeout });
+        match (deadline, self.total()) {
+            (Some(deadline), Some(total)) if deadline.at < total.at => Some(deadline),
+            (Some(deadline), None) => Some(deadline),
+            (_, total) => total,
         }
-    } else {
-        Ok(None)
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+enum Timeout {
+    Connect,
+    Read,
+    Total,
+}
+
+/// The instant an operation times out at, and which timeout it is.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    at: Instant,
+    timeout: Timeout,
+}
+
+impl Deadline {
+    /// Returns the error of this deadline having been reached.
+    pub(crate) fn error(&self) -> Error {
+        match self.timeout {
+            Timeout::Connect => Error::ConnectTimeout,
+            Timeout::Read => Error::ReadTimeout,
+            Timeout::Total => Error::TotalTimeout,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn instant(&self) -> tokio::time::Instant { self.at.into() }
+}
+
+/// Returns the time left until `deadline`, failing if it was reached.
+fn remaining(deadline: Option<Deadline>) -> io::Result<Option<Duration>> {
+    match deadline {
+        Some(deadline) => match deadline.at.checked_duration_since(Instant::now()) {
+            Some(duration) if !duration.is_zero() => Ok(Some(duration)),
+            _ => Err(deadline.error().into_io_error()),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Replaces `err` with the error of `deadline` if it's how a blocking socket times out.
+fn timed_out(err: io::Error, deadline: Option<Deadline>) -> io::Error {
+    match deadline {
+        Some(deadline)
+            if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) =>
+            deadline.error().into_io_error(),
+        _ => err,
+    }
+}
+
+/// Sets the read and write timeouts of `tcp` to `deadline`.
+#[cfg(any(feature = "proxy", feature = "rustls"))]
+fn set_socket_timeouts(tcp: &TcpStream, deadline: Option<Deadline>) -> io::Result<()> {
+    tcp.set_read_timeout(remaining(deadline)?)?;
+    tcp.set_write_timeout(remaining(deadline)?)
+}
+
 impl Read for HttpStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let timeout = |tcp: &TcpStream, timeout_at: Option<Instant>| -> io::Result<()> {
-            let _ = tcp.set_read_timeout(timeout_at_to_duration(timeout_at)?);
-            Ok(())
+        let timeout = |tcp: &TcpStream, timeouts: &Timeouts| -> io::Result<Option<Deadline>> {
+            let deadline = timeouts.read();
+            let _ = tcp.set_read_timeout(remaining(deadline)?);
+            Ok(deadline)
         };
 
-        let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                timeout(inner, *timeout_at)?;
-                inner.read(buf)
+        let (result, deadline) = match self {
+            HttpStream::Unsecured(inner, timeouts) => {
+                let deadline = timeout(inner, timeouts)?;
+                (inner.read(buf), deadline)
             }
             #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                timeout(inner.get_ref(), *timeout_at)?;
-                inner.read(buf)
+            HttpStream::Secured(inner, timeouts) => {
+                let deadline = timeout(inner.get_ref(), timeouts)?;
+                (inner.read(buf), deadline)
             }
             #[cfg(feature = "async")]
-            HttpStream::Buffer(cursor) => std::io::Read::read(cursor, buf),
+            HttpStream::Buffer(cursor) => (std::io::Read::read(cursor, buf), None),
         };
-        match result {
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                // We're a blocking socket, so EWOULDBLOCK indicates a timeout
-                Err(timeout_err())
-            }
-            r => r,
-        }
+        // We're a blocking socket, so EWOULDBLOCK indicates a timeout
+        result.map_err(|e| timed_out(e, deadline))
     }
 }
 
-fn set_socket_write_timeout(tcp: &TcpStream, timeout_at: Option<Instant>) -> io::Result<()> {
-    tcp.set_write_timeout(timeout_at_to_duration(timeout_at)?)?;
-    Ok(())
+fn set_socket_write_timeout(tcp: &TcpStream, timeouts: &Timeouts) -> io::Result<Option<Deadline>> {
+    let deadline = timeouts.total();
+    tcp.set_write_timeout(remaining(deadline)?)?;
+    Ok(deadline)
 }
 
 impl Write for HttpStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                set_socket_write_timeout(inner, *timeout_at)?;
-                inner.write(buf)
+        let (result, deadline) = match self {
+            HttpStream::Unsecured(inner, timeouts) => {
+                let deadline = set_socket_write_timeout(inner, timeouts)?;
+                (inner.write(buf), deadline)
             }
             #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                set_socket_write_timeout(inner.get_ref(), *timeout_at)?;
-                inner.write(buf)
+            HttpStream::Secured(inner, timeouts) => {
+                let deadline = set_socket_write_timeout(inner.get_ref(), timeouts)?;
+                (inner.write(buf), deadline)
             }
             #[cfg(feature = "async")]
             HttpStream::Buffer(_) => {
                 debug_assert!(false, "We shouldn't write to a pre-loaded stream");
-                Ok(buf.len())
+                (Ok(buf.len()), None)
             }
         };
-        match result {
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                // We're a blocking socket, so EWOULDBLOCK indicates a timeout
-                Err(timeout_err())
-            }
-            r => r,
-        }
+        // We're a blocking socket, so EWOULDBLOCK indicates a timeout
+        result.map_err(|e| timed_out(e, deadline))
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                set_socket_write_timeout(inner, *timeout_at)?;
-                inner.flush()
+        let (result, deadline) = match self {
+            HttpStream::Unsecured(inner, timeouts) => {
+                let deadline = set_socket_write_timeout(inner, timeouts)?;
+                (inner.flush(), deadline)
             }
             #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                set_socket_write_timeout(inner.get_ref(), *timeout_at)?;
-                inner.flush()
+            HttpStream::Secured(inner, timeouts) => {
+                let deadline = set_socket_write_timeout(inner.get_ref(), timeouts)?;
+                (inner.flush(), deadline)
             }
             #[cfg(feature = "async")]
             HttpStream::Buffer(_) => {
                 debug_assert!(false, "We shouldn't write to a pre-loaded stream");
-                Ok(())
+                (Ok(()), None)
             }
         };
-        match result {
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                // We're a blocking socket, so EWOULDBLOCK indicates a timeout
-                Err(timeout_err())
-            }
-            r => r,
-        }
+        // We're a blocking socket, so EWOULDBLOCK indicates a timeout
+        result.map_err(|e| timed_out(e, deadline))
     }
 }
 
@@ -217,6 +302,49 @@ impl AsyncWrite for AsyncHttpStream {
     }
 }
 
+/// A reader failing once the read timeout of a request passed without anything to read.
+#[cfg(feature = "async")]
+struct TimedReader<R> {
+    inner: R,
+    timeouts: Timeouts,
+    /// The timer of the pending read.
+    sleep: Option<(Pin<Box<tokio::time::Sleep>>, Deadline)>,
+}
+
+#[cfg(feature = "async")]
+impl<R> TimedReader<R> {
+    fn new(inner: R, timeouts: Timeouts) -> TimedReader<R> {
+        TimedReader { inner, timeouts, sleep: None }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for TimedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Poll::Ready(result) = Pin::new(&mut this.inner).poll_read(cx, buf) {
+            this.sleep = None;
+            return Poll::Ready(result);
+        }
+        if this.sleep.is_none() {
+            // The deadline of the whole request is enforced by the caller.
+            let Some(deadline) = this.timeouts.read.and_then(|_| this.timeouts.read()) else {
+                return Poll::Pending;
+            };
+            this.sleep = Some((Box::pin(tokio::time::sleep_until(deadline.instant())), deadline));
+        }
+        let (sleep, deadline) = this.sleep.as_mut().unwrap();
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(deadline.error().into_io_error())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 /// The starting value of [`AsyncConnectionState::permits`].
 #[cfg(feature = "async")]
 const MAX_SEND_PERMITS: isize = isize::MAX;
@@ -247,6 +375,8 @@ struct AsyncConnectionState {
     /// Defaults to 60 seconds after open to align with nginx's default timeout of 75 seconds, but
     /// can be overridden by the `Keep-Alive` header.
     socket_new_requests_timeout: Mutex<Instant>,
+    /// The address this connection was made to.
+    remote_addr: Option<SocketAddr>,
 }
 
 #[cfg(feature = "async")]
@@ -274,27 +404,29 @@ impl AsyncConnection {
     /// Creates a new `AsyncConnection`.
     pub(crate) async fn new(
         params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
+        timeouts: Timeouts,
     ) -> Result<AsyncConnection, Error> {
         let future = async move {
             let socket = Self::connect(params).await?;
+            let remote_addr = socket.peer_addr().ok();
 
-            if params.https {
+            let stream = if params.https {
                 #[cfg(not(any(
                     feature = "async-https-rustls",
                     feature = "async-https-rustls-probe"
                 )))]
                 return Err(Error::HttpsFeatureNotEnabled);
                 #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
-                rustls_stream::wrap_async_stream(socket, params.host).await
+                rustls_stream::wrap_async_stream(socket, params.host, params.tls).await?
             } else {
-                Ok(AsyncHttpStream::Unsecured(socket))
-            }
+                AsyncHttpStream::Unsecured(socket)
+            };
+            Ok((stream, remote_addr))
         };
-        let stream = if let Some(timeout_at) = timeout_at {
-            tokio::time::timeout_at(timeout_at.into(), future)
+        let (stream, remote_addr) = if let Some(deadline) = timeouts.connect() {
+            tokio::time::timeout_at(deadline.instant(), future)
                 .await
-                .unwrap_or(Err(Error::IoError(timeout_err())))?
+                .unwrap_or_else(|_| Err(deadline.error()))?
         } else {
             future.await?
         };
@@ -311,6 +443,7 @@ impl AsyncConnection {
             readable_request_id: AtomicUsize::new(0),
             min_dropped_reader_id: AtomicUsize::new(usize::MAX),
             socket_new_requests_timeout: Mutex::new(Instant::now() + Duration::from_secs(60)),
+            remote_addr,
         }))))
     }
 
@@ -319,33 +452,54 @@ impl AsyncConnection {
         log::trace!("Looking up host {host}");
 
         let addrs = tokio::net::lookup_host((host, port)).await.map_err(Error::IoError)?;
-        let addrs: Vec<_> = addrs.collect();
-        let addrs_count = addrs.len();
-
-        if addrs.is_empty() {
-            return Err(Error::AddressNotFound);
-        }
-
-        // Try all resolved addresses. Return the first one to which we could connect. If all
-        // failed return the last error encountered.
-        for (i, addr) in addrs.iter().enumerate() {
-            #[cfg(feature = "log")]
-            log::trace!("Attempting to connect to {addr} for {host}");
+        Self::connect_any(addrs.collect()).await
+    }
 
-            match AsyncTcpStream::connect(addr).await {
-                Ok(s) => {
-                    #[cfg(feature = "log")]
-                    log::trace!("Connected to {addr} for {host}");
-                    return Ok(s);
+    /// Connects to the first of `addrs` which accepts a connection.
+    async fn connect_any(addrs: Vec<SocketAddr>) -> Result<AsyncTcpStream, Error> {
+        let mut addrs = VecDeque::from(interleave_families(addrs));
+
+        // Race the resolved addresses as described in RFC 8305, starting the next attempt once
+        // the previous one failed or after `CONNECTION_ATTEMPT_DELAY`. Return the first
+        // connection made. If all failed return the last error encountered.
+        let mut attempts: Vec<Pin<Box<dyn Future<Output = io::Result<AsyncTcpStream>> + Send>>> =
+            Vec::new();
+        let mut delay = Box::pin(tokio::time::sleep(Duration::ZERO));
+        let mut error = None;
+        std::future::poll_fn(|cx| loop {
+            let mut i = 0;
+            while i < attempts.len() {
+                match attempts[i].as_mut().poll(cx) {
+                    Poll::Ready(Ok(stream)) => {
+                        #[cfg(feature = "log")]
+                        log::trace!("Connected to {:?}", stream.peer_addr());
+                        return Poll::Ready(Ok(stream));
+                    }
+                    Poll::Ready(Err(e)) => {
+                        drop(attempts.remove(i));
+                        error = Some(e);
+                    }
+                    Poll::Pending => i += 1,
                 }
-                Err(e) =>
-                    if i == addrs_count - 1 {
-                        return Err(Error::IoError(e));
-                    },
             }
-        }
 
-        Err(Error::AddressNotFound)
+            let start_next =
+                !addrs.is_empty() && (attempts.is_empty() || delay.as_mut().poll(cx).is_ready());
+            if start_next {
+                let addr = addrs.pop_front().unwrap();
+                #[cfg(feature = "log")]
+                log::trace!("Attempting to connect to {addr}");
+                attempts.push(Box::pin(AsyncTcpStream::connect(addr)));
+                delay.as_mut().reset(tokio::time::Instant::now() + CONNECTION_ATTEMPT_DELAY);
+            } else if attempts.is_empty() {
+                return Poll::Ready(Err(error
+                    .take()
+                    .map_or(Error::AddressNotFound, Error::IoError)));
+            } else {
+                return Poll::Pending;
+            }
+        })
+        .await
     }
 
     /// Asynchronously connect to the server.
@@ -356,6 +510,17 @@ impl AsyncConnection {
                 // do proxy things
                 let mut tcp = Self::tcp_connect(&proxy.server, proxy.port).await?;
 
+                if let ProxyKind::Socks5 { .. } = proxy.kind {
+                    let addr = if proxy.resolves_locally() {
+                        let mut addrs = tokio::net::lookup_host((params.host, params.port)).await?;
+                        Some(addrs.next().ok_or(Error::AddressNotFound)?)
+                    } else {
+                        None
+                    };
+                    Self::socks5_handshake(&mut tcp, proxy, params, addr).await?;
+                    return Ok(tcp);
+                }
+
                 let proxy_request = proxy.connect(params.host, params.port);
                 tcp.write_all(proxy_request.as_bytes()).await?;
                 tcp.flush().await?;
@@ -392,9 +557,60 @@ impl AsyncConnection {
         Self::tcp_connect(&params.host, params.port).await
     }
 
-    async fn timeout<O, F: Future<Output = O>>(timeout: Option<Instant>, f: F) -> Result<O, Error> {
-        if let Some(time) = timeout {
-            tokio::time::timeout_at(time.into(), f).await.map_err(|_| Error::IoError(timeout_err()))
+    /// Returns whether this connection can't be used for new requests, eg. because the server
+    /// closed it while it was idle. Connections with requests in flight are assumed open.
+    pub(crate) async fn is_closed(&self) -> bool {
+        let conn = Arc::clone(&*self.0.lock().unwrap());
+        if conn.permits.load(Ordering::Acquire) <= 0
+            || Instant::now() > *conn.socket_new_requests_timeout.lock().unwrap()
+        {
+            return true;
+        }
+        let Ok(mut read) = conn.read.try_lock() else { return false };
+        if conn.readable_request_id.load(Ordering::Acquire)
+            != conn.next_request_id.load(Ordering::Acquire)
+        {
+            return false;
+        }
+        // An idle connection has nothing to read: it was closed if it's readable.
+        std::future::poll_fn(|cx| match Pin::new(&mut *read).poll_fill_buf(cx) {
+            Poll::Pending => Poll::Ready(false),
+            Poll::Ready(_) => Poll::Ready(true),
+        })
+        .await
+    }
+
+    /// Asks the SOCKS5 `proxy` to connect to the server, at `addr` if it was resolved locally.
+    #[cfg(feature = "proxy")]
+    async fn socks5_handshake(
+        tcp: &mut AsyncTcpStream,
+        proxy: &Proxy,
+        params: ConnectionParams<'_>,
+        addr: Option<SocketAddr>,
+    ) -> Result<(), Error> {
+        tcp.write_all(&proxy.socks5_greeting()).await?;
+        let mut reply = [0; 2];
+        tcp.read_exact(&mut reply).await?;
+        if let Some(auth) = proxy.socks5_auth(reply)? {
+            tcp.write_all(&auth).await?;
+            tcp.read_exact(&mut reply).await?;
+            Proxy::socks5_verify_auth(reply)?;
+        }
+
+        tcp.write_all(&Proxy::socks5_connect(params.host, params.port, addr)?).await?;
+        let mut reply = [0; 5];
+        tcp.read_exact(&mut reply).await?;
+        let mut bound_addr = vec![0; Proxy::socks5_verify_connect(reply)?];
+        tcp.read_exact(&mut bound_addr).await?;
+        Ok(())
+    }
+
+    async fn timeout<O, F: Future<Output = O>>(
+        deadline: Option<Deadline>,
+        f: F,
+    ) -> Result<O, Error> {
+        if let Some(deadline) = deadline {
+            tokio::time::timeout_at(deadline.instant(), f).await.map_err(|_| deadline.error())
         } else {
             Ok(f.await)
         }
@@ -431,8 +647,8 @@ impl AsyncConnection {
                 // new connection (because `permits` is exhausted/poisoned) or there are no
                 // pending readers (because `next_request_id` and `readable_request_id` are the
                 // same).
-                read = Some(Self::timeout(request.timeout_at, conn.read.lock()).await?);
-                write = Some(Self::timeout(request.timeout_at, conn.write.lock()).await?);
+                read = Some(Self::timeout(request.timeouts().total(), conn.read.lock()).await?);
+                write = Some(Self::timeout(request.timeouts().total(), conn.write.lock()).await?);
                 while {
                     let next_read = conn.readable_request_id.load(Ordering::Relaxed);
                     let next_request = conn.next_request_id.load(Ordering::Relaxed);
@@ -443,8 +659,9 @@ impl AsyncConnection {
                     read.take();
                     write.take();
                     tokio::task::yield_now().await;
-                    read = Some(Self::timeout(request.timeout_at, conn.read.lock()).await?);
-                    write = Some(Self::timeout(request.timeout_at, conn.write.lock()).await?);
+                    read = Some(Self::timeout(request.timeouts().total(), conn.read.lock()).await?);
+                    write =
+                        Some(Self::timeout(request.timeouts().total(), conn.write.lock()).await?);
                 }
             }
 
@@ -465,7 +682,7 @@ impl AsyncConnection {
                 };
                 (_internal) => {
                     let new_connection =
-                        AsyncConnection::new(request.connection_params(), request.timeout_at)
+                        AsyncConnection::new(request.connection_params(), request.timeouts())
                             .await?;
                     *self.0.lock().unwrap() = Arc::clone(&*new_connection.0.lock().unwrap());
                     core::mem::drop(read);
@@ -483,7 +700,7 @@ impl AsyncConnection {
                 let mut write = if let Some(write) = write {
                     write
                 } else {
-                    Self::timeout(request.timeout_at, conn.write.lock()).await?
+                    Self::timeout(request.timeouts().total(), conn.write.lock()).await?
                 };
 
                 let socket_timeout = *conn.socket_new_requests_timeout.lock().unwrap();
@@ -503,7 +720,8 @@ impl AsyncConnection {
                 );
                 this_request.id = Some(request_id);
                 let write_res =
-                    Self::timeout(request.timeout_at, write.write_all(&request.as_bytes())).await;
+                    Self::timeout(request.timeouts().total(), request.write_to_async(&mut *write))
+                        .await;
                 match write_res {
                     Err(e) => {
                         // If we failed to write the request, mark the socket as dead for future
@@ -511,18 +729,18 @@ impl AsyncConnection {
                         conn.permits.store(0, Ordering::Release);
                         return Err(e);
                     }
-                    Ok(Err(ioe)) => {
+                    Ok(Err(e)) => {
                         conn.permits.store(0, Ordering::Release);
-                        return Err(Error::IoError(ioe));
+                        return Err(e);
                     }
                     Ok(Ok(())) => {}
                 }
             }
 
             let mut should_retry = false;
-            let response = Self::timeout(request.timeout_at, async {
+            let response = Self::timeout(request.timeouts().total(), async {
                 if read.is_none() {
-                    read = Some(Self::timeout(request.timeout_at, conn.read.lock()).await?);
+                    read = Some(Self::timeout(request.timeouts().total(), conn.read.lock()).await?);
                 }
 
                 while {
@@ -567,7 +785,7 @@ impl AsyncConnection {
                 );
 
                 let response = Response::create_async(
-                    &mut *read,
+                    TimedReader::new(&mut *read, request.timeouts()),
                     request.config.method == Method::Head,
                     request.config.max_headers_size,
                     request.config.max_status_line_len,
@@ -633,7 +851,7 @@ impl AsyncConnection {
                 Ok(response)
             })
             .await;
-            let response = match response {
+            let mut response = match response {
                 Ok(Ok(response)) => response,
                 Err(e) | Ok(Err(e)) => {
                     if should_retry {
@@ -650,15 +868,73 @@ impl AsyncConnection {
             };
 
             core::mem::drop(read);
+            response.remote_addr = conn.remote_addr;
             async_handle_redirects(self, request, response).await
         })
     }
 }
 
+#[cfg(feature = "async")]
+impl AsyncConnection {
+    /// Sends the [`Request`](struct.Request.html) on this new connection, consuming it, and
+    /// returns the response once its headers were read. Redirections are followed on new
+    /// connections.
+    pub(crate) fn send_streaming(
+        self,
+        request: ParsedRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<AsyncResponseLazy, Error>> + Send>> {
+        Box::pin(async move {
+            let conn = self.0.into_inner().unwrap();
+            let AsyncConnectionState { read, write, remote_addr, .. } = Arc::try_unwrap(conn)
+                .map_err(|_| Error::Other("streaming a response over a shared connection"))?;
+            let mut write = write.into_inner();
+            Self::timeout(request.timeouts().total(), async {
+                request.write_to_async(&mut write).await?;
+                Ok::<_, Error>(write.flush().await?)
+            })
+            .await??;
+
+            let mut response = Self::timeout(
+                request.timeouts().total(),
+                AsyncResponseLazy::create(
+                    Box::pin(TimedReader::new(read.into_inner(), request.timeouts())),
+                    request.config.method == Method::Head,
+                    request.config.max_headers_size,
+                    request.config.max_status_line_len,
+                    request.config.max_body_size,
+                    request.timeout_at,
+                ),
+            )
+            .await??;
+            response.remote_addr = remote_addr;
+
+            let status_code = response.status_code;
+            let url = response.headers.get("location");
+            match async_get_redirect(request, status_code, url) {
+                NextHopAsync::Redirect(request) => {
+                    let (request, _) = request?;
+                    AsyncConnection::new(request.connection_params(), request.timeouts())
+                        .await?
+                        .send_streaming(request)
+                        .await
+                }
+                NextHopAsync::Destination(request) => {
+                    let mut response = response;
+                    let dst_url = request.url;
+                    dst_url.write_base_url_to(&mut response.url).unwrap();
+                    dst_url.write_resource_to(&mut response.url).unwrap();
+                    Ok(response)
+                }
+            }
+        })
+    }
+}
+
 /// A connection to the server for sending
 /// [`Request`](struct.Request.html)s.
 pub struct Connection {
     stream: HttpStream,
+    remote_addr: Option<SocketAddr>,
 }
 
 impl Connection {
@@ -666,70 +942,142 @@ impl Connection {
     /// for specifics about *what* is being sent.
     pub(crate) fn new(
         params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
+        timeouts: Timeouts,
     ) -> Result<Connection, Error> {
-        let socket = Self::connect(params, timeout_at)?;
+        let deadline = timeouts.connect();
+        let socket = Self::connect(params, deadline).map_err(|e| match e {
+            Error::IoError(e) => Error::from(timed_out(e, deadline)),
+            e => e,
+        })?;
+        let remote_addr = socket.peer_addr().ok();
 
         let stream = if params.https {
             #[cfg(not(feature = "rustls"))]
             return Err(Error::HttpsFeatureNotEnabled);
             #[cfg(feature = "rustls")]
             {
-                let tls = rustls_stream::wrap_stream(socket, params.host)?;
-                HttpStream::Secured(Box::new(tls), timeout_at)
+                let mut tls = rustls_stream::wrap_stream(socket, params.host, params.tls)?;
+                set_socket_timeouts(tls.get_ref(), deadline)?;
+                rustls_stream::handshake(&mut tls).map_err(|e| timed_out(e, deadline))?;
+                HttpStream::Secured(Box::new(tls), timeouts)
             }
         } else {
-            HttpStream::create_unsecured(socket, timeout_at)
+            HttpStream::create_unsecured(socket, timeouts)
         };
 
-        Ok(Connection { stream })
+        Ok(Connection { stream, remote_addr })
     }
 
-    fn tcp_connect(host: &str, port: u16, timeout_at: Option<Instant>) -> Result<TcpStream, Error> {
+    /// Resolves `host`, giving up at `deadline`.
+    fn resolve(
+        host: &str,
+        port: u16,
+        deadline: Option<Deadline>,
+    ) -> Result<Vec<SocketAddr>, Error> {
         #[cfg(feature = "log")]
         log::trace!("Looking up host {host}");
 
-        let addrs = (host, port).to_socket_addrs().map_err(Error::IoError)?;
-        let addrs_count = addrs.len();
+        // The lookup can't be given a timeout, so it's enforced with a thread.
+        let host = host.to_owned();
+        enforce_timeout(deadline, move || Ok((host, port).to_socket_addrs()?.collect()))
+    }
 
-        // Try all resolved addresses. Return the first one to which we could connect. If all
-        // failed return the last error encountered.
-        for (i, addr) in addrs.enumerate() {
-            #[cfg(feature = "log")]
-            log::trace!("Attempting to connect to {addr} for {host}");
+    fn tcp_connect(host: &str, port: u16, deadline: Option<Deadline>) -> Result<TcpStream, Error> {
+        let addrs = Self::resolve(host, port, deadline)?;
+        Self::connect_any(addrs, deadline)
+    }
 
-            let stream = if let Some(timeout) = timeout_at_to_duration(timeout_at)? {
+    /// Connects to the first of `addrs` which accepts a connection, giving up at `deadline`.
+    fn connect_any(addrs: Vec<SocketAddr>, deadline: Option<Deadline>) -> Result<TcpStream, Error> {
+        use std::sync::mpsc::channel;
+
+        let connect = |addr: SocketAddr, timeout: Option<Duration>| {
+            if let Some(timeout) = timeout {
                 TcpStream::connect_timeout(&addr, timeout)
             } else {
                 TcpStream::connect(addr)
-            };
+            }
+        };
+        if let [addr] = addrs[..] {
+            #[cfg(feature = "log")]
+            log::trace!("Attempting to connect to {addr}");
+            return Ok(connect(addr, remaining(deadline)?)?);
+        }
 
-            match stream {
-                Ok(s) => {
+        // Race the resolved addresses as described in RFC 8305, on up to
+        // `MAX_BLOCKING_CONNECTION_ATTEMPTS` threads, starting the next attempt once the previous
+        // one failed or after `CONNECTION_ATTEMPT_DELAY`. Return the first connection made. If all
+        // failed return the last error encountered.
+        let mut addrs = VecDeque::from(interleave_families(addrs));
+        let (sender, receiver) = channel();
+        let mut pending = 0;
+        let mut error = None;
+        loop {
+            if pending < MAX_BLOCKING_CONNECTION_ATTEMPTS {
+                if let Some(addr) = addrs.pop_front() {
                     #[cfg(feature = "log")]
-                    log::trace!("Connected to {addr} for {host}");
-                    return Ok(s);
+                    log::trace!("Attempting to connect to {addr}");
+                    let timeout = remaining(deadline)?;
+                    let sender = sender.clone();
+                    std::thread::spawn(move || {
+                        let _ = sender.send(connect(addr, timeout));
+                    });
+                    pending += 1;
                 }
-                Err(e) =>
-                    if i == addrs_count - 1 {
-                        return Err(Error::IoError(e));
-                    },
             }
-        }
+            if pending == 0 {
+                return Err(error.map_or(Error::AddressNotFound, Error::IoError));
+            }
 
-        Err(Error::AddressNotFound)
+            // Wait for an attempt to finish, or until the next one is due.
+            let mut wait = remaining(deadline)?;
+            if pending < MAX_BLOCKING_CONNECTION_ATTEMPTS && !addrs.is_empty() {
+                wait = Some(
+                    wait.map_or(CONNECTION_ATTEMPT_DELAY, |w| w.min(CONNECTION_ATTEMPT_DELAY)),
+                );
+            }
+            let result = match wait {
+                Some(wait) => receiver.recv_timeout(wait).ok(),
+                // We hold a sender, so this can't fail.
+                None => receiver.recv().ok(),
+            };
+            match result {
+                Some(Ok(stream)) => {
+                    #[cfg(feature = "log")]
+                    log::trace!("Connected to {:?}", stream.peer_addr());
+                    return Ok(stream);
+                }
+                Some(Err(e)) => {
+                    pending -= 1;
+                    error = Some(e);
+                }
+                None => {}
+            }
+        }
     }
 
-    /// Connect to the server.
+    /// Connect to the server, giving up at `deadline`.
     fn connect(
         params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
+        deadline: Option<Deadline>,
     ) -> Result<TcpStream, Error> {
         #[cfg(feature = "proxy")]
         match &params.proxy {
             Some(proxy) => {
                 // do proxy things
-                let mut tcp = Self::tcp_connect(&proxy.server, proxy.port, timeout_at)?;
+                let mut tcp = Self::tcp_connect(&proxy.server, proxy.port, deadline)?;
+                set_socket_timeouts(&tcp, deadline)?;
+
+                if let ProxyKind::Socks5 { .. } = proxy.kind {
+                    let addr = if proxy.resolves_locally() {
+                        let addrs = Self::resolve(params.host, params.port, deadline)?;
+                        Some(*addrs.first().ok_or(Error::AddressNotFound)?)
+                    } else {
+                        None
+                    };
+                    Self::socks5_handshake(&mut tcp, proxy, params, addr)?;
+                    return Ok(tcp);
+                }
 
                 write!(tcp, "{}", proxy.connect(params.host, params.port))?;
                 tcp.flush()?;
@@ -759,34 +1107,125 @@ impl Connection {
 
                 Ok(tcp)
             }
-            None => Self::tcp_connect(params.host, params.port, timeout_at),
+            None => Self::tcp_connect(params.host, params.port, deadline),
         }
 
         #[cfg(not(feature = "proxy"))]
-        Self::tcp_connect(params.host, params.port, timeout_at)
+        Self::tcp_connect(params.host, params.port, deadline)
+    }
+
+    /// Asks the SOCKS5 `proxy` to connect to the server, at `addr` if it was resolved locally.
+    #[cfg(feature = "proxy")]
+    fn socks5_handshake(
+        tcp: &mut TcpStream,
+        proxy: &Proxy,
+        params: ConnectionParams<'_>,
+        addr: Option<SocketAddr>,
+    ) -> Result<(), Error> {
+        tcp.write_all(&proxy.socks5_greeting())?;
+        let mut reply = [0; 2];
+        tcp.read_exact(&mut reply)?;
+        if let Some(auth) = proxy.socks5_auth(reply)? {
+            tcp.write_all(&auth)?;
+            tcp.read_exact(&mut reply)?;
+            Proxy::socks5_verify_auth(reply)?;
+        }
+
+        tcp.write_all(&Proxy::socks5_connect(params.host, params.port, addr)?)?;
+        let mut reply = [0; 5];
+        tcp.read_exact(&mut reply)?;
+        let mut bound_addr = vec![0; Proxy::socks5_verify_connect(reply)?];
+        tcp.read_exact(&mut bound_addr)?;
+        Ok(())
     }
 
     /// Sends the [`Request`](struct.Request.html), consumes this
     /// connection, and returns a [`Response`](struct.Response.html).
     pub(crate) fn send(mut self, request: ParsedRequest) -> Result<ResponseLazy, Error> {
-        enforce_timeout(request.timeout_at, move || {
+        enforce_timeout(request.timeouts().total(), move || {
             // Send request
             #[cfg(feature = "log")]
             log::trace!("Writing HTTP request.");
-            self.stream.write_all(&request.as_bytes())?;
+            request.write_to(&mut self.stream)?;
 
             // Receive response
             #[cfg(feature = "log")]
             log::trace!("Reading HTTP response.");
-            let response = ResponseLazy::from_stream(
+            let mut response = ResponseLazy::from_stream(
                 self.stream,
                 request.config.max_headers_size,
                 request.config.max_status_line_len,
                 request.config.max_body_size,
             )?;
+            response.remote_addr = self.remote_addr;
             handle_redirects(request, response)
         })
     }
+
+    /// Sends the [`Request`](struct.Request.html) and reads the whole
+    /// [`Response`](struct.Response.html), without following redirects.
+    /// This connection is returned too if the server keeps it open.
+    ///
+    /// Fails in the outer result if the server didn't send any of its
+    /// response, eg. with [`io::ErrorKind::UnexpectedEof`] if it closed
+    /// the connection because it was idle, and in the inner one if
+    /// reading the response failed.
+    pub(crate) fn send_keep_alive(
+        self,
+        request: &ParsedRequest,
+    ) -> Result<Result<(Response, Option<Connection>), Error>, Error> {
+        let mut stream = self.stream;
+        stream.set_timeouts(request.timeouts());
+        #[cfg(feature = "log")]
+        log::trace!("Writing HTTP request.");
+        request.write_to(&mut stream)?;
+        stream.flush()?;
+
+        #[cfg(feature = "log")]
+        log::trace!("Reading HTTP response.");
+        let mut reader =
+            io::BufReader::with_capacity(crate::response::BACKING_READ_BUFFER_LENGTH, stream);
+        if io::BufRead::fill_buf(&mut reader)?.is_empty() {
+            return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let remote_addr = self.remote_addr;
+        Ok(Self::read_keep_alive(reader, request, remote_addr))
+    }
+
+    fn read_keep_alive(
+        reader: io::BufReader<HttpStream>,
+        request: &ParsedRequest,
+        remote_addr: Option<SocketAddr>,
+    ) -> Result<(Response, Option<Connection>), Error> {
+        let response = ResponseLazy::from_reader(
+            reader,
+            request.config.max_headers_size,
+            request.config.max_status_line_len,
+            request.config.max_body_size,
+        )?;
+        let is_head = request.config.method == Method::Head;
+        let (mut response, stream) =
+            Response::create_keep_alive(response, is_head, request.config.max_body_size)?;
+        response.remote_addr = remote_addr;
+        Ok((response, stream.map(|stream| Connection { stream, remote_addr })))
+    }
+}
+
+/// Returns the request following the redirection `response` is, if any, or sets the URL of the
+/// final `response`.
+pub(crate) fn next_hop(
+    request: ParsedRequest,
+    response: &mut Response,
+) -> Result<Option<ParsedRequest>, Error> {
+    match get_redirect(request, response.status_code, response.headers.get("location")) {
+        NextHop::Redirect(request) => Ok(Some(request?.0)),
+        NextHop::Destination(request) => {
+            let dst_url = request.url;
+            dst_url.write_base_url_to(&mut response.url).unwrap();
+            dst_url.write_resource_to(&mut response.url).unwrap();
+            Ok(None)
+        }
+    }
 }
 
 fn handle_redirects(
@@ -798,7 +1237,7 @@ fn handle_redirects(
     match get_redirect(request, status_code, url) {
         NextHop::Redirect(request) => {
             let (request, _) = request?;
-            Connection::new(request.connection_params(), request.timeout_at)?.send(request)
+            Connection::new(request.connection_params(), request.timeouts())?.send(request)
         }
         NextHop::Destination(request) => {
             let dst_url = request.url;
@@ -824,7 +1263,7 @@ async fn async_handle_redirects(
             let new_connection;
             if needs_new_connection {
                 new_connection =
-                    AsyncConnection::new(request.connection_params(), request.timeout_at).await?;
+                    AsyncConnection::new(request.connection_params(), request.timeouts()).await?;
                 connection = &new_connection;
             }
             connection.send(request).await
@@ -868,6 +1307,11 @@ macro_rules! redirect_utils {
                                 match request.config.method {
                                     Method::Post | Method::Put | Method::Delete => {
                                         request.config.method = Method::Get;
+                                        // A streamed body was already sent and can't be
+                                        // resent, but isn't needed for the GET anyway.
+                                        if !request.is_replayable() {
+                                            request.config.body = None;
+                                        }
                                     }
                                     _ => {}
                                 }
@@ -889,20 +1333,36 @@ redirect_utils!(get_redirect, NextHop, ResponseLazy);
 #[cfg(feature = "async")]
 redirect_utils!(async_get_redirect, NextHopAsync, Response);
 
+/// Orders `addrs` alternating between IPv6 and IPv4 addresses, starting with the family of the
+/// first one, so that connection attempts to an unreachable family don't hold up the other one.
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (first, second): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut addrs = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter(), second.into_iter());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return addrs,
+            (a, b) => addrs.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
 /// Enforce the timeout by running the function in a new thread and
 /// parking the current one with a timeout.
 ///
 /// While bitreq does use timeouts (somewhat) properly, some
 /// interfaces such as [ToSocketAddrs] don't allow for specifying the
 /// timeout. Hence this.
-fn enforce_timeout<F, R>(timeout_at: Option<Instant>, f: F) -> Result<R, Error>
+fn enforce_timeout<F, R>(deadline: Option<Deadline>, f: F) -> Result<R, Error>
 where
     F: 'static + Send + FnOnce() -> Result<R, Error>,
     R: 'static + Send,
 {
     use std::sync::mpsc::{channel, RecvTimeoutError};
 
-    match timeout_at {
+    match deadline {
         Some(deadline) => {
             let (sender, receiver) = channel();
             let thread = std::thread::spawn(move || {
@@ -910,19 +1370,83 @@ where
                 let _ = sender.send(());
                 result
             });
-            if let Some(timeout_duration) = deadline.checked_duration_since(Instant::now()) {
+            if let Some(timeout_duration) = deadline.at.checked_duration_since(Instant::now()) {
                 match receiver.recv_timeout(timeout_duration) {
                     Ok(()) => thread.join().unwrap(),
                     Err(err) => match err {
-                        RecvTimeoutError::Timeout => Err(Error::IoError(timeout_err())),
+                        RecvTimeoutError::Timeout => Err(deadline.error()),
                         RecvTimeoutError::Disconnected =>
                             Err(Error::Other("request connection paniced")),
                     },
                 }
             } else {
-                Err(Error::IoError(timeout_err()))
+                Err(deadline.error())
             }
         }
         None => f(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{SocketAddr, TcpListener};
+
+    use super::{interleave_families, Connection};
+    use crate::Error;
+
+    fn parse(addrs: &[&str]) -> Vec<SocketAddr> {
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    /// Returns a listener, and an address nothing listens on.
+    fn listener() -> (TcpListener, SocketAddr) {
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        (TcpListener::bind("127.0.0.1:0").unwrap(), refused)
+    }
+
+    #[test]
+    fn interleaves_address_families() {
+        let addrs = parse(&["[::1]:80", "[::2]:80", "[::3]:80", "1.0.0.1:80", "1.0.0.2:80"]);
+        let expected = parse(&["[::1]:80", "1.0.0.1:80", "[::2]:80", "1.0.0.2:80", "[::3]:80"]);
+        assert_eq!(interleave_families(addrs), expected);
+
+        let addrs = parse(&["1.0.0.1:80", "1.0.0.2:80", "[::1]:80"]);
+        let expected = parse(&["1.0.0.1:80", "[::1]:80", "1.0.0.2:80"]);
+        assert_eq!(interleave_families(addrs), expected);
+
+        assert_eq!(interleave_families(Vec::new()), Vec::new());
+    }
+
+    #[test]
+    fn connects_to_any_address() {
+        let (listener, refused) = listener();
+        let addr = listener.local_addr().unwrap();
+        for addrs in [vec![addr], vec![refused, addr], vec![refused, refused, refused, addr]] {
+            let stream = Connection::connect_any(addrs, None).unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+        }
+
+        let result = Connection::connect_any(vec![refused, refused, refused], None);
+        assert!(matches!(result, Err(Error::IoError(_))));
+        let result = Connection::connect_any(Vec::new(), None);
+        assert!(matches!(result, Err(Error::AddressNotFound)));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_connects_to_any_address() {
+        use super::AsyncConnection;
+
+        let (listener, refused) = listener();
+        let addr = listener.local_addr().unwrap();
+        for addrs in [vec![addr], vec![refused, addr], vec![refused, refused, refused, addr]] {
+            let stream = AsyncConnection::connect_any(addrs).await.unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+        }
+
+        let result = AsyncConnection::connect_any(vec![refused, refused, refused]).await;
+        assert!(matches!(result, Err(Error::IoError(_))));
+        let result = AsyncConnection::connect_any(Vec::new()).await;
+        assert!(matches!(result, Err(Error::AddressNotFound)));
+    }
+}
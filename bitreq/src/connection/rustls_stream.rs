@@ -25,6 +25,8 @@ use super::{AsyncHttpStream, AsyncTcpStream};
 #[cfg(all(feature = "native-tls", not(feature = "rustls"), feature = "tokio-native-tls"))]
 use super::{AsyncHttpStream, AsyncTcpStream};
 use crate::Error;
+#[cfg(feature = "rustls")]
+use crate::TlsConfig;
 
 #[cfg(feature = "rustls")]
 pub type SecuredStream = StreamOwned<ClientConnection, TcpStream>;
@@ -32,8 +34,9 @@ pub type SecuredStream = StreamOwned<ClientConnection, TcpStream>;
 #[cfg(feature = "rustls")]
 static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
 
+/// Returns the root certificates trusted by default, depending on the enabled features.
 #[cfg(feature = "rustls")]
-fn build_client_config() -> Arc<ClientConfig> {
+pub(crate) fn default_root_certificates() -> RootCertStore {
     let mut root_certificates = RootCertStore::empty();
 
     #[cfg(feature = "https-rustls-probe")]
@@ -44,19 +47,38 @@ fn build_client_config() -> Arc<ClientConfig> {
     #[cfg(feature = "webpki-roots")]
     root_certificates.extend(TLS_SERVER_ROOTS.iter().cloned());
 
-    let config =
-        ClientConfig::builder().with_root_certificates(root_certificates).with_no_client_auth();
+    root_certificates
+}
+
+#[cfg(feature = "rustls")]
+fn build_client_config() -> Arc<ClientConfig> {
+    let config = ClientConfig::builder()
+        .with_root_certificates(default_root_certificates())
+        .with_no_client_auth();
     Arc::new(config)
 }
 
+/// Returns the rustls configuration of connections using `tls`, or the default one.
 #[cfg(feature = "rustls")]
-pub(super) fn wrap_stream(tcp: TcpStream, host: &str) -> Result<SecuredStream, Error> {
+fn client_config(tls: Option<&TlsConfig>) -> Result<Arc<ClientConfig>, Error> {
+    match tls {
+        Some(tls) => tls.client_config(),
+        None => Ok(CONFIG.get_or_init(build_client_config).clone()),
+    }
+}
+
+#[cfg(feature = "rustls")]
+pub(super) fn wrap_stream(
+    tcp: TcpStream,
+    host: &str,
+    tls: Option<&TlsConfig>,
+) -> Result<SecuredStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
     let dns_name = ServerName::try_from(host)
         .map(|name| name.to_owned())
         .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::Other, err)))?;
-    let sess = ClientConnection::new(CONFIG.get_or_init(build_client_config).clone(), dns_name)
+    let sess = ClientConnection::new(client_config(tls)?, dns_name)
         .map_err(Error::RustlsCreateConnection)?;
 
     #[cfg(feature = "log")]
@@ -64,6 +86,15 @@ pub(super) fn wrap_stream(tcp: TcpStream, host: &str) -> Result<SecuredStream, E
     Ok(StreamOwned::new(sess, tcp))
 }
 
+/// Completes the TLS handshake of `stream`, which is otherwise done by its first read or write.
+#[cfg(feature = "rustls")]
+pub(super) fn handshake(stream: &mut SecuredStream) -> io::Result<()> {
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    Ok(())
+}
+
 // Async rustls TLS implementation
 
 #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
@@ -73,6 +104,7 @@ pub type AsyncSecuredStream = TlsStream<tokio::net::TcpStream>;
 pub(super) async fn wrap_async_stream(
     tcp: AsyncTcpStream,
     host: &str,
+    tls: Option<&TlsConfig>,
 ) -> Result<AsyncHttpStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
@@ -80,7 +112,7 @@ pub(super) async fn wrap_async_stream(
         .map(|name| name.to_owned())
         .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::Other, err)))?;
 
-    let connector = TlsConnector::from(CONFIG.get_or_init(build_client_config).clone());
+    let connector = TlsConnector::from(client_config(tls)?);
 
     #[cfg(feature = "log")]
     log::trace!("Establishing TLS session to {host}.");
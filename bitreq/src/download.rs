@@ -0,0 +1,275 @@
+//! Resumable downloads of response bodies to files.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+
+use crate::{Error, Request, ResponseLazy};
+
+const BUFFER_LENGTH: usize = 16 * 1024;
+
+type Progress = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// A download of the response body of a GET request to a file, which
+/// resumes where it stopped if interrupted.
+///
+/// The body is written to `<path>.part` as it is read, and moved to
+/// `path` once complete. If a download is interrupted, the next attempt
+/// only requests the missing part with a `Range` header, with an
+/// `If-Range` header so the server sends the whole body again if it
+/// changed. The `ETag` or `Last-Modified` header this relies on is
+/// kept in `<path>.part.validator`, so downloads also resume after the
+/// program was restarted.
+///
+/// Redirections are followed, and the request's timeout applies to each
+/// attempt. Note that the limit set with
+/// [`with_max_body_size`](struct.Request.html#method.with_max_body_size)
+/// applies to downloads too.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// let request = bitreq::get("https://example.com/bitcoin.tar.gz").with_timeout(60);
+/// let expected_sha256 = [0; 32];
+/// bitreq::Download::new(request, "bitcoin.tar.gz")
+///     .with_sha256(expected_sha256)
+///     .with_progress(|downloaded, total| println!("{}/{:?}", downloaded, total))
+///     .send()?;
+/// # Ok(()) }
+/// ```
+pub struct Download {
+    request: Request,
+    path: PathBuf,
+    sha256: Option<[u8; 32]>,
+    progress: Option<Progress>,
+    max_attempts: usize,
+}
+
+impl Download {
+    /// Creates a download of the response body of `request`, which
+    /// should be a GET request, to the file at `path`.
+    pub fn new<P: Into<PathBuf>>(request: Request, path: P) -> Download {
+        Download { request, path: path.into(), sha256: None, progress: None, max_attempts: 3 }
+    }
+
+    /// Sets the expected SHA256 hash of the file, which is verified as
+    /// it is downloaded.
+    ///
+    /// If the file has another hash, the download fails with
+    /// [`ChecksumMismatch`](enum.Error.html#variant.ChecksumMismatch)
+    /// and is discarded.
+    pub fn with_sha256(mut self, sha256: [u8; 32]) -> Download {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    /// Sets a function called with the number of bytes of the file
+    /// downloaded so far, and its total size if known, as it is
+    /// downloaded.
+    pub fn with_progress<F: FnMut(u64, Option<u64>) + Send + 'static>(
+        mut self,
+        progress: F,
+    ) -> Download {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Sets how many times the file is requested before giving up, if
+    /// the connection fails. The default is 3.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Download {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Downloads the file, resuming an earlier download of it if
+    /// possible, and returns its size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DownloadStatus`](enum.Error.html#variant.DownloadStatus)
+    /// if the server doesn't send the file, and
+    /// [`ChecksumMismatch`](enum.Error.html#variant.ChecksumMismatch) if
+    /// it doesn't have the expected hash. Otherwise, see
+    /// [`Request::send`](struct.Request.html#method.send).
+    pub fn send(mut self) -> Result<u64, Error> {
+        let part = with_suffix(&self.path, ".part");
+        let validator = with_suffix(&self.path, ".part.validator");
+
+        let mut attempt = 1;
+        let (length, engine) = loop {
+            match self.attempt(&part, &validator) {
+                Ok(downloaded) => break downloaded,
+                Err(e) if attempt < self.max_attempts && is_interruption(&e) => {
+                    #[cfg(feature = "log")]
+                    log::debug!("Download of {:?} failed, resuming: {}", self.path, e);
+                    #[cfg(not(feature = "log"))]
+                    let _ = e;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        if let Some(expected) = self.sha256 {
+            if sha256::Hash::from_engine(engine).to_byte_array() != expected {
+                remove(&part)?;
+                remove(&validator)?;
+                return Err(Error::ChecksumMismatch);
+            }
+        }
+        fs::rename(&part, &self.path)?;
+        remove(&validator)?;
+        Ok(length)
+    }
+
+    /// Downloads the rest of the file to `part`, returning its size and hash.
+    fn attempt(
+        &mut self,
+        part: &Path,
+        validator_path: &Path,
+    ) -> Result<(u64, sha256::HashEngine), Error> {
+        let mut file =
+            OpenOptions::new().read(true).write(true).create(true).truncate(false).open(part)?;
+        let mut offset = file.metadata()?.len();
+        let validator = match offset {
+            0 => None,
+            _ => fs::read_to_string(validator_path).ok(),
+        };
+
+        // Ranges refer to the encoded body, which we want to store as is.
+        let mut request = self.request.clone().with_header("Accept-Encoding", "identity");
+        if let Some(validator) = &validator {
+            request = request
+                .with_header("Range", format!("bytes={}-", offset))
+                .with_header("If-Range", validator.as_str());
+        }
+        let response = request.send_lazy()?;
+
+        let total = match response.status_code {
+            206 if validator.is_some() => match content_range(&response) {
+                Some((start, total)) if start == offset => total,
+                _ => return Err(restart(part, validator_path, "unexpected range")),
+            },
+            416 if validator.is_some() => match content_range(&response) {
+                // The file was already fully downloaded.
+                Some((_, Some(total))) if total == offset => {
+                    let engine = hash_file(&mut file, offset)?;
+                    return Ok((offset, engine));
+                }
+                _ => return Err(restart(part, validator_path, "unsatisfiable range")),
+            },
+            200 => {
+                offset = 0;
+                file.set_len(0)?;
+                match response_validator(&response) {
+                    Some(validator) => fs::write(validator_path, validator)?,
+                    None => remove(validator_path)?,
+                }
+                response.headers.get("content-length").and_then(|length| length.parse().ok())
+            }
+            status_code => return Err(Error::DownloadStatus(status_code)),
+        };
+
+        let mut engine = hash_file(&mut file, offset)?;
+        self.report(offset, total);
+        let mut buf = Vec::with_capacity(BUFFER_LENGTH);
+        let mut response = response.peekable();
+        while response.peek().is_some() {
+            buf.clear();
+            for byte in response.by_ref().take(BUFFER_LENGTH) {
+                buf.push(byte?.0);
+            }
+            file.write_all(&buf)?;
+            engine.input(&buf);
+            offset += buf.len() as u64;
+            self.report(offset, total);
+        }
+        file.flush()?;
+
+        if total.is_some_and(|total| total != offset) {
+            let e = io::Error::new(io::ErrorKind::UnexpectedEof, "the download ended early");
+            return Err(Error::IoError(e));
+        }
+        Ok((offset, engine))
+    }
+
+    fn report(&mut self, downloaded: u64, total: Option<u64>) {
+        if let Some(progress) = &mut self.progress {
+            progress(downloaded, total);
+        }
+    }
+}
+
+/// Returns whether `err` is how a download fails if the connection is lost, or times out.
+fn is_interruption(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::IoError(_) | Error::ConnectTimeout | Error::ReadTimeout | Error::TotalTimeout
+    )
+}
+
+/// Returns the first byte position and the complete length, if known, of the `Content-Range`
+/// header of `response`.
+fn content_range(response: &ResponseLazy) -> Option<(u64, Option<u64>)> {
+    let range = response.headers.get("content-range")?.trim().strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    let start = match range.trim() {
+        "*" => 0,
+        range => range.split_once('-')?.0.trim().parse().ok()?,
+    };
+    Some((start, total))
+}
+
+/// Returns the value of the `If-Range` header to resume a download of the body of `response`.
+fn response_validator(response: &ResponseLazy) -> Option<&str> {
+    // Weak entity tags can't be used in `If-Range` headers.
+    match response.headers.get("etag") {
+        Some(etag) if !etag.starts_with("W/") => Some(etag),
+        _ => response.headers.get("last-modified").map(String::as_str),
+    }
+}
+
+/// Hashes the first `length` bytes of `file`, leaving it positioned after them.
+fn hash_file(file: &mut File, length: u64) -> io::Result<sha256::HashEngine> {
+    let mut engine = sha256::HashEngine::default();
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = Read::by_ref(file).take(length);
+    let mut buf = vec![0; BUFFER_LENGTH];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            read => engine.input(&buf[..read]),
+        }
+    }
+    file.seek(SeekFrom::Start(length))?;
+    Ok(engine)
+}
+
+/// Discards the partial download, which can't be resumed, for the next attempt to start over.
+fn restart(part: &Path, validator: &Path, msg: &'static str) -> Error {
+    if let Err(e) = remove(part).and_then(|()| remove(validator)) {
+        return Error::IoError(e);
+    }
+    Error::IoError(io::Error::new(io::ErrorKind::Other, msg))
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
@@ -25,6 +25,10 @@ pub enum Error {
     #[cfg(feature = "native-tls")]
     /// Ran into a native-tls error while creating the connection.
     NativeTlsCreateConnection(native_tls::Error),
+    #[cfg(feature = "rustls")]
+    /// A certificate or key given to [TlsConfig](crate::TlsConfig) is
+    /// not valid PEM.
+    InvalidPem(rustls::pki_types::pem::Error),
     /// Ran into an IO problem while loading the response.
     #[cfg(feature = "std")]
     IoError(io::Error),
@@ -46,6 +50,15 @@ pub enum Error {
     /// [ToSocketAddrs](std::net::ToSocketAddrs) did not resolve to an
     /// address.
     AddressNotFound,
+    /// Connecting to the server took longer than
+    /// [Request::with_connect_timeout](crate::request::Request::with_connect_timeout).
+    ConnectTimeout,
+    /// The server sent nothing for longer than
+    /// [Request::with_read_timeout](crate::request::Request::with_read_timeout).
+    ReadTimeout,
+    /// The request took longer than
+    /// [Request::with_total_timeout](crate::request::Request::with_total_timeout).
+    TotalTimeout,
     /// The response was a redirection, but the `Location` header is
     /// missing.
     RedirectLocationMissing,
@@ -75,9 +88,28 @@ pub enum Error {
     /// The provided credentials were rejected by the proxy server.
     #[cfg(feature = "proxy")]
     InvalidProxyCreds,
+    /// The SOCKS5 proxy server failed to connect to the host, with the
+    /// given reply code (RFC 1928), eg. 4 if the host is unreachable.
+    #[cfg(feature = "proxy")]
+    ProxySocks5(u8),
+    /// The host name is longer than the 255 bytes a SOCKS5 proxy
+    /// accepts.
+    #[cfg(feature = "proxy")]
+    ProxySocks5HostTooLong,
     /// The response body size surpasses
     /// [Request::with_max_body_size](crate::request::Request::with_max_body_size).
     BodyOverflow,
+    /// The request body was streamed from a reader, eg. set with
+    /// [Request::with_body_reader](crate::request::Request::with_body_reader), and was already
+    /// sent, eg. before a redirection, so it cannot be sent again.
+    RequestBodyConsumed,
+    /// The server answered a [Download](crate::Download) with the given
+    /// status code instead of the file.
+    #[cfg(feature = "download")]
+    DownloadStatus(i32),
+    /// The downloaded file doesn't have the expected SHA256 hash.
+    #[cfg(feature = "download")]
+    ChecksumMismatch,
     // TODO: Uncomment these two for 3.0
     // /// The URL does not start with http:// or https://.
     // InvalidProtocol,
@@ -106,12 +138,17 @@ impl fmt::Display for Error {
             RustlsCreateConnection(err) => write!(f, "error creating rustls connection: {}", err),
             #[cfg(feature = "native-tls")]
             NativeTlsCreateConnection(err) => write!(f, "error creating native-tls connection: {err}"),
+            #[cfg(feature = "rustls")]
+            InvalidPem(err) => write!(f, "invalid PEM in the TLS configuration: {}", err),
             MalformedChunkLength => write!(f, "non-usize chunk length with transfer-encoding: chunked"),
             MalformedChunkEnd => write!(f, "chunk did not end after reading the expected amount of bytes"),
             MalformedContentLength => write!(f, "non-usize content length"),
             HeadersOverflow => write!(f, "the headers' total size surpassed max_headers_size"),
             StatusLineOverflow => write!(f, "the status line length surpassed max_status_line_length"),
             AddressNotFound => write!(f, "could not resolve host to a socket address"),
+            ConnectTimeout => write!(f, "the connect timeout of the request was reached"),
+            ReadTimeout => write!(f, "the read timeout of the request was reached"),
+            TotalTimeout => write!(f, "the timeout of the request was reached"),
             RedirectLocationMissing => write!(f, "redirection location header missing"),
             InfiniteRedirectionLoop => write!(f, "infinite redirection loop detected"),
             TooManyRedirections => write!(f, "too many redirections (over the max)"),
@@ -125,7 +162,16 @@ impl fmt::Display for Error {
             ProxyConnect => write!(f, "could not connect to the proxy server"),
             #[cfg(feature = "proxy")]
             InvalidProxyCreds => write!(f, "the provided proxy credentials are invalid"),
+            #[cfg(feature = "proxy")]
+            ProxySocks5(code) => write!(f, "the SOCKS5 proxy server failed to connect (reply code {})", code),
+            #[cfg(feature = "proxy")]
+            ProxySocks5HostTooLong => write!(f, "the host name is longer than the 255 bytes a SOCKS5 proxy accepts"),
             BodyOverflow => write!(f, "the response body size surpassed max_body_size"),
+            RequestBodyConsumed => write!(f, "the streamed request body was already sent"),
+            #[cfg(feature = "download")]
+            DownloadStatus(code) => write!(f, "the server answered the download with status code {}", code),
+            #[cfg(feature = "download")]
+            ChecksumMismatch => write!(f, "the downloaded file does not have the expected sha256 hash"),
             // TODO: Uncomment these two for 3.0
             // InvalidProtocol => write!(f, "the url does not start with http:// or https://"),
             // InvalidProtocolInRedirect => write!(f, "got redirected to an absolute url which does not start with http:// or https://"),
@@ -147,14 +193,38 @@ impl error::Error for Error {
             InvalidUtf8InBody(err) => Some(err),
             #[cfg(feature = "rustls")]
             RustlsCreateConnection(err) => Some(err),
+            #[cfg(feature = "rustls")]
+            InvalidPem(err) => Some(err),
             _ => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl Error {
+    /// Converts this error into an [`io::Error`], from which [`From<io::Error>`] gets it back.
+    pub(crate) fn into_io_error(self) -> io::Error {
+        match self {
+            Error::IoError(err) => err,
+            Error::ConnectTimeout | Error::ReadTimeout | Error::TotalTimeout =>
+                io::Error::new(io::ErrorKind::TimedOut, self),
+            _ => io::Error::new(io::ErrorKind::Other, self),
+        }
+    }
+}
+
 #[cfg(feature = "std")]
 impl From<io::Error> for Error {
-    fn from(other: io::Error) -> Error { Error::IoError(other) }
+    fn from(other: io::Error) -> Error {
+        // Errors such as timeouts are passed through readers as `io::Error`s.
+        if !other.get_ref().is_some_and(|err| err.is::<Error>()) {
+            return Error::IoError(other);
+        }
+        match other.into_inner().map(|err| err.downcast::<Error>()) {
+            Some(Ok(err)) => *err,
+            _ => Error::Other("io::Error lost its bitreq::Error"),
+        }
+    }
 }
 
 impl From<UrlParseError> for Error {
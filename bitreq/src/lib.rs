@@ -38,6 +38,11 @@
 //! feature variants were added, and is now an alias for
 //! `https-rustls`.
 //!
+//! With this feature and the other rustls-based ones, the certificate
+//! authorities to trust, client certificates, public key pins and ALPN
+//! protocols can be configured with a
+//! [`TlsConfig`](struct.TlsConfig.html).
+//!
 //! ## `https-rustls-probe`
 //!
 //! Like `https-rustls`, but uses the
@@ -56,10 +61,12 @@
 //! This feature enables asynchronous HTTP requests using tokio. It provides
 //! [`send_async()`](struct.Request.html#method.send_async) and
 //! [`send_lazy_async()`](struct.Request.html#method.send_lazy_async) methods
-//! that return futures for non-blocking operation.
+//! that return futures for non-blocking operation, and
+//! [`send_streaming_async()`](struct.Request.html#method.send_streaming_async)
+//! which streams the response body as it is read.
 //!
-//! It also enables [`Client`](struct.Client.html) to reuse TCP connections
-//! across requests.
+//! It also enables [`Client`](struct.Client.html) to send requests
+//! asynchronously, reusing TCP connections across requests.
 //!
 //! ## `async-https` or `async-https-rustls`
 //!
@@ -83,7 +90,24 @@
 //!
 //! ## `proxy`
 //!
-//! This feature enables HTTP proxy support.
+//! This feature enables HTTP and SOCKS5 proxy support.
+//!
+//! ## `compression`
+//!
+//! This feature sends an `Accept-Encoding: gzip, deflate` header with
+//! requests, unless they have one, and transparently decodes gzip and
+//! deflate compressed response bodies, using the
+//! [`flate2`](https://crates.io/crates/flate2) crate. The limit set with
+//! [`with_max_body_size`](struct.Request.html#method.with_max_body_size)
+//! applies to the decoded body. The `Content-Encoding` and
+//! `Content-Length` headers of decoded responses are removed.
+//!
+//! ## `download`
+//!
+//! This feature enables [`Download`](struct.Download.html), which
+//! downloads files resuming interrupted downloads and verifying their
+//! SHA256 hash, using the
+//! [`bitcoin_hashes`](https://crates.io/crates/bitcoin_hashes) crate.
 //!
 //! # Examples
 //!
@@ -179,15 +203,25 @@
 //!
 //! To avoid timing out, or limit the request's response time, use
 //! `with_timeout(n)` before `send()`. The given value is in seconds.
+//! Sub-second limits, and separate limits on connecting and on each
+//! read, are set with `with_total_timeout`, `with_connect_timeout` and
+//! `with_read_timeout`.
 //!
 //! NOTE: There is no timeout by default.
 //!
 //! ```no_run
 //! # #[cfg(feature = "std")]
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//!
 //! let response = bitreq::post("http://example.com")
 //!     .with_timeout(10)
 //!     .send()?;
+//! let response = bitreq::post("http://example.com")
+//!     .with_connect_timeout(Duration::from_millis(500))
+//!     .with_read_timeout(Duration::from_secs(2))
+//!     .with_total_timeout(Duration::from_secs(10))
+//!     .send()?;
 //! # Ok(()) }
 //! # #[cfg(not(feature = "std"))]
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
@@ -199,8 +233,9 @@
 //! `.with_proxy()` on your request.
 //!
 //! Supported proxy formats are `host:port` and
-//! `user:password@proxy:host`. Only HTTP CONNECT proxies are
-//! supported at this time.
+//! `user:password@proxy:host`. HTTP CONNECT proxies are created with
+//! `Proxy::new_http` and SOCKS5 proxies, eg. Tor, with
+//! `Proxy::new_socks5`.
 //!
 //! ```no_run
 //! # #[cfg(feature = "std")]
@@ -239,8 +274,12 @@
 //!   ```
 //!   std::env::set_var("BITREQ_TIMEOUT", "8");
 //!   ```
-//! If the timeout is set with `with_timeout`, the environment
-//! variable will be ignored.
+//! If the timeout is set with `with_timeout` or `with_total_timeout`,
+//! the environment variable will be ignored.
+//!
+//! Each timeout is reported by its own error:
+//! [`Error::ConnectTimeout`], [`Error::ReadTimeout`] or
+//! [`Error::TotalTimeout`].
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 #![deny(missing_docs)]
@@ -250,10 +289,16 @@
 
 extern crate alloc;
 
+#[cfg(feature = "std")]
+mod body;
 #[cfg(feature = "std")]
 mod client;
+#[cfg(feature = "compression")]
+mod compression;
 #[cfg(feature = "std")]
 mod connection;
+#[cfg(feature = "download")]
+mod download;
 mod error;
 #[cfg(feature = "proxy")]
 mod proxy;
@@ -261,15 +306,23 @@ mod proxy;
 mod request;
 #[cfg(feature = "std")]
 mod response;
+#[cfg(feature = "rustls")]
+mod tls;
 mod url;
 
-#[cfg(feature = "async")]
-pub use client::{Client, RequestExt};
+#[cfg(feature = "std")]
+pub use client::{Client, PoolStats, RequestExt};
+#[cfg(feature = "download")]
+pub use download::Download;
 pub use error::*;
 #[cfg(feature = "proxy")]
 pub use proxy::*;
 #[cfg(feature = "std")]
 pub use request::*;
+#[cfg(feature = "async")]
+pub use response::AsyncResponseLazy;
 #[cfg(feature = "std")]
 pub use response::{Response, ResponseLazy};
+#[cfg(feature = "rustls")]
+pub use tls::TlsConfig;
 pub use url::{ParseError as UrlParseError, Url};
@@ -6,16 +6,27 @@ use core::time::Duration;
 #[cfg(feature = "std")]
 use std::env;
 #[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
 use std::time::Instant;
 
+#[cfg(feature = "async")]
+use tokio::io::AsyncRead;
+
+#[cfg(feature = "std")]
+use crate::body::Body;
 #[cfg(feature = "async")]
 use crate::connection::AsyncConnection;
 #[cfg(feature = "std")]
-use crate::connection::Connection;
+use crate::connection::{Connection, Timeouts};
 #[cfg(feature = "proxy")]
 use crate::proxy::Proxy;
+#[cfg(feature = "rustls")]
+use crate::tls::TlsConfig;
 #[cfg(feature = "std")]
 use crate::url::Url;
+#[cfg(feature = "async")]
+use crate::AsyncResponseLazy;
 #[cfg(feature = "std")]
 use crate::{Error, Response, ResponseLazy};
 
@@ -48,6 +59,23 @@ pub enum Method {
     Custom(String),
 }
 
+impl Method {
+    /// Returns whether sending a request with this method several times has the same effect as
+    /// sending it once, as defined by RFC 9110.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::Get
+                | Method::Head
+                | Method::Put
+                | Method::Delete
+                | Method::Options
+                | Method::Trace
+        )
+    }
+}
+
 impl fmt::Display for Method {
     /// Formats the Method to the form in the HTTP request,
     /// ie. Method::Get -> "GET", Method::Post -> "POST", etc.
@@ -88,8 +116,10 @@ pub struct Request {
     url: URL,
     params: Vec<(String, String)>,
     headers: BTreeMap<String, String>,
-    body: Option<Vec<u8>>,
-    timeout: Option<u64>,
+    pub(crate) body: Option<Body>,
+    timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) read_timeout: Option<Duration>,
     pub(crate) pipelining: bool,
     pub(crate) max_headers_size: Option<usize>,
     pub(crate) max_status_line_len: Option<usize>,
@@ -97,6 +127,8 @@ pub struct Request {
     max_redirects: usize,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<Proxy>,
+    #[cfg(feature = "rustls")]
+    pub(crate) tls: Option<TlsConfig>,
 }
 
 impl Request {
@@ -115,6 +147,8 @@ impl Request {
             headers: BTreeMap::new(),
             body: None,
             timeout: None,
+            connect_timeout: None,
+            read_timeout: None,
             pipelining: false,
             // Default matches chrome as of 2022-11:
             // https://groups.google.com/a/chromium.org/g/chromium-os-discuss/c/in-f59OKYAE/m/uVanwcXkAgAJ
@@ -127,6 +161,8 @@ impl Request {
             max_redirects: 100,
             #[cfg(feature = "proxy")]
             proxy: None,
+            #[cfg(feature = "rustls")]
+            tls: None,
         }
     }
 
@@ -154,10 +190,49 @@ impl Request {
     pub fn with_body<T: Into<Vec<u8>>>(mut self, body: T) -> Request {
         let body = body.into();
         let body_length = body.len();
-        self.body = Some(body);
+        self.body = Some(Body::Bytes(body));
         self.with_header("Content-Length", format!("{}", body_length))
     }
 
+    /// Sets the request body to the contents of `reader`, which are sent
+    /// as they are read instead of being buffered in memory.
+    ///
+    /// If `length` is given, it is sent as the `Content-Length` and
+    /// exactly that many bytes are read, failing if `reader` ends
+    /// earlier. Otherwise, the body is sent with `Transfer-Encoding:
+    /// chunked` until `reader` ends.
+    ///
+    /// The body can only be sent once, and only with the blocking API,
+    /// eg. [`send`](struct.Request.html#method.send). Clones of this
+    /// request share the same reader, and a redirection which would
+    /// need to send the body again fails with
+    /// [`RequestBodyConsumed`](enum.Error.html#variant.RequestBodyConsumed).
+    pub fn with_body_reader<R: Read + Send + 'static>(
+        mut self,
+        reader: R,
+        length: Option<u64>,
+    ) -> Request {
+        self.body = Some(Body::reader(reader, length));
+        self
+    }
+
+    /// Sets the request body to the contents of `stream`, which are
+    /// sent as they are read instead of being buffered in memory.
+    ///
+    /// This is the asynchronous equivalent of
+    /// [`with_body_reader`](struct.Request.html#method.with_body_reader),
+    /// and the body can only be sent with the async API, eg.
+    /// [`send_async`](struct.Request.html#method.send_async).
+    #[cfg(feature = "async")]
+    pub fn with_body_stream<S: AsyncRead + Send + 'static>(
+        mut self,
+        stream: S,
+        length: Option<u64>,
+    ) -> Request {
+        self.body = Some(Body::stream(stream, length));
+        self
+    }
+
     /// Adds given key and value as query parameter to request url
     /// (resource).
     ///
@@ -185,12 +260,46 @@ impl Request {
         }
     }
 
-    /// Sets the request timeout in seconds.
+    /// Sets the request timeout in seconds, see
+    /// [`with_total_timeout`](struct.Request.html#method.with_total_timeout).
     pub fn with_timeout(mut self, timeout: u64) -> Request {
+        self.timeout = Some(Duration::from_secs(timeout));
+        self
+    }
+
+    /// Sets how long the request may take in total, from connecting to
+    /// the server to reading the last byte of the response, including
+    /// redirections. The request fails with
+    /// [`TotalTimeout`](enum.Error.html#variant.TotalTimeout) once it is
+    /// reached.
+    ///
+    /// There is no timeout by default, unless the `BITREQ_TIMEOUT`
+    /// environment variable is set to a number of seconds.
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Request {
         self.timeout = Some(timeout);
         self
     }
 
+    /// Sets how long connecting to the server may take, including the
+    /// proxy and TLS handshakes, for each connection the request opens.
+    /// The request fails with
+    /// [`ConnectTimeout`](enum.Error.html#variant.ConnectTimeout) once it
+    /// is reached.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Request {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long the server may send nothing while the response is
+    /// read, ie. the timeout of each read from the connection. The
+    /// request fails with
+    /// [`ReadTimeout`](enum.Error.html#variant.ReadTimeout) once it is
+    /// reached.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Request {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the max redirects we follow until giving up. 100 by
     /// default.
     ///
@@ -268,6 +377,15 @@ impl Request {
         self
     }
 
+    /// Sets the TLS configuration of HTTPS connections, eg. to trust a
+    /// private certificate authority or present a client certificate.
+    /// See [`TlsConfig`](struct.TlsConfig.html).
+    #[cfg(feature = "rustls")]
+    pub fn with_tls_config(mut self, tls: TlsConfig) -> Request {
+        self.tls = Some(tls);
+        self
+    }
+
     /// Enables HTTP request pipelining for this request.
     ///
     /// Note that because pipelined requests may be replayed in case of failure, you should only
@@ -298,7 +416,7 @@ impl Request {
         let is_head = parsed_request.config.method == Method::Head;
         let max_body_size = parsed_request.config.max_body_size;
         let connection =
-            Connection::new(parsed_request.connection_params(), parsed_request.timeout_at)?;
+            Connection::new(parsed_request.connection_params(), parsed_request.timeouts())?;
         let response = connection.send(parsed_request)?;
         Response::create(response, is_head, max_body_size)
     }
@@ -311,7 +429,7 @@ impl Request {
     #[cfg(feature = "std")]
     pub fn send_lazy(self) -> Result<ResponseLazy, Error> {
         let parsed_request = ParsedRequest::new(self)?;
-        Connection::new(parsed_request.connection_params(), parsed_request.timeout_at)?
+        Connection::new(parsed_request.connection_params(), parsed_request.timeouts())?
             .send(parsed_request)
     }
 
@@ -327,7 +445,7 @@ impl Request {
     #[cfg(feature = "async")]
     pub async fn send_async(self) -> Result<Response, Error> {
         let parsed_request = ParsedRequest::new(self)?;
-        AsyncConnection::new(parsed_request.connection_params(), parsed_request.timeout_at)
+        AsyncConnection::new(parsed_request.connection_params(), parsed_request.timeouts())
             .await?
             .send(parsed_request)
             .await
@@ -336,10 +454,8 @@ impl Request {
     /// Sends this request to the host asynchronously, "loaded lazily".
     ///
     /// Note that due to API limitations the response is not actually loaded lazily - it is loaded
-    /// immediately and then can be re-read from the response. In a future version an
-    /// `AsyncResponseLazy` will be added and lazy loading support will be added.
-    ///
-    /// Until then, you should use [`Self::send_async`].
+    /// immediately and then can be re-read from the response. To read the body as it arrives,
+    /// use [`Self::send_streaming_async`].
     ///
     /// # Errors
     ///
@@ -349,9 +465,29 @@ impl Request {
         let response = self.send_async().await?;
         Ok(ResponseLazy::dummy_from_response(response))
     }
+
+    /// Sends this request to the host asynchronously, returning the
+    /// response once its headers were read, with its body loaded as it
+    /// is read.
+    ///
+    /// The request is sent on a new connection, which is closed once
+    /// the response is dropped.
+    ///
+    /// # Errors
+    ///
+    /// See [`send_async`](struct.Request.html#method.send_async).
+    #[cfg(feature = "async")]
+    pub async fn send_streaming_async(self) -> Result<AsyncResponseLazy, Error> {
+        let parsed_request = ParsedRequest::new(self)?;
+        AsyncConnection::new(parsed_request.connection_params(), parsed_request.timeouts())
+            .await?
+            .send_streaming(parsed_request)
+            .await
+    }
 }
 
 #[cfg(feature = "std")]
+#[derive(Clone)]
 pub(crate) struct ParsedRequest {
     pub(crate) url: Url,
     pub(crate) redirects: Vec<Url>,
@@ -403,10 +539,10 @@ impl ParsedRequest {
         }
 
         let timeout = config.timeout.or_else(|| match env::var("BITREQ_TIMEOUT") {
-            Ok(t) => t.parse::<u64>().ok(),
+            Ok(t) => t.parse::<u64>().ok().map(Duration::from_secs),
             Err(_) => None,
         });
-        let timeout_at = timeout.map(|t| Instant::now() + Duration::from_secs(t));
+        let timeout_at = timeout.and_then(|t| Instant::now().checked_add(t));
 
         Ok(ParsedRequest { url, redirects: Vec::new(), config, timeout_at })
     }
@@ -442,9 +578,20 @@ impl ParsedRequest {
             write!(http, "{}: {}\r\n", k, v).unwrap();
         }
 
-        if self.config.method == Method::Post
-            || self.config.method == Method::Put
-            || self.config.method == Method::Patch
+        #[cfg(feature = "compression")]
+        if self.config.headers.keys().all(|key| !key.eq_ignore_ascii_case("accept-encoding")) {
+            write!(http, "Accept-Encoding: {}\r\n", crate::compression::ACCEPT_ENCODING).unwrap();
+        }
+
+        let framing = self.config.body.as_ref().and_then(Body::framing_header);
+        if let Some((k, v)) = &framing {
+            write!(http, "{}: {}\r\n", k, v).unwrap();
+        }
+
+        if framing.is_none()
+            && (self.config.method == Method::Post
+                || self.config.method == Method::Put
+                || self.config.method == Method::Patch)
         {
             let not_length = |key: &String| {
                 let key = key.to_lowercase();
@@ -467,14 +614,36 @@ impl ParsedRequest {
         http
     }
 
-    /// Returns the HTTP request as bytes, ready to be sent to
-    /// the server.
-    pub(crate) fn as_bytes(&self) -> Vec<u8> {
-        let mut head = self.get_http_head().into_bytes();
-        if let Some(body) = &self.config.body {
-            head.extend(body);
+    /// Writes the HTTP request to `stream`, streaming its body if it
+    /// was set from a reader.
+    pub(crate) fn write_to<W: std::io::Write>(&self, stream: &mut W) -> Result<(), Error> {
+        let head = self.get_http_head();
+        match &self.config.body {
+            Some(body) => body.write_to(head.as_bytes(), stream),
+            None => Ok(stream.write_all(head.as_bytes())?),
         }
-        head
+    }
+
+    /// Writes the HTTP request to `stream` asynchronously, streaming
+    /// its body if it was set from a stream.
+    #[cfg(feature = "async")]
+    pub(crate) async fn write_to_async<W>(&self, stream: &mut W) -> Result<(), Error>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let head = self.get_http_head();
+        match &self.config.body {
+            Some(body) => body.write_to_async(head.as_bytes(), stream).await,
+            None => Ok(stream.write_all(head.as_bytes()).await?),
+        }
+    }
+
+    /// Returns whether this request can be sent again, ie. its body
+    /// isn't streamed.
+    pub(crate) fn is_replayable(&self) -> bool {
+        self.config.body.as_ref().map_or(true, Body::is_replayable)
     }
 
     /// Returns the redirected version of this Request, unless an
@@ -527,6 +696,14 @@ impl ParsedRequest {
     pub(crate) fn connection_params(&self) -> ConnectionParams<'_> {
         ConnectionParams::from_request(self)
     }
+
+    pub(crate) fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            timeout_at: self.timeout_at,
+            connect: self.config.connect_timeout,
+            read: self.config.read_timeout,
+        }
+    }
 }
 
 /// A key which determines whether an existing connection can be reused
@@ -538,6 +715,8 @@ pub(crate) struct ConnectionParams<'a> {
     pub(crate) port: u16,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<&'a Proxy>,
+    #[cfg(feature = "rustls")]
+    pub(crate) tls: Option<&'a TlsConfig>,
 }
 
 #[cfg(feature = "std")]
@@ -549,6 +728,8 @@ impl<'a> ConnectionParams<'a> {
             port: request.url.port(),
             #[cfg(feature = "proxy")]
             proxy: request.config.proxy.as_ref(),
+            #[cfg(feature = "rustls")]
+            tls: request.config.tls.as_ref(),
         }
     }
 }
@@ -562,6 +743,8 @@ pub(crate) struct OwnedConnectionParams {
     pub(crate) port: u16,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<Proxy>,
+    #[cfg(feature = "rustls")]
+    pub(crate) tls: Option<TlsConfig>,
 }
 
 #[cfg(feature = "std")]
@@ -570,6 +753,10 @@ impl PartialEq<ConnectionParams<'_>> for OwnedConnectionParams {
         if self.https != other.https || self.host != other.host || self.port != other.port {
             return false;
         }
+        #[cfg(feature = "rustls")]
+        if self.tls.as_ref() != other.tls {
+            return false;
+        }
         #[cfg(feature = "proxy")]
         {
             self.proxy.as_ref() == other.proxy
@@ -590,6 +777,8 @@ impl From<ConnectionParams<'_>> for OwnedConnectionParams {
             port: other.port,
             #[cfg(feature = "proxy")]
             proxy: other.proxy.cloned(),
+            #[cfg(feature = "rustls")]
+            tls: other.tls.cloned(),
         }
     }
 }
@@ -3,17 +3,27 @@ use core::str;
 #[cfg(feature = "async")]
 use std::future::Future;
 #[cfg(feature = "std")]
-use std::io::{self, BufReader, Bytes, Read};
+use std::io::{self, BufReader, Read};
+#[cfg(feature = "std")]
+use std::net::SocketAddr;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(feature = "async")]
+use std::time::Instant;
 
 #[cfg(feature = "async")]
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
 
+#[cfg(feature = "compression")]
+use crate::compression::Decoder;
 #[cfg(feature = "std")]
 use crate::connection::HttpStream;
 use crate::Error;
 
 #[cfg(feature = "std")]
-const BACKING_READ_BUFFER_LENGTH: usize = 16 * 1024;
+pub(crate) const BACKING_READ_BUFFER_LENGTH: usize = 16 * 1024;
 #[cfg(feature = "std")]
 const MAX_CONTENT_LENGTH: usize = 16 * 1024;
 
@@ -46,6 +56,10 @@ pub struct Response {
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The address of the server this response was received from, or of the proxy if one was
+    /// used. `None` if it isn't known.
+    #[cfg(feature = "std")]
+    pub remote_addr: Option<SocketAddr>,
 
     body: Vec<u8>,
 }
@@ -57,21 +71,41 @@ impl Response {
         is_head: bool,
         max_body_size: Option<usize>,
     ) -> Result<Response, Error> {
-        let mut body = Vec::new();
-        if !is_head && parent.status_code != 204 && parent.status_code != 304 {
-            for byte in &mut parent {
-                let (byte, length) = byte?;
-                if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max) {
-                    return Err(Error::BodyOverflow);
-                }
-                body.reserve(length);
-                body.push(byte);
-            }
-        }
+        let body = parent.read_body(is_head, max_body_size)?;
 
-        let ResponseLazy { status_code, reason_phrase, headers, url, .. } = parent;
+        let ResponseLazy { status_code, reason_phrase, headers, url, remote_addr, .. } = parent;
 
-        Ok(Response { status_code, reason_phrase, headers, url, body })
+        Ok(Response { status_code, reason_phrase, headers, url, remote_addr, body })
+    }
+
+    /// Fully reads a [`Response`], and returns the stream it was read from if the server keeps
+    /// the connection open for another request.
+    #[cfg(feature = "std")]
+    pub(crate) fn create_keep_alive(
+        mut parent: ResponseLazy,
+        is_head: bool,
+        max_body_size: Option<usize>,
+    ) -> Result<(Response, Option<HttpStream>), Error> {
+        let body = parent.read_body(is_head, max_body_size)?;
+
+        // HTTP/1.1 connections are persistent unless told otherwise, but a body delimited by the
+        // end of the stream leaves nothing to reuse.
+        let close = parent.headers.get("connection").is_some_and(|connection| {
+            connection.split(',').any(|token| token.trim().eq_ignore_ascii_case("close"))
+        });
+        let delimited = !matches!(parent.state, HttpStreamState::EndOnClose);
+
+        let ResponseLazy { status_code, reason_phrase, headers, url, remote_addr, stream, .. } =
+            parent;
+        let reader = stream.into_inner();
+        // Bytes past the response can't be the answer to a later request.
+        let stream = if !close && delimited && reader.buffer().is_empty() {
+            Some(reader.into_inner())
+        } else {
+            None
+        };
+
+        Ok((Response { status_code, reason_phrase, headers, url, remote_addr, body }, stream))
     }
 
     #[cfg(feature = "async")]
@@ -82,73 +116,34 @@ impl Response {
     /// In order to avoid changing the API while fixing this, we read the full response but then
     /// return a "lazy" response that has the full contents pre-read.
     pub(crate) async fn create_async<R: AsyncRead + Unpin>(
-        mut stream: R,
+        stream: R,
         is_head: bool,
         max_headers_size: Option<usize>,
         max_status_line_len: Option<usize>,
         max_body_size: Option<usize>,
     ) -> Result<Response, Error> {
-        use HttpStreamState::*;
-
-        let ResponseMetadata {
-            status_code,
-            reason_phrase,
-            mut headers,
-            state,
-            max_trailing_headers_size,
-        } = read_metadata_async(&mut stream, max_headers_size, max_status_line_len).await?;
+        let (status_code, reason_phrase, mut reader) = AsyncBody::create(
+            stream,
+            is_head,
+            max_headers_size,
+            max_status_line_len,
+            max_body_size,
+        )
+        .await?;
 
         let mut body = Vec::new();
-        if !is_head && status_code != 204 && status_code != 304 {
-            match state {
-                EndOnClose => {
-                    while let Some(byte_result) = read_until_closed_async(&mut stream).await {
-                        let (byte, length) = byte_result?;
-                        if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(length);
-                        body.push(byte);
-                    }
-                }
-                ContentLength(mut length) => {
-                    while let Some(byte_result) =
-                        read_with_content_length_async(&mut stream, &mut length).await
-                    {
-                        let (byte, expected_length) = byte_result?;
-                        if max_body_size
-                            .is_some_and(|max| body.len().saturating_add(expected_length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(expected_length);
-                        body.push(byte);
-                    }
-                }
-                Chunked(mut expecting_chunks, mut chunk_length, mut content_length) =>
-                    while let Some(byte_result) = read_chunked_async(
-                        &mut stream,
-                        &mut headers,
-                        &mut expecting_chunks,
-                        &mut chunk_length,
-                        &mut content_length,
-                        max_trailing_headers_size,
-                    )
-                    .await
-                    {
-                        let (byte, length) = byte_result?;
-                        if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(length);
-                        body.push(byte);
-                    },
-            }
+        while let Some(chunk) = reader.read_chunk().await? {
+            body.extend_from_slice(&chunk);
         }
 
-        Ok(Response { status_code, reason_phrase, headers, url: String::new(), body })
+        Ok(Response {
+            status_code,
+            reason_phrase,
+            headers: reader.headers,
+            url: String::new(),
+            remote_addr: None,
+            body,
+        })
     }
 
     /// Returns the body as an `&str`.
@@ -305,16 +300,49 @@ pub struct ResponseLazy {
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The address of the server this response was received from, or of the proxy if one was
+    /// used. `None` if it isn't known.
+    pub remote_addr: Option<SocketAddr>,
 
     stream: HttpStreamBytes,
     state: HttpStreamState,
     max_trailing_headers_size: Option<usize>,
     max_body_size: Option<usize>,
     bytes_read: usize,
+    #[cfg(feature = "compression")]
+    decoder: Option<Decoder>,
+    #[cfg(feature = "compression")]
+    bytes_decoded: usize,
 }
 
 #[cfg(feature = "std")]
-type HttpStreamBytes = Bytes<BufReader<HttpStream>>;
+type HttpStreamBytes = ByteStream;
+
+/// The bytes of a buffered [`HttpStream`], which can be recovered once the response was read.
+#[cfg(feature = "std")]
+pub(crate) struct ByteStream(BufReader<HttpStream>);
+
+#[cfg(feature = "std")]
+impl ByteStream {
+    fn into_inner(self) -> BufReader<HttpStream> { self.0 }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for ByteStream {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut byte = 0;
+        loop {
+            return match self.0.read(core::slice::from_mut(&mut byte)) {
+                Ok(0) => None,
+                Ok(..) => Some(Ok(byte)),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Some(Err(e)),
+            };
+        }
+    }
+}
 
 #[cfg(feature = "std")]
 impl ResponseLazy {
@@ -324,7 +352,17 @@ impl ResponseLazy {
         max_status_line_len: Option<usize>,
         max_body_size: Option<usize>,
     ) -> Result<ResponseLazy, Error> {
-        let mut stream = BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream).bytes();
+        let reader = BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream);
+        ResponseLazy::from_reader(reader, max_headers_size, max_status_line_len, max_body_size)
+    }
+
+    pub(crate) fn from_reader(
+        reader: BufReader<HttpStream>,
+        max_headers_size: Option<usize>,
+        max_status_line_len: Option<usize>,
+        max_body_size: Option<usize>,
+    ) -> Result<ResponseLazy, Error> {
+        let mut stream = ByteStream(reader);
         let ResponseMetadata {
             status_code,
             reason_phrase,
@@ -332,17 +370,26 @@ impl ResponseLazy {
             state,
             max_trailing_headers_size,
         } = read_metadata(&mut stream, max_headers_size, max_status_line_len)?;
+        #[cfg(feature = "compression")]
+        let mut headers = headers;
+        #[cfg(feature = "compression")]
+        let decoder = Decoder::from_headers(&mut headers);
 
         Ok(ResponseLazy {
             status_code,
             reason_phrase,
             headers,
             url: String::new(),
+            remote_addr: None,
             stream,
             state,
             max_trailing_headers_size,
             max_body_size,
             bytes_read: 0,
+            #[cfg(feature = "compression")]
+            decoder,
+            #[cfg(feature = "compression")]
+            bytes_decoded: 0,
         })
     }
 
@@ -354,21 +401,58 @@ impl ResponseLazy {
             reason_phrase: response.reason_phrase,
             headers: response.headers,
             url: response.url,
-            stream: BufReader::with_capacity(1, http_stream).bytes(),
+            remote_addr: response.remote_addr,
+            stream: ByteStream(BufReader::with_capacity(1, http_stream)),
             state: HttpStreamState::EndOnClose,
             max_trailing_headers_size: None,
             // Body was already fully loaded and size-checked by send_async
             max_body_size: None,
             bytes_read: 0,
+            // and decoded.
+            #[cfg(feature = "compression")]
+            decoder: None,
+            #[cfg(feature = "compression")]
+            bytes_decoded: 0,
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl ResponseLazy {
+    /// Reads the rest of the body, unless the response can't have one.
+    fn read_body(&mut self, is_head: bool, max_body_size: Option<usize>) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+        if !is_head && self.status_code != 204 && self.status_code != 304 {
+            for byte in &mut *self {
+                let (byte, length) = byte?;
+                if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max) {
+                    return Err(Error::BodyOverflow);
+                }
+                body.reserve(length);
+                body.push(byte);
+            }
+        }
+        Ok(body)
+    }
+}
+
 #[cfg(feature = "std")]
 impl Iterator for ResponseLazy {
     type Item = Result<(u8, usize), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            return self.next_decoded();
+        }
+        self.next_raw()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ResponseLazy {
+    /// Reads the next byte of the body as it was sent.
+    fn next_raw(&mut self) -> Option<<Self as Iterator>::Item> {
         use HttpStreamState::*;
         let result = match self.state {
             EndOnClose => read_until_closed(&mut self.stream),
@@ -394,6 +478,53 @@ impl Iterator for ResponseLazy {
 
         result
     }
+
+    /// Reads the next byte of the decoded body.
+    #[cfg(feature = "compression")]
+    fn next_decoded(&mut self) -> Option<<Self as Iterator>::Item> {
+        loop {
+            let decoder = self.decoder.as_mut()?;
+            if let Some((byte, available)) = decoder.take() {
+                // Check the decoded body size limit, against zip bombs.
+                if self.max_body_size.is_some_and(|max| self.bytes_decoded + available > max) {
+                    return Some(Err(Error::BodyOverflow));
+                }
+                self.bytes_decoded += 1;
+                return Some(Ok((byte, available)));
+            }
+            if decoder.is_finished() {
+                return None;
+            }
+
+            // Feed the decoder the bytes known to be in the body, up to the 16KiB estimate.
+            let mut encoded = Vec::new();
+            let mut estimate = 1;
+            let finished = loop {
+                if encoded.len() >= estimate {
+                    break false;
+                }
+                match self.next_raw() {
+                    Some(Ok((byte, length))) => {
+                        if encoded.is_empty() {
+                            estimate = length;
+                        }
+                        encoded.push(byte);
+                    }
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => break true,
+                }
+            };
+            let decoder = self.decoder.as_mut()?;
+            if let Err(e) = decoder.feed(&encoded) {
+                return Some(Err(e));
+            }
+            if finished {
+                if let Err(e) = decoder.finish() {
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
 
 #[cfg(feature = "std")]
@@ -403,10 +534,7 @@ impl Read for ResponseLazy {
         for res in self {
             // there is no use for the estimated length in the read implementation
             // so it is ignored.
-            let (byte, _) = res.map_err(|e| match e {
-                Error::IoError(e) => e,
-                _ => io::Error::new(io::ErrorKind::Other, e),
-            })?;
+            let (byte, _) = res.map_err(Error::into_io_error)?;
 
             buf[index] = byte;
             index += 1;
@@ -422,6 +550,341 @@ impl Read for ResponseLazy {
     }
 }
 
+/// An HTTP response, which is loaded asynchronously as its body is
+/// read.
+///
+/// This is the async equivalent of
+/// [`ResponseLazy`](struct.ResponseLazy.html), returned from
+/// [`send_streaming_async()`](struct.Request.html#method.send_streaming_async).
+/// The body is read from the connection only as it is consumed, either
+/// in chunks with [`chunk`](#method.chunk) or the
+/// [`Stream`](futures_core::Stream) implementation, or through the
+/// [`AsyncRead`] implementation, so a slow consumer isn't sent more
+/// than the connection buffers.
+///
+/// The limit set with
+/// [`with_max_body_size`](struct.Request.html#method.with_max_body_size)
+/// and the request's timeout apply to reading the body, and any
+/// trailing headers of a chunked body are added to
+/// [`headers`](#structfield.headers) once it was read. After an error,
+/// no more of the body is returned.
+///
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "async")]
+/// # async fn download() -> Result<(), bitreq::Error> {
+/// let mut response = bitreq::get("http://example.com").send_streaming_async().await?;
+/// let mut length = 0;
+/// while let Some(chunk) = response.chunk().await? {
+///     length += chunk.len();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub struct AsyncResponseLazy {
+    /// The status code of the response, eg. 404.
+    pub status_code: i32,
+    /// The reason phrase of the response, eg. "Not Found".
+    pub reason_phrase: String,
+    /// The headers of the response. The header field names (the
+    /// keys) are all lowercase.
+    pub headers: BTreeMap<String, String>,
+    /// The URL of the resource returned in this response. May differ from the
+    /// request URL if it was redirected or typo corrections were applied (e.g.
+    /// <http://example.com?foo=bar> would be corrected to
+    /// <http://example.com/?foo=bar>).
+    pub url: String,
+    /// The address of the server this response was received from, or of the proxy if one was
+    /// used. `None` if it isn't known.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// The rest of the body, unless it was read or is being read.
+    body: Option<Box<AsyncBody<AsyncStream>>>,
+    reading: Option<ReadChunk>,
+    timeout_at: Option<Instant>,
+    /// The last chunk, of which `position` bytes were read through [`AsyncRead`].
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+#[cfg(feature = "async")]
+type AsyncStream = Pin<Box<dyn AsyncRead + Send>>;
+
+#[cfg(feature = "async")]
+type ReadChunk = Pin<
+    Box<dyn Future<Output = (Box<AsyncBody<AsyncStream>>, Result<Option<Vec<u8>>, Error>)> + Send>,
+>;
+
+#[cfg(feature = "async")]
+impl AsyncResponseLazy {
+    pub(crate) async fn create(
+        stream: AsyncStream,
+        is_head: bool,
+        max_headers_size: Option<usize>,
+        max_status_line_len: Option<usize>,
+        max_body_size: Option<usize>,
+        timeout_at: Option<Instant>,
+    ) -> Result<AsyncResponseLazy, Error> {
+        let (status_code, reason_phrase, body) = AsyncBody::create(
+            stream,
+            is_head,
+            max_headers_size,
+            max_status_line_len,
+            max_body_size,
+        )
+        .await?;
+        Ok(AsyncResponseLazy {
+            status_code,
+            reason_phrase,
+            headers: body.headers.clone(),
+            url: String::new(),
+            remote_addr: None,
+            body: Some(Box::new(body)),
+            reading: None,
+            timeout_at,
+            chunk: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Reads the next chunk of the body, returning `None` once it was
+    /// fully read.
+    ///
+    /// Chunks are at most 16 KiB, unless the body is compressed and the
+    /// `compression` feature is enabled, and don't match the chunks of a
+    /// body sent with `Transfer-Encoding: chunked`.
+    ///
+    /// # Errors
+    ///
+    /// See [`send_async`](struct.Request.html#method.send_async).
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        std::future::poll_fn(|cx| self.poll_chunk(cx)).await
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Vec<u8>>, Error>> {
+        if self.position < self.chunk.len() {
+            let chunk = self.chunk.split_off(self.position);
+            self.chunk.clear();
+            self.position = 0;
+            return Poll::Ready(Ok(Some(chunk)));
+        }
+
+        let reading = match &mut self.reading {
+            Some(reading) => reading,
+            None => {
+                let mut body = match self.body.take() {
+                    Some(body) => body,
+                    None => return Poll::Ready(Ok(None)),
+                };
+                let timeout_at = self.timeout_at;
+                self.reading.insert(Box::pin(async move {
+                    let result = match timeout_at {
+                        Some(timeout_at) =>
+                            tokio::time::timeout_at(timeout_at.into(), body.read_chunk())
+                                .await
+                                .unwrap_or(Err(Error::TotalTimeout)),
+                        None => body.read_chunk().await,
+                    };
+                    (body, result)
+                }))
+            }
+        };
+        let (body, result) = match reading.as_mut().poll(cx) {
+            Poll::Ready(read) => read,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.reading = None;
+        match result {
+            Ok(Some(_)) => self.body = Some(body),
+            // The trailers were read into the body's headers.
+            Ok(None) => self.headers = body.headers,
+            Err(_) => {}
+        }
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for AsyncResponseLazy {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_chunk(cx).map(Result::transpose)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for AsyncResponseLazy {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.position == this.chunk.len() {
+            match this.poll_chunk(cx) {
+                Poll::Ready(Ok(Some(chunk))) => {
+                    this.chunk = chunk;
+                    this.position = 0;
+                }
+                Poll::Ready(Ok(None)) => return Poll::Ready(Ok(())),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into_io_error())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let read = buf.remaining().min(this.chunk.len() - this.position);
+        buf.put_slice(&this.chunk[this.position..this.position + read]);
+        this.position += read;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The body of a response read asynchronously from `stream`, in chunks.
+#[cfg(feature = "async")]
+struct AsyncBody<R> {
+    stream: R,
+    /// The headers of the response, to which trailers are added.
+    headers: BTreeMap<String, String>,
+    state: HttpStreamState,
+    max_trailing_headers_size: Option<usize>,
+    max_body_size: Option<usize>,
+    bytes_read: usize,
+    #[cfg(feature = "compression")]
+    decoder: Option<Decoder>,
+    #[cfg(feature = "compression")]
+    bytes_decoded: usize,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncBody<R> {
+    /// Reads the status line and headers of a response, returning its
+    /// status code, reason phrase and body.
+    async fn create(
+        mut stream: R,
+        is_head: bool,
+        max_headers_size: Option<usize>,
+        max_status_line_len: Option<usize>,
+        max_body_size: Option<usize>,
+    ) -> Result<(i32, String, AsyncBody<R>), Error> {
+        let ResponseMetadata {
+            status_code,
+            reason_phrase,
+            headers,
+            mut state,
+            max_trailing_headers_size,
+        } = read_metadata_async(&mut stream, max_headers_size, max_status_line_len).await?;
+        if is_head || status_code == 204 || status_code == 304 {
+            state = HttpStreamState::ContentLength(0);
+        }
+        #[cfg(feature = "compression")]
+        let mut headers = headers;
+        #[cfg(feature = "compression")]
+        let decoder = Decoder::from_headers(&mut headers);
+
+        let body = AsyncBody {
+            stream,
+            headers,
+            state,
+            max_trailing_headers_size,
+            max_body_size,
+            bytes_read: 0,
+            #[cfg(feature = "compression")]
+            decoder,
+            #[cfg(feature = "compression")]
+            bytes_decoded: 0,
+        };
+        Ok((status_code, reason_phrase, body))
+    }
+
+    /// Reads the next chunk of the body, if any.
+    async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            return self.read_decoded_chunk().await;
+        }
+        self.read_raw_chunk().await
+    }
+
+    /// Reads the next chunk of the body as it was sent, of the bytes known to be in the body, up
+    /// to the 16KiB estimate.
+    async fn read_raw_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        use HttpStreamState::*;
+
+        let mut chunk = Vec::new();
+        let mut estimate = 1;
+        while chunk.len() < estimate {
+            let result = match self.state {
+                EndOnClose => match read_until_closed_async(&mut self.stream).await {
+                    // The async stream reports the end of the body as an error.
+                    Some(Err(Error::IoError(e))) if e.kind() == io::ErrorKind::UnexpectedEof =>
+                        None,
+                    result => result,
+                },
+                ContentLength(ref mut length) =>
+                    read_with_content_length_async(&mut self.stream, length).await,
+                Chunked(ref mut expecting_chunks, ref mut length, ref mut content_length) =>
+                    read_chunked_async(
+                        &mut self.stream,
+                        &mut self.headers,
+                        expecting_chunks,
+                        length,
+                        content_length,
+                        self.max_trailing_headers_size,
+                    )
+                    .await,
+            };
+            let (byte, length) = match result {
+                Some(result) => result?,
+                None => break,
+            };
+            if self.max_body_size.is_some_and(|max| self.bytes_read + length > max) {
+                return Err(Error::BodyOverflow);
+            }
+            if chunk.is_empty() {
+                estimate = length;
+                chunk.reserve(length);
+            }
+            self.bytes_read += 1;
+            chunk.push(byte);
+        }
+        Ok(if chunk.is_empty() { None } else { Some(chunk) })
+    }
+
+    /// Reads the next chunk of the decoded body.
+    #[cfg(feature = "compression")]
+    async fn read_decoded_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            let decoder = match self.decoder.as_mut() {
+                Some(decoder) => decoder,
+                None => return Ok(None),
+            };
+            let decoded = decoder.take_all();
+            if !decoded.is_empty() {
+                // Check the decoded body size limit, against zip bombs.
+                self.bytes_decoded += decoded.len();
+                if self.max_body_size.is_some_and(|max| self.bytes_decoded > max) {
+                    return Err(Error::BodyOverflow);
+                }
+                return Ok(Some(decoded));
+            }
+            if decoder.is_finished() {
+                return Ok(None);
+            }
+
+            let encoded = self.read_raw_chunk().await?;
+            let decoder = match self.decoder.as_mut() {
+                Some(decoder) => decoder,
+                None => return Ok(None),
+            };
+            match encoded {
+                Some(encoded) => decoder.feed(&encoded)?,
+                None => decoder.finish()?,
+            }
+        }
+    }
+}
+
 #[cfg(feature = "std")]
 enum HttpStreamState {
     // No Content-Length, and Transfer-Encoding != chunked, so we just
@@ -479,7 +942,7 @@ macro_rules! define_read_methods {
             if let Some(byte) = maybe_await!(bytes.next(), $($await)?) {
                 match byte {
                     Ok(byte) => Some(Ok((byte, 1))),
-                    Err(err) => Some(Err(Error::IoError(err))),
+                    Err(err) => Some(Err(Error::from(err))),
                 }
             } else {
                 None
@@ -497,7 +960,7 @@ macro_rules! define_read_methods {
                     match byte {
                         // Cap Content-Length to 16KiB, to avoid out-of-memory issues.
                         Ok(byte) => return Some(Ok((byte, (*content_length).min(MAX_CONTENT_LENGTH) + 1))),
-                        Err(err) => return Some(Err(Error::IoError(err))),
+                        Err(err) => return Some(Err(Error::from(err))),
                     }
                 }
             }
@@ -597,7 +1060,7 @@ macro_rules! define_read_methods {
 
                             return Some(Ok((byte, (*chunk_length).min(MAX_CONTENT_LENGTH) + 1)));
                         }
-                        Err(err) => return Some(Err(Error::IoError(err))),
+                        Err(err) => return Some(Err(Error::from(err))),
                     }
                 }
             }
@@ -689,7 +1152,7 @@ macro_rules! define_read_methods {
                             bytes.push(byte);
                         }
                     }
-                    Err(err) => return Err(Error::IoError(err)),
+                    Err(err) => return Err(Error::from(err)),
                 }
             }
             String::from_utf8(bytes).map_err(|_error| Error::InvalidUtf8InResponse)
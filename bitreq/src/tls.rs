@@ -0,0 +1,279 @@
+//! Custom TLS configuration for HTTPS requests made with rustls.
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::ring::cipher_suite::TLS13_AES_128_GCM_SHA256;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    SupportedCipherSuite,
+};
+
+use crate::connection::default_root_certificates;
+use crate::Error;
+
+/// The TLS configuration of HTTPS requests, which by default trust the
+/// root certificates of the enabled `https-rustls*` feature.
+///
+/// A configuration is built once and can then be shared by any number
+/// of requests, set with
+/// [`Request::with_tls_config`](struct.Request.html#method.with_tls_config)
+/// or [`Client::with_tls_config`](struct.Client.html#method.with_tls_config).
+/// Connections are only reused by requests with the same configuration,
+/// ie. clones of the same `TlsConfig`.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// let tls = bitreq::TlsConfig::new()
+///     .without_default_roots()
+///     .with_root_certificates_pem(&std::fs::read("ca.pem")?)?
+///     .with_client_certificate_pem(&std::fs::read("client.pem")?, &std::fs::read("client-key.pem")?)?;
+/// let response = bitreq::get("https://bitcoind.internal:8332/").with_tls_config(tls).send()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    inner: Arc<Inner>,
+}
+
+#[derive(Clone, Default)]
+struct Inner {
+    root_certificates: Vec<CertificateDer<'static>>,
+    without_default_roots: bool,
+    client_certificate: Option<(Vec<CertificateDer<'static>>, Arc<PrivateKeyDer<'static>>)>,
+    pinned_public_keys: Vec<[u8; 32]>,
+    alpn_protocols: Vec<Vec<u8>>,
+    /// The rustls configuration built from the above, once needed.
+    client_config: OnceLock<Result<Arc<ClientConfig>, rustls::Error>>,
+}
+
+impl TlsConfig {
+    /// Creates a configuration equivalent to the default one.
+    pub fn new() -> TlsConfig { TlsConfig::default() }
+
+    /// Trusts the root certificates in `pem`, a PEM file of one or more
+    /// certificates, in addition to the default ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidPem`](enum.Error.html#variant.InvalidPem) if
+    /// `pem` doesn't contain certificates, or can't be parsed.
+    pub fn with_root_certificates_pem(mut self, pem: &[u8]) -> Result<TlsConfig, Error> {
+        let certificates = parse_certificates(pem)?;
+        self.inner_mut().root_certificates.extend(certificates);
+        Ok(self)
+    }
+
+    /// Only trusts the root certificates added with
+    /// [`with_root_certificates_pem`](#method.with_root_certificates_pem),
+    /// eg. to only connect to servers with certificates issued by a
+    /// private certificate authority.
+    pub fn without_default_roots(mut self) -> TlsConfig {
+        self.inner_mut().without_default_roots = true;
+        self
+    }
+
+    /// Presents the certificate chain in `certificates_pem`, starting
+    /// with the client's certificate, to servers asking for one, with
+    /// the PKCS#1, PKCS#8 or SEC1 private key in `key_pem`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidPem`](enum.Error.html#variant.InvalidPem) if
+    /// `certificates_pem` doesn't contain certificates or `key_pem`
+    /// doesn't contain a private key, or they can't be parsed.
+    pub fn with_client_certificate_pem(
+        mut self,
+        certificates_pem: &[u8],
+        key_pem: &[u8],
+    ) -> Result<TlsConfig, Error> {
+        let certificates = parse_certificates(certificates_pem)?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem).map_err(Error::InvalidPem)?;
+        self.inner_mut().client_certificate = Some((certificates, Arc::new(key)));
+        Ok(self)
+    }
+
+    /// Only accepts servers whose certificate has a public key with the
+    /// given SHA-256 hash, of its DER-encoded SubjectPublicKeyInfo, which
+    /// can be computed with:
+    ///
+    /// ```text
+    /// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+    /// ```
+    ///
+    /// Pins are checked after the certificate was verified, and can be
+    /// set several times, eg. for the current and next keys of the
+    /// server. Only the server's own certificate is matched against
+    /// them, not the rest of its chain.
+    pub fn with_pinned_public_key(mut self, sha256: [u8; 32]) -> TlsConfig {
+        self.inner_mut().pinned_public_keys.push(sha256);
+        self
+    }
+
+    /// Sets the protocols offered to servers with ALPN, in order of
+    /// preference, eg. `[b"http/1.1"]`. None are offered by default.
+    ///
+    /// Note that bitreq only speaks HTTP/1.x.
+    pub fn with_alpn_protocols<I, P>(mut self, protocols: I) -> TlsConfig
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.inner_mut().alpn_protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        let inner = Arc::make_mut(&mut self.inner);
+        inner.client_config = OnceLock::new();
+        inner
+    }
+
+    /// Returns the rustls configuration, building it on first use.
+    pub(crate) fn client_config(&self) -> Result<Arc<ClientConfig>, Error> {
+        self.inner
+            .client_config
+            .get_or_init(|| self.inner.build_client_config())
+            .clone()
+            .map_err(Error::RustlsCreateConnection)
+    }
+}
+
+impl Inner {
+    fn build_client_config(&self) -> Result<Arc<ClientConfig>, rustls::Error> {
+        let mut root_certificates = if self.without_default_roots {
+            RootCertStore::empty()
+        } else {
+            default_root_certificates()
+        };
+        for certificate in &self.root_certificates {
+            root_certificates.add(certificate.clone())?;
+        }
+
+        let verifier = WebPkiServerVerifier::builder(Arc::new(root_certificates))
+            .build()
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        let builder = ClientConfig::builder();
+        let builder =
+            match self.pinned_public_keys.is_empty() {
+                true => builder.with_webpki_verifier(verifier),
+                false => builder.dangerous().with_custom_certificate_verifier(Arc::new(
+                    PinningVerifier { verifier, pins: self.pinned_public_keys.clone() },
+                )),
+            };
+        let mut config = match &self.client_certificate {
+            Some((certificates, key)) =>
+                builder.with_client_auth_cert(certificates.clone(), key.clone_key())?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols.clone();
+        Ok(Arc::new(config))
+    }
+}
+
+fn parse_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::InvalidPem)?;
+    match certificates.is_empty() {
+        true => Err(Error::InvalidPem(rustls::pki_types::pem::Error::NoItemsFound)),
+        false => Ok(certificates),
+    }
+}
+
+/// Verifies certificates as usual, then checks the server's public key
+/// against the pinned ones.
+#[derive(Debug)]
+struct PinningVerifier {
+    verifier: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let public_key = ParsedCertificate::try_from(end_entity)?.subject_public_key_info();
+        let hash = sha256(&public_key);
+        if !self.pins.iter().any(|pin| pin[..] == *hash.as_ref()) {
+            #[cfg(feature = "log")]
+            log::debug!("The public key of the certificate of {server_name:?} isn't pinned.");
+            return Err(CertificateError::ApplicationVerificationFailure.into());
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.verifier.supported_verify_schemes()
+    }
+}
+
+/// Hashes `data` with the SHA-256 implementation of rustls' crypto provider.
+fn sha256(data: &[u8]) -> rustls::crypto::hash::Output {
+    let hash = match TLS13_AES_128_GCM_SHA256 {
+        SupportedCipherSuite::Tls12(suite) => suite.common.hash_provider,
+        SupportedCipherSuite::Tls13(suite) => suite.common.hash_provider,
+    };
+    hash.hash(data)
+}
+
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &TlsConfig) -> bool { Arc::ptr_eq(&self.inner, &other.inner) }
+}
+
+impl Eq for TlsConfig {}
+
+impl Hash for TlsConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) { Arc::as_ptr(&self.inner).hash(state) }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("root_certificates", &self.inner.root_certificates.len())
+            .field("without_default_roots", &self.inner.without_default_roots)
+            .field("client_certificate", &self.inner.client_certificate.is_some())
+            .field("pinned_public_keys", &self.inner.pinned_public_keys.len())
+            .field("alpn_protocols", &self.inner.alpn_protocols)
+            .finish()
+    }
+}
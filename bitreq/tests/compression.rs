@@ -0,0 +1,134 @@
+#![cfg(feature = "compression")]
+
+extern crate bitreq;
+mod setup;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+
+const BODY: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ";
+
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zlib(body: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Spawns an HTTP server answering every request with `head` followed by `body`, returning its
+/// URL and the last request it received.
+fn spawn_server(head: String, body: Vec<u8>) -> (String, Arc<Mutex<String>>) {
+    let last_request = Arc::new(Mutex::new(String::new()));
+    let request = Arc::clone(&last_request);
+    let server = setup::spawn_server(move |received, stream| {
+        *request.lock().unwrap() = received.to_owned();
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(&body);
+        false
+    });
+    (server.url("/"), last_request)
+}
+
+fn serve_encoded(encoding: &str, body: Vec<u8>) -> (String, Arc<Mutex<String>>) {
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        encoding,
+        body.len()
+    );
+    spawn_server(head, body)
+}
+
+#[test]
+fn decodes_gzip_and_deflate() {
+    let body = BODY.repeat(100);
+    for (encoding, encoded) in [("gzip", gzip(body.as_bytes())), ("deflate", zlib(body.as_bytes()))]
+    {
+        let (url, request) = serve_encoded(encoding, encoded);
+        let response = bitreq::get(&url).send().unwrap();
+        assert_eq!(response.as_str().unwrap(), body);
+        assert_eq!(response.headers.get("content-encoding"), None);
+        assert_eq!(response.headers.get("content-length"), None);
+        assert!(request.lock().unwrap().contains("Accept-Encoding: gzip, deflate\r\n"));
+
+        let mut lazy = bitreq::get(&url).send_lazy().unwrap();
+        let mut decoded = String::new();
+        lazy.read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, body);
+    }
+}
+
+#[test]
+fn decodes_chunked_gzip() {
+    let body = BODY.repeat(100);
+    let encoded = gzip(body.as_bytes());
+    let mut chunked = Vec::new();
+    for chunk in encoded.chunks(100) {
+        chunked.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        chunked.extend_from_slice(chunk);
+        chunked.extend_from_slice(b"\r\n");
+    }
+    chunked.extend_from_slice(b"0\r\n\r\n");
+    let head = "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n";
+    let (url, _) = spawn_server(head.to_owned(), chunked);
+    let response = bitreq::get(&url).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), body);
+}
+
+#[test]
+fn keeps_other_encodings() {
+    let (url, request) = serve_encoded("br", b"not brotli".to_vec());
+    let response = bitreq::get(&url).with_header("Accept-Encoding", "br").send().unwrap();
+    assert_eq!(response.as_bytes(), b"not brotli");
+    assert_eq!(response.headers.get("content-encoding").unwrap(), "br");
+    let request = request.lock().unwrap();
+    assert!(request.contains("Accept-Encoding: br\r\n"));
+    assert!(!request.contains("gzip"));
+}
+
+#[test]
+fn max_body_size_applies_to_decoded_body() {
+    // A megabyte of zeros compresses to about a kilobyte.
+    let (url, _) = serve_encoded("gzip", gzip(&[0; 1_000_000]));
+    let response = bitreq::get(&url).with_max_body_size(Some(1_000_000)).send().unwrap();
+    assert_eq!(response.as_bytes().len(), 1_000_000);
+
+    let result = bitreq::get(&url).with_max_body_size(Some(100_000)).send();
+    assert!(matches!(result, Err(bitreq::Error::BodyOverflow)));
+    let mut lazy = bitreq::get(&url).with_max_body_size(Some(100_000)).send_lazy().unwrap();
+    assert!(lazy.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn invalid_body() {
+    let (url, _) = serve_encoded("gzip", b"not gzip".to_vec());
+    assert!(bitreq::get(&url).send().is_err());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn decodes_async() {
+    let body = BODY.repeat(100);
+    let (url, _) = serve_encoded("gzip", gzip(body.as_bytes()));
+    let response = bitreq::get(&url).send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), body);
+    assert_eq!(response.headers.get("content-encoding"), None);
+
+    let mut response = bitreq::get(&url).send_streaming_async().await.unwrap();
+    let mut decoded = Vec::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        decoded.extend_from_slice(&chunk);
+    }
+    assert_eq!(decoded, body.as_bytes());
+
+    let (url, _) = serve_encoded("deflate", zlib(&[0; 1_000_000]));
+    let result = bitreq::get(&url).with_max_body_size(Some(100_000)).send_async().await;
+    assert!(matches!(result, Err(bitreq::Error::BodyOverflow)));
+}
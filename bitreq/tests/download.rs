@@ -0,0 +1,211 @@
+#![cfg(feature = "download")]
+
+extern crate bitreq;
+mod setup;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bitcoin_hashes::{sha256, Hash};
+
+const LENGTH: usize = 100_000;
+const ETAG: &str = "\"v1\"";
+
+fn file() -> Vec<u8> { (0..LENGTH).map(|i| (i % 251) as u8).collect() }
+
+fn sha256(data: &[u8]) -> [u8; 32] { sha256::Hash::hash(data).to_byte_array() }
+
+struct Server {
+    url: String,
+    /// The headers of the requests received.
+    requests: Arc<Mutex<Vec<String>>>,
+    /// The number of bytes of the body sent before closing the next connection, if not 0.
+    truncate_next: Arc<AtomicUsize>,
+}
+
+/// Spawns an HTTP server serving `file()` at `/file`, with support for range requests.
+fn spawn_server() -> Server {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let truncate_next = Arc::new(AtomicUsize::new(0));
+    let server = {
+        let requests = Arc::clone(&requests);
+        let truncate_next = Arc::clone(&truncate_next);
+        setup::spawn_server(move |request, stream| {
+            requests.lock().unwrap().push(request.to_owned());
+            let header = |name: &str| {
+                request.lines().find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.eq_ignore_ascii_case(name).then(|| value.trim().to_owned())
+                })
+            };
+
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let file = file();
+            let (head, body) = match path {
+                "/redirect" => (
+                    "HTTP/1.1 301 Moved\r\nLocation: /file\r\nContent-Length: 0\r\n".to_owned(),
+                    &[][..],
+                ),
+                "/file" => {
+                    let start = header("range")
+                        .filter(|_| header("if-range").as_deref() == Some(ETAG))
+                        .and_then(|range| {
+                            range.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok()
+                        });
+                    match start {
+                        Some(start) if start >= LENGTH => (
+                            format!("HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n", LENGTH),
+                            &[][..],
+                        ),
+                        Some(start) => (
+                            format!(
+                                "HTTP/1.1 206 Partial Content\r\nETag: {}\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n",
+                                ETAG, start, LENGTH - 1, LENGTH, LENGTH - start
+                            ),
+                            &file[start..],
+                        ),
+                        None => (
+                            format!("HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\n", ETAG, LENGTH),
+                            &file[..],
+                        ),
+                    }
+                }
+                _ => ("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n".to_owned(), &[][..]),
+            };
+            let body = match truncate_next.swap(0, Ordering::Relaxed) {
+                0 => body,
+                truncate => &body[..truncate.min(body.len())],
+            };
+            let _ = stream.write_all(format!("{}Connection: close\r\n\r\n", head).as_bytes());
+            let _ = stream.write_all(body);
+            false
+        })
+    };
+    Server { url: server.url(""), requests, truncate_next }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bitreq-download-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("file")
+}
+
+fn part(path: &Path) -> PathBuf { path.with_file_name("file.part") }
+
+fn validator(path: &Path) -> PathBuf { path.with_file_name("file.part.validator") }
+
+#[test]
+fn downloads_and_verifies() {
+    let server = spawn_server();
+    let path = temp_path("verifies");
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = Arc::clone(&progress);
+    let length = bitreq::Download::new(bitreq::get(format!("{}/redirect", server.url)), &path)
+        .with_sha256(sha256(&file()))
+        .with_progress(move |downloaded, total| reported.lock().unwrap().push((downloaded, total)))
+        .send()
+        .unwrap();
+    assert_eq!(length, LENGTH as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+    assert!(!part(&path).exists());
+    assert!(!validator(&path).exists());
+    let progress = progress.lock().unwrap();
+    assert_eq!(progress.first(), Some(&(0, Some(LENGTH as u64))));
+    assert_eq!(progress.last(), Some(&(LENGTH as u64, Some(LENGTH as u64))));
+}
+
+#[test]
+fn resumes_interrupted_download() {
+    let server = spawn_server();
+    let path = temp_path("interrupted");
+    server.truncate_next.store(30_000, Ordering::Relaxed);
+    bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_sha256(sha256(&file()))
+        .send()
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+    let requests = server.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(!requests[0].contains("Range"));
+    assert!(requests[1].contains("Range: bytes=30000-\r\n"));
+    assert!(requests[1].contains("If-Range: \"v1\"\r\n"));
+}
+
+#[test]
+fn resumes_partial_file() {
+    let server = spawn_server();
+    let path = temp_path("partial");
+    std::fs::write(part(&path), &file()[..40_000]).unwrap();
+    std::fs::write(validator(&path), "\"v1\"").unwrap();
+    bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_sha256(sha256(&file()))
+        .send()
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+    assert!(server.requests.lock().unwrap()[0].contains("Range: bytes=40000-\r\n"));
+
+    // A complete partial file is only verified.
+    std::fs::write(part(&path), file()).unwrap();
+    std::fs::write(validator(&path), "\"v1\"").unwrap();
+    bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_sha256(sha256(&file()))
+        .send()
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+}
+
+#[test]
+fn restarts_if_file_changed() {
+    let server = spawn_server();
+    let path = temp_path("changed");
+    std::fs::write(part(&path), [0xff; 40_000]).unwrap();
+    std::fs::write(validator(&path), "\"v0\"").unwrap();
+    bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_sha256(sha256(&file()))
+        .send()
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+
+    // Without a validator, the download starts over.
+    std::fs::write(part(&path), [0xff; 40_000]).unwrap();
+    bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path).send().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), file());
+}
+
+#[test]
+fn rejects_wrong_hash() {
+    let server = spawn_server();
+    let path = temp_path("hash");
+    let result = bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_sha256([0; 32])
+        .send();
+    assert!(matches!(result, Err(bitreq::Error::ChecksumMismatch)));
+    assert!(!path.exists());
+    assert!(!part(&path).exists());
+}
+
+#[test]
+fn fails_on_error_status() {
+    let server = spawn_server();
+    let path = temp_path("status");
+    let result =
+        bitreq::Download::new(bitreq::get(format!("{}/missing", server.url)), &path).send();
+    assert!(matches!(result, Err(bitreq::Error::DownloadStatus(404))));
+    assert!(!path.exists());
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let server = spawn_server();
+    let path = temp_path("attempts");
+    server.truncate_next.store(30_000, Ordering::Relaxed);
+    let result = bitreq::Download::new(bitreq::get(format!("{}/file", server.url)), &path)
+        .with_max_attempts(1)
+        .send();
+    assert!(matches!(result, Err(bitreq::Error::IoError(_))));
+    assert_eq!(std::fs::read(part(&path)).unwrap(), &file()[..30_000]);
+    assert_eq!(std::fs::read_to_string(validator(&path)).unwrap(), "\"v1\"");
+}
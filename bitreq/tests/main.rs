@@ -48,6 +48,19 @@ async fn test_timeout_high_enough() {
     assert_eq!(result.as_str().unwrap(), "j: Q");
 }
 
+#[tokio::test]
+async fn test_remote_addr() {
+    setup();
+    let response = make_request(bitreq::get(url("/a"))).await;
+    let remote_addr = response.remote_addr.unwrap();
+    assert!(remote_addr.ip().is_loopback());
+    assert_eq!(remote_addr.port(), 35562);
+
+    // The address is the one of the server the redirection led to.
+    let response = make_request(bitreq::get(url("/redirect"))).await;
+    assert_eq!(response.remote_addr, Some(remote_addr));
+}
+
 #[tokio::test]
 async fn test_headers() {
     setup();
@@ -181,12 +194,7 @@ async fn tcp_connect_timeout() {
     let request =
         bitreq::Request::new(bitreq::Method::Get, "http://127.0.0.1:32162").with_timeout(1);
     let resp = maybe_make_request(request, true).await;
-    assert!(resp.is_err());
-    if let Some(bitreq::Error::IoError(err)) = resp.err() {
-        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
-    } else {
-        panic!("timeout test request did not return an error");
-    }
+    assert!(matches!(resp, Err(bitreq::Error::TotalTimeout)), "{:?}", resp);
 }
 
 #[tokio::test]
@@ -267,3 +275,61 @@ async fn test_future_drop_doesnt_hang() {
         "Request should complete quickly, and certainly not have to wait for its timeout to try again"
     );
 }
+
+#[tokio::test]
+async fn test_body_reader() {
+    setup();
+    let body = || io::Cursor::new(b"Hello, world!".repeat(5000));
+    let length = 13 * 5000;
+
+    let response = bitreq::post(url("/echo")).with_body_reader(body(), Some(length)).send();
+    assert_eq!(response.unwrap().as_bytes(), body().into_inner());
+    let response = bitreq::post(url("/echo")).with_body_reader(body(), None).send();
+    assert_eq!(response.unwrap().as_bytes(), body().into_inner());
+
+    // Only the given length is sent, and bodies ending before it are rejected.
+    let response = bitreq::post(url("/echo")).with_body_reader(body(), Some(5)).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "Hello");
+    let response = bitreq::post(url("/echo")).with_body_reader(body(), Some(length + 1)).send();
+    assert!(
+        matches!(response, Err(bitreq::Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof)
+    );
+
+    // Streamed bodies can only be sent once.
+    let request = bitreq::post(url("/echo")).with_body_reader(body(), None);
+    assert!(request.clone().send().is_ok());
+    assert!(matches!(request.send(), Err(bitreq::Error::RequestBodyConsumed)));
+
+    // But aren't needed after a 303 redirection.
+    let response = bitreq::post(url("/redirect")).with_body_reader(body(), None).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "j: ");
+
+    let client = bitreq::Client::new(1);
+    let response = client.send(bitreq::post(url("/echo")).with_body_reader(body(), None));
+    assert_eq!(response.unwrap().as_bytes(), body().into_inner());
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn test_body_stream() {
+    setup();
+    let body = || io::Cursor::new(b"Hello, world!".repeat(5000));
+    let length = 13 * 5000;
+
+    let request = bitreq::post(url("/echo")).with_body_stream(body(), Some(length));
+    assert_eq!(request.send_async().await.unwrap().as_bytes(), body().into_inner());
+    let request = bitreq::post(url("/echo")).with_body_stream(body(), None);
+    assert_eq!(request.send_async().await.unwrap().as_bytes(), body().into_inner());
+    let request = bitreq::post(url("/echo")).with_body_stream(body(), Some(length + 1));
+    assert!(request.send_async().await.is_err());
+
+    let client = bitreq::Client::new(1);
+    let request = bitreq::post(url("/echo")).with_body_stream(body(), None);
+    assert_eq!(client.send_async(request).await.unwrap().as_bytes(), body().into_inner());
+
+    // Each kind of body can only be sent with the matching API.
+    let request = bitreq::post(url("/echo")).with_body_stream(body(), None);
+    assert!(request.send().is_err());
+    let request = bitreq::post(url("/echo")).with_body_reader(body(), None);
+    assert!(request.send_async().await.is_err());
+}
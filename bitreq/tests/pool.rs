@@ -0,0 +1,211 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+mod setup;
+
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bitreq::{Client, PoolStats, RequestExt};
+
+/// How the test server treats a connection after responding.
+#[derive(Clone, Copy)]
+enum Mode {
+    /// Keeps the connection open for more requests.
+    KeepAlive,
+    /// Sends `Connection: close` and closes the connection.
+    Close,
+    /// Closes the connection without telling the client.
+    SilentClose,
+}
+
+/// Spawns an HTTP server answering every request with `ok` after `delay`, returning its address
+/// and the number of connections it accepted.
+fn spawn_server(mode: Mode, delay: Duration) -> (String, Arc<AtomicUsize>) {
+    let server = setup::spawn_server(move |_, stream| {
+        thread::sleep(delay);
+        let connection = match mode {
+            Mode::Close => "Connection: close\r\n",
+            Mode::KeepAlive | Mode::SilentClose => "Connection: keep-alive\r\n",
+        };
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n{}\r\nok", connection);
+        stream.write_all(response.as_bytes()).is_ok() && matches!(mode, Mode::KeepAlive)
+    });
+    (server.url(""), server.connections)
+}
+
+fn get(client: &Client, url: &str) -> String {
+    let response = bitreq::get(url).with_timeout(5).send_with_client(client).unwrap();
+    assert_eq!(response.status_code, 200);
+    response.as_str().unwrap().to_owned()
+}
+
+#[test]
+fn reuses_connections() {
+    let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+    let client = Client::new(10);
+    for _ in 0..5 {
+        assert_eq!(get(&client, &url), "ok");
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn connection_close_is_not_reused() {
+    let (url, accepted) = spawn_server(Mode::Close, Duration::ZERO);
+    let client = Client::new(10);
+    for _ in 0..3 {
+        assert_eq!(get(&client, &url), "ok");
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn retries_on_connection_closed_by_server() {
+    let (url, accepted) = spawn_server(Mode::SilentClose, Duration::ZERO);
+    let client = Client::new(10);
+    assert_eq!(get(&client, &url), "ok");
+    // Let the server close the pooled connection.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(get(&client, &url), "ok");
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn does_not_retry_non_idempotent_requests() {
+    let (url, accepted) = spawn_server(Mode::SilentClose, Duration::ZERO);
+    let client = Client::new(10);
+    assert_eq!(get(&client, &url), "ok");
+    // Let the server close the pooled connection.
+    thread::sleep(Duration::from_millis(100));
+    let result = bitreq::post(&url).with_body("once").with_timeout(5).send_with_client(&client);
+    assert!(result.is_err());
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn idle_timeout() {
+    let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+    let client = Client::new(10).with_idle_timeout(Duration::from_millis(50));
+    assert_eq!(get(&client, &url), "ok");
+    assert_eq!(get(&client, &url), "ok");
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(get(&client, &url), "ok");
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn max_connections_per_host() {
+    let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::from_millis(50));
+    let client = Client::new(10).with_max_connections_per_host(1);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let (client, url) = (client.clone(), url.clone());
+            thread::spawn(move || get(&client, &url))
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), "ok");
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn lru_eviction() {
+    let (url_a, accepted_a) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+    let (url_b, accepted_b) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+    let client = Client::new(1);
+    assert_eq!(get(&client, &url_a), "ok");
+    // Evicts the idle connection to the first server.
+    assert_eq!(get(&client, &url_b), "ok");
+    assert_eq!(get(&client, &url_a), "ok");
+    assert_eq!(get(&client, &url_a), "ok");
+    assert_eq!(accepted_a.load(Ordering::SeqCst), 2);
+    assert_eq!(accepted_b.load(Ordering::SeqCst), 1);
+    assert_eq!(client.stats(), PoolStats { hits: 1, misses: 3, evictions: 2 });
+}
+
+#[test]
+fn max_lifetime() {
+    let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+    let client = Client::new(10).with_max_lifetime(Duration::from_millis(100));
+    assert_eq!(get(&client, &url), "ok");
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(get(&client, &url), "ok");
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(get(&client, &url), "ok");
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    assert_eq!(client.stats(), PoolStats { hits: 1, misses: 2, evictions: 1 });
+}
+
+#[cfg(feature = "async")]
+mod with_async {
+    use super::*;
+
+    async fn get_async(client: &Client, url: &str) -> String {
+        let request = bitreq::get(url).with_timeout(5);
+        let response = request.send_async_with_client(client).await.unwrap();
+        assert_eq!(response.status_code, 200);
+        response.as_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn reuses_connections() {
+        let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+        let client = Client::new(10);
+        for _ in 0..5 {
+            assert_eq!(get_async(&client, &url).await, "ok");
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(client.stats(), PoolStats { hits: 4, misses: 1, evictions: 0 });
+    }
+
+    #[tokio::test]
+    async fn replaces_connection_closed_by_server() {
+        let (url, accepted) = spawn_server(Mode::SilentClose, Duration::ZERO);
+        let client = Client::new(10);
+        assert_eq!(get_async(&client, &url).await, "ok");
+        // Let the server close the cached connection.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(get_async(&client, &url).await, "ok");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        assert_eq!(client.stats(), PoolStats { hits: 0, misses: 2, evictions: 1 });
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_and_max_lifetime() {
+        let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::ZERO);
+        let client = Client::new(10).with_idle_timeout(Duration::from_millis(50));
+        assert_eq!(get_async(&client, &url).await, "ok");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(get_async(&client, &url).await, "ok");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        let client = Client::new(10).with_max_lifetime(Duration::from_millis(100));
+        assert_eq!(get_async(&client, &url).await, "ok");
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(get_async(&client, &url).await, "ok");
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(get_async(&client, &url).await, "ok");
+        assert_eq!(accepted.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn max_connections_per_host() {
+        let (url, accepted) = spawn_server(Mode::KeepAlive, Duration::from_millis(50));
+        let client = Client::new(10).with_max_connections_per_host(2);
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..6 {
+            let (client, url) = (client.clone(), url.clone());
+            requests.spawn(async move { get_async(&client, &url).await });
+        }
+        while let Some(body) = requests.join_next().await {
+            assert_eq!(body.unwrap(), "ok");
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,9 +1,12 @@
 #![cfg(feature = "std")]
+#![allow(dead_code)]
 
 extern crate bitreq;
 extern crate tiny_http;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Once};
 use std::thread;
 use std::time::Duration;
@@ -201,6 +204,70 @@ pub fn setup() {
 
 pub fn url(req: &str) -> String { format!("http://localhost:35562{}", req) }
 
+/// A server spawned by [`spawn_server`].
+pub struct TestServer {
+    /// The address the server listens on.
+    pub addr: SocketAddr,
+    /// The number of connections the server accepted.
+    pub connections: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Returns the `http` URL of `path` on the server.
+    pub fn url(&self, path: &str) -> String { format!("http://{}{}", self.addr, path) }
+}
+
+/// Spawns an HTTP server on a local port, answering each request by calling `respond` with the
+/// head of the request and its connection. The connection is closed once `respond` returns
+/// `false`.
+pub fn spawn_server<F>(respond: F) -> TestServer
+where
+    F: Fn(&str, &mut TcpStream) -> bool + Send + Sync + 'static,
+{
+    spawn_server_with(|stream| stream, respond)
+}
+
+/// Like [`spawn_server`], but wraps each connection with `wrap` first, eg. to serve HTTPS.
+pub fn spawn_server_with<S, W, F>(wrap: W, respond: F) -> TestServer
+where
+    S: Read + Write,
+    W: Fn(TcpStream) -> S + Send + Sync + 'static,
+    F: Fn(&str, &mut S) -> bool + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let server = TestServer { addr: listener.local_addr().unwrap(), connections: Arc::default() };
+    let connections = Arc::clone(&server.connections);
+    let handler = Arc::new((wrap, respond));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || {
+                let (wrap, respond) = &*handler;
+                let mut stream = wrap(stream);
+                let mut buf = Vec::new();
+                let mut chunk = [0; 1024];
+                loop {
+                    let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                        match stream.read(&mut chunk) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                        }
+                        continue;
+                    };
+                    let head = String::from_utf8_lossy(&buf[..end + 4]).into_owned();
+                    buf.drain(..end + 4);
+                    if !respond(&head, &mut stream) {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    server
+}
+
+static BLOCKING_CLIENT: std::sync::OnceLock<bitreq::Client> = std::sync::OnceLock::new();
 #[cfg(feature = "async")]
 static CLIENT: std::sync::OnceLock<bitreq::Client> = std::sync::OnceLock::new();
 #[cfg(feature = "async")]
@@ -216,6 +283,7 @@ pub async fn maybe_make_request(
         (Ok(resp), Ok(mut lazy_resp)) => {
             assert_eq!(lazy_resp.status_code, resp.status_code);
             assert_eq!(lazy_resp.reason_phrase, resp.reason_phrase);
+            assert_eq!(lazy_resp.remote_addr, resp.remote_addr);
             let mut lazy_bytes = Vec::new();
             lazy_resp.read_to_end(&mut lazy_bytes).unwrap();
             assert_eq!(lazy_bytes, resp.as_bytes());
@@ -224,6 +292,21 @@ pub async fn maybe_make_request(
         (res, lazy_res) => panic!("{res:?} != {}", lazy_res.is_err()),
     }
 
+    if !_slow_request {
+        let client = BLOCKING_CLIENT.get_or_init(|| bitreq::Client::new(100));
+        match (&response, client.send(request.clone())) {
+            (Ok(resp), Ok(client_resp)) => {
+                assert_eq!(client_resp.status_code, resp.status_code);
+                assert_eq!(client_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(client_resp.url, resp.url);
+                assert_eq!(client_resp.remote_addr, resp.remote_addr);
+                assert_eq!(client_resp.as_bytes(), resp.as_bytes());
+            }
+            (Err(e), Err(client_e)) => assert_eq!(format!("{e:?}"), format!("{client_e:?}")),
+            (res, client_res) => panic!("{res:?} != {client_res:?}"),
+        }
+    }
+
     #[cfg(feature = "async")]
     {
         if let Ok(resp) = &response {
@@ -235,6 +318,15 @@ pub async fn maybe_make_request(
         }
         let async_future = request.clone().send_async();
         let lazy_async_future = request.clone().send_lazy_async();
+        let streaming_request = request.clone();
+        let streaming_future = async {
+            let mut response = streaming_request.send_streaming_async().await?;
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                body.extend_from_slice(&chunk);
+            }
+            Ok::<_, bitreq::Error>((response, body))
+        };
         let client_future = async move {
             if !_slow_request {
                 // In order to ensure that clients are able to continue doing things after the tokio
@@ -257,21 +349,36 @@ pub async fn maybe_make_request(
                 request.send_async().await
             }
         };
-        let (async_response, lazy_async_response, client_response) =
-            tokio::join!(async_future, lazy_async_future, client_future);
+        let (async_response, lazy_async_response, streaming_response, client_response) =
+            tokio::join!(async_future, lazy_async_future, streaming_future, client_future);
+
+        match (&streaming_response, &response) {
+            (Ok((streaming_resp, body)), Ok(resp)) => {
+                assert_eq!(streaming_resp.status_code, resp.status_code);
+                assert_eq!(streaming_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(streaming_resp.url, resp.url);
+                assert_eq!(streaming_resp.remote_addr, resp.remote_addr);
+                assert_eq!(body, resp.as_bytes());
+            }
+            (Err(streaming_e), Err(e)) => assert_eq!(format!("{e:?}"), format!("{streaming_e:?}")),
+            (streaming_res, res) => panic!("{res:?} != {}", streaming_res.is_ok()),
+        }
 
         match (&response, &async_response, lazy_async_response, client_response) {
             (Ok(resp), Ok(async_resp), Ok(mut lazy_resp), Ok(client_resp)) => {
                 assert_eq!(async_resp.status_code, resp.status_code);
                 assert_eq!(async_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(async_resp.remote_addr, resp.remote_addr);
                 assert_eq!(async_resp.as_bytes(), resp.as_bytes());
 
                 assert_eq!(client_resp.status_code, resp.status_code);
                 assert_eq!(client_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(client_resp.remote_addr, resp.remote_addr);
                 assert_eq!(client_resp.as_bytes(), resp.as_bytes());
 
                 assert_eq!(lazy_resp.status_code, resp.status_code);
                 assert_eq!(lazy_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(lazy_resp.remote_addr, resp.remote_addr);
                 let mut lazy_bytes = Vec::new();
                 lazy_resp.read_to_end(&mut lazy_bytes).unwrap();
                 assert_eq!(lazy_bytes, resp.as_bytes());
@@ -0,0 +1,118 @@
+#![cfg(feature = "async")]
+
+extern crate bitreq;
+mod setup;
+
+use std::future::poll_fn;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::io::AsyncReadExt;
+
+/// Spawns an HTTP server answering a request with `head` and then calling `body` with the
+/// connection, returning its URL.
+fn spawn_server<F>(head: &'static str, body: F) -> String
+where
+    F: Fn(&mut std::net::TcpStream) + Send + Sync + 'static,
+{
+    let server = setup::spawn_server(move |_, stream| {
+        let _ = stream.write_all(head.as_bytes());
+        body(stream);
+        false
+    });
+    server.url("/")
+}
+
+#[tokio::test]
+async fn streams_body_until_closed() {
+    let url = spawn_server("HTTP/1.1 200 OK\r\n\r\n", |stream| {
+        for _ in 0..100 {
+            let _ = stream.write_all(&[b'a'; 1000]);
+        }
+    });
+    let mut response = bitreq::get(url).send_streaming_async().await.unwrap();
+    assert_eq!(response.status_code, 200);
+    let mut body = Vec::new();
+    response.read_to_end(&mut body).await.unwrap();
+    assert_eq!(body, [b'a'; 100_000]);
+}
+
+#[tokio::test]
+async fn streams_chunked_body_with_trailers() {
+    let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+    let url = spawn_server(head, |stream| {
+        let _ = stream.write_all(b"5\r\nHello\r\n8\r\n, world!\r\n0\r\nX-Checksum: 42\r\n\r\n");
+    });
+    let mut response = bitreq::get(url).send_streaming_async().await.unwrap();
+    assert_eq!(response.headers.get("x-checksum"), None);
+    let mut body = String::new();
+    response.read_to_string(&mut body).await.unwrap();
+    assert_eq!(body, "Hello, world!");
+    assert_eq!(response.headers.get("x-checksum").unwrap(), "42");
+    assert_eq!(response.headers.get("content-length").unwrap(), "13");
+}
+
+#[tokio::test]
+async fn streams_chunks() {
+    let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+    let url = spawn_server(head, |stream| {
+        let _ = stream.write_all(b"5\r\nHello\r\n8\r\n, world!\r\n0\r\n\r\n");
+    });
+    let mut response = bitreq::get(url).send_streaming_async().await.unwrap();
+    let mut body = Vec::new();
+    while let Some(chunk) = poll_fn(|cx| Pin::new(&mut response).poll_next(cx)).await {
+        body.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(body, b"Hello, world!");
+    assert!(poll_fn(|cx| Pin::new(&mut response).poll_next(cx)).await.is_none());
+}
+
+#[tokio::test]
+async fn applies_backpressure() {
+    const LENGTH: usize = 256 * 1024 * 1024;
+    let written = Arc::new(AtomicUsize::new(0));
+    let server_written = Arc::clone(&written);
+    let head = "HTTP/1.1 200 OK\r\nContent-Length: 268435456\r\n\r\n";
+    let url = spawn_server(head, move |stream| {
+        let chunk = [0; 64 * 1024];
+        while server_written.load(Ordering::Relaxed) < LENGTH {
+            if stream.write_all(&chunk).is_err() {
+                return;
+            }
+            server_written.fetch_add(chunk.len(), Ordering::Relaxed);
+        }
+    });
+    let mut response =
+        bitreq::get(url).with_max_body_size(None).send_streaming_async().await.unwrap();
+    assert!(!response.chunk().await.unwrap().unwrap().is_empty());
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    // The server can only fill the socket buffers while the body isn't read.
+    assert!(written.load(Ordering::Relaxed) < LENGTH / 4);
+}
+
+#[tokio::test]
+async fn limits_body_size() {
+    let url = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 100000\r\n\r\n", |stream| {
+        let _ = stream.write_all(&[0; 100_000]);
+    });
+    let mut response =
+        bitreq::get(url).with_max_body_size(Some(1000)).send_streaming_async().await.unwrap();
+    assert!(matches!(response.chunk().await, Err(bitreq::Error::BodyOverflow)));
+}
+
+#[tokio::test]
+async fn times_out_reading_body() {
+    let url = spawn_server("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n12345", |_| {
+        thread::sleep(Duration::from_secs(5));
+    });
+    let mut response = bitreq::get(url).with_timeout(1).send_streaming_async().await.unwrap();
+    match response.chunk().await {
+        Err(bitreq::Error::TotalTimeout) => {}
+        res => panic!("{res:?}"),
+    }
+}
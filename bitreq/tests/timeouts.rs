@@ -0,0 +1,123 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+mod setup;
+
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Spawns an HTTP server answering a request with a 10 byte body, sent one byte every
+/// `interval`, returning its URL.
+fn spawn_slow_server(interval: Duration) -> String {
+    let server = setup::spawn_server(move |_, stream| {
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n");
+        for byte in b"0123456789" {
+            thread::sleep(interval);
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+        }
+        false
+    });
+    server.url("/")
+}
+
+/// Returns the address of a server which accepts connections but never answers.
+#[cfg(any(feature = "rustls", feature = "proxy"))]
+fn silent_server() -> (std::net::TcpListener, String) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+#[test]
+fn read_timeout() {
+    let url = spawn_slow_server(Duration::from_secs(2));
+    let start = Instant::now();
+    let result = bitreq::get(url).with_read_timeout(Duration::from_millis(200)).send();
+    assert!(matches!(result, Err(bitreq::Error::ReadTimeout)), "{:?}", result);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn read_timeout_is_per_read() {
+    let url = spawn_slow_server(Duration::from_millis(50));
+    let response = bitreq::get(url).with_read_timeout(Duration::from_millis(500)).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "0123456789");
+}
+
+#[test]
+fn total_timeout() {
+    let url = spawn_slow_server(Duration::from_millis(50));
+    let start = Instant::now();
+    let result = bitreq::get(url)
+        .with_read_timeout(Duration::from_millis(500))
+        .with_total_timeout(Duration::from_millis(250))
+        .send();
+    assert!(matches!(result, Err(bitreq::Error::TotalTimeout)), "{:?}", result);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn connect_timeout_of_tls_handshake() {
+    let (_listener, addr) = silent_server();
+    let start = Instant::now();
+    let result = bitreq::get(format!("https://{}/", addr))
+        .with_connect_timeout(Duration::from_millis(200))
+        .with_total_timeout(Duration::from_secs(5))
+        .send();
+    assert!(matches!(result, Err(bitreq::Error::ConnectTimeout)), "{:?}", result);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[cfg(feature = "proxy")]
+#[test]
+fn connect_timeout_of_proxy_handshake() {
+    let (_listener, addr) = silent_server();
+    let url = spawn_slow_server(Duration::ZERO);
+    for proxy in [format!("socks5://{}", addr), format!("http://{}", addr)] {
+        let proxy = bitreq::Proxy::new_socks5(&proxy).or_else(|_| bitreq::Proxy::new_http(&proxy));
+        let result = bitreq::get(&url)
+            .with_proxy(proxy.unwrap())
+            .with_connect_timeout(Duration::from_millis(200))
+            .send();
+        assert!(matches!(result, Err(bitreq::Error::ConnectTimeout)), "{:?}", result);
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_timeouts() {
+    let url = spawn_slow_server(Duration::from_secs(2));
+    let result = bitreq::get(&url).with_read_timeout(Duration::from_millis(200)).send_async().await;
+    assert!(matches!(result, Err(bitreq::Error::ReadTimeout)), "{:?}", result);
+
+    let mut response = bitreq::get(&url)
+        .with_read_timeout(Duration::from_millis(200))
+        .send_streaming_async()
+        .await
+        .unwrap();
+    assert!(matches!(response.chunk().await, Err(bitreq::Error::ReadTimeout)));
+
+    let url = spawn_slow_server(Duration::from_millis(50));
+    let response =
+        bitreq::get(&url).with_read_timeout(Duration::from_millis(500)).send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "0123456789");
+
+    let result =
+        bitreq::get(&url).with_total_timeout(Duration::from_millis(250)).send_async().await;
+    assert!(matches!(result, Err(bitreq::Error::TotalTimeout)), "{:?}", result);
+}
+
+#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[tokio::test]
+async fn async_connect_timeout() {
+    let (_listener, addr) = silent_server();
+    let result = bitreq::get(format!("https://{}/", addr))
+        .with_connect_timeout(Duration::from_millis(200))
+        .send_async()
+        .await;
+    assert!(matches!(result, Err(bitreq::Error::ConnectTimeout)), "{:?}", result);
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "rustls")]
+
+extern crate bitreq;
+mod setup;
+
+use std::io::Write;
+use std::sync::Arc;
+
+use bitreq::TlsConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+const CA: &[u8] = include_bytes!("tls/ca.pem");
+const SERVER_CERT: &[u8] = include_bytes!("tls/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("tls/server-key.pem");
+const CLIENT_CERT: &[u8] = include_bytes!("tls/client.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("tls/client-key.pem");
+/// The SHA-256 hash of the public key of `SERVER_CERT`.
+const SERVER_PIN: &str = "1a518102ea19ef4ba0654d20e0ec2674891a7c7423349fa5658b8cfac53a6779";
+
+fn pin(hex: &str) -> [u8; 32] {
+    let mut pin = [0; 32];
+    for (i, byte) in pin.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    pin
+}
+
+/// Spawns an HTTPS server with a certificate issued by the test CA, which optionally
+/// authenticates clients, returning its URL. Responses describe the client certificate and
+/// ALPN protocol of the connection.
+fn spawn_server() -> String {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_slice(CA).unwrap()).unwrap();
+    let verifier =
+        WebPkiClientVerifier::builder(Arc::new(roots)).allow_unauthenticated().build().unwrap();
+    let mut config = ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(
+            CertificateDer::pem_slice_iter(SERVER_CERT).map(Result::unwrap).collect(),
+            PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap(),
+        )
+        .unwrap();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let config = Arc::new(config);
+
+    let wrap = move |tcp| {
+        let conn = ServerConnection::new(Arc::clone(&config)).unwrap();
+        StreamOwned::new(conn, tcp)
+    };
+    let server = setup::spawn_server_with(wrap, |_, stream| {
+        let body = format!(
+            "client certificate: {}, alpn: {}",
+            stream.conn.peer_certificates().is_some(),
+            stream.conn.alpn_protocol().map_or("none".into(), String::from_utf8_lossy),
+        );
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes());
+        let _ = stream.flush();
+        false
+    });
+    format!("https://{}", server.addr)
+}
+
+fn custom_root() -> TlsConfig { TlsConfig::new().with_root_certificates_pem(CA).unwrap() }
+
+#[test]
+fn trusts_custom_root() {
+    let url = spawn_server();
+    assert!(bitreq::get(&url).send().is_err());
+
+    let response = bitreq::get(&url).with_tls_config(custom_root()).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: false, alpn: none");
+
+    let tls = custom_root().without_default_roots();
+    assert!(bitreq::get(&url).with_tls_config(tls).send().is_ok());
+}
+
+#[test]
+fn presents_client_certificate() {
+    let url = spawn_server();
+    let tls = custom_root().with_client_certificate_pem(CLIENT_CERT, CLIENT_KEY).unwrap();
+    let response = bitreq::get(&url).with_tls_config(tls).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: true, alpn: none");
+}
+
+#[test]
+fn checks_pinned_public_key() {
+    let url = spawn_server();
+    let tls = custom_root().with_pinned_public_key([0; 32]).with_pinned_public_key(pin(SERVER_PIN));
+    assert!(bitreq::get(&url).with_tls_config(tls).send().is_ok());
+
+    let tls = custom_root().with_pinned_public_key([0; 32]);
+    assert!(bitreq::get(&url).with_tls_config(tls).send().is_err());
+}
+
+#[test]
+fn offers_alpn_protocols() {
+    let url = spawn_server();
+    let tls = custom_root().with_alpn_protocols([&b"h2"[..], b"http/1.1"]);
+    let response = bitreq::get(&url).with_tls_config(tls).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: false, alpn: http/1.1");
+}
+
+#[test]
+fn client_tls_config() {
+    let url = spawn_server();
+    let tls = custom_root().with_client_certificate_pem(CLIENT_CERT, CLIENT_KEY).unwrap();
+    let client = bitreq::Client::new(1).with_tls_config(tls);
+    let response = client.send(bitreq::get(&url)).unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: true, alpn: none");
+
+    // A request's own configuration takes precedence.
+    let response = client.send(bitreq::get(&url).with_tls_config(custom_root())).unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: false, alpn: none");
+}
+
+#[test]
+fn rejects_invalid_pem() {
+    let result = TlsConfig::new().with_root_certificates_pem(b"not a certificate");
+    assert!(matches!(result, Err(bitreq::Error::InvalidPem(_))));
+    let result = TlsConfig::new().with_client_certificate_pem(CLIENT_CERT, CLIENT_CERT);
+    assert!(matches!(result, Err(bitreq::Error::InvalidPem(_))));
+}
+
+#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[tokio::test]
+async fn async_tls_config() {
+    let url = spawn_server();
+    let tls = custom_root()
+        .with_client_certificate_pem(CLIENT_CERT, CLIENT_KEY)
+        .unwrap()
+        .with_pinned_public_key(pin(SERVER_PIN))
+        .with_alpn_protocols(["http/1.1"]);
+    let response = bitreq::get(&url).with_tls_config(tls.clone()).send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: true, alpn: http/1.1");
+
+    let client = bitreq::Client::new(1).with_tls_config(tls);
+    let response = client.send_async(bitreq::get(&url)).await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "client certificate: true, alpn: http/1.1");
+
+    assert!(bitreq::get(&url).send_async().await.is_err());
+}
@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! A scriptable mock of the `bitcoind` JSON-RPC server.
+//!
+//! Allows testing code using the clients in `client_sync` without a `bitcoind` executable: load
+//! the mock with canned replies, point a client at [`MockServer::url`] and assert on the recorded
+//! requests.
+//!
+//! ```
+//! # #[cfg(feature = "client-sync")] {
+//! use corepc_client::mock::{MockServer, Reply};
+//! use serde_json::json;
+//!
+//! let mock = MockServer::new().unwrap();
+//! mock.on("getblockcount", Reply::Result(json!(101)));
+//!
+//! let client = corepc_client::client_sync::v30::Client::new(&mock.url());
+//! assert_eq!(client.get_block_count().unwrap().0, 101);
+//! assert_eq!(mock.requests()[0].method, "getblockcount");
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{fmt, io, thread};
+
+use jsonrpc::error::{result_to_response, standard_error, RpcError, StandardError};
+use jsonrpc::server::http::{HttpHandler, HttpResponse, HttpServer};
+use jsonrpc::server::ServerRequest;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+/// The reply of the mock to a request.
+#[derive(Clone, Debug)]
+pub enum Reply {
+    /// Responds with the given result.
+    Result(Value),
+    /// Responds with the given JSON-RPC error.
+    Error(RpcError),
+    /// Responds with the given HTTP status and body, eg. `401` or `500` with
+    /// `Work queue depth exceeded`.
+    Http {
+        /// The HTTP status code.
+        status: u16,
+        /// The raw response body.
+        body: String,
+    },
+    /// Responds with a `200 OK` whose body is not valid JSON.
+    Malformed(String),
+    /// Waits before sending the reply.
+    Delayed(Duration, Box<Reply>),
+}
+
+impl Reply {
+    /// Responds with a JSON-RPC error with the given code and message, eg. `-28` while warming up.
+    pub fn error(code: i32, message: &str) -> Reply {
+        Reply::Error(RpcError { code, message: message.to_owned(), data: None })
+    }
+
+    /// Waits `delay` before sending this reply.
+    pub fn delayed(self, delay: Duration) -> Reply { Reply::Delayed(delay, Box::new(self)) }
+}
+
+/// A request received by the mock.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedRequest {
+    /// The name of the RPC call.
+    pub method: String,
+    /// Parameters of the RPC call, `null` if absent.
+    pub params: Value,
+}
+
+/// Replies are looked up by method and, optionally, parameters serialized as a string.
+type Key = (String, Option<String>);
+
+#[derive(Default)]
+struct State {
+    replies: HashMap<Key, Reply>,
+    queued: HashMap<Key, VecDeque<Reply>>,
+    requests: Vec<RecordedRequest>,
+}
+
+impl State {
+    /// Finds the reply to a request: queued ones first, those matching the parameters first.
+    fn reply(&mut self, method: &str, params: &Value) -> Reply {
+        let keys = [(method.to_owned(), Some(params.to_string())), (method.to_owned(), None)];
+        for key in &keys {
+            if let Some(reply) = self.queued.get_mut(key).and_then(VecDeque::pop_front) {
+                return reply;
+            }
+        }
+        for key in &keys {
+            if let Some(reply) = self.replies.get(key) {
+                return reply.clone();
+            }
+        }
+        Reply::Error(standard_error(StandardError::MethodNotFound, None))
+    }
+}
+
+struct Handler(Arc<Mutex<State>>);
+
+impl Handler {
+    /// Returns the response to a single request, or the HTTP response replacing the whole body.
+    fn handle_request(
+        &self,
+        request: &RawValue,
+    ) -> Result<Option<jsonrpc::Response>, HttpResponse> {
+        let request: ServerRequest = match serde_json::from_str(request.get()) {
+            Ok(request) => request,
+            Err(_) => {
+                let error = standard_error(StandardError::InvalidRequest, None);
+                return Ok(Some(result_to_response(Err(error), Value::Null)));
+            }
+        };
+        let params = match request.params {
+            Some(ref params) => serde_json::from_str(params.get()).unwrap_or(Value::Null),
+            None => Value::Null,
+        };
+
+        let mut reply = {
+            let mut state = self.0.lock().expect("poisoned mutex");
+            let reply = state.reply(&request.method, &params);
+            state.requests.push(RecordedRequest { method: request.method.clone(), params });
+            reply
+        };
+        loop {
+            let result = match reply {
+                Reply::Result(result) => Ok(result),
+                Reply::Error(error) => Err(error),
+                Reply::Http { status, body } => return Err(HttpResponse { status, body }),
+                Reply::Malformed(body) => return Err(HttpResponse::json(body)),
+                Reply::Delayed(delay, next) => {
+                    thread::sleep(delay);
+                    reply = *next;
+                    continue;
+                }
+            };
+            return Ok(request.id.map(|id| result_to_response(result, id)));
+        }
+    }
+}
+
+impl HttpHandler for Handler {
+    fn handle_http(&self, body: &str) -> HttpResponse {
+        let parse_error = || {
+            let error = standard_error(StandardError::ParseError, None);
+            let response = result_to_response(Err(error), Value::Null);
+            HttpResponse::json(serde_json::to_string(&response).expect("serializes"))
+        };
+
+        if body.trim_start().starts_with('[') {
+            let batch: Vec<&RawValue> = match serde_json::from_str(body) {
+                Ok(batch) => batch,
+                Err(_) => return parse_error(),
+            };
+            let mut responses = vec![];
+            for request in batch {
+                match self.handle_request(request) {
+                    Ok(response) => responses.extend(response),
+                    Err(http) => return http,
+                }
+            }
+            if responses.is_empty() {
+                return HttpResponse::no_content();
+            }
+            HttpResponse::json(serde_json::to_string(&responses).expect("serializes"))
+        } else {
+            let request: &RawValue = match serde_json::from_str(body) {
+                Ok(request) => request,
+                Err(_) => return parse_error(),
+            };
+            match self.handle_request(request) {
+                Ok(Some(response)) =>
+                    HttpResponse::json(serde_json::to_string(&response).expect("serializes")),
+                Ok(None) => HttpResponse::no_content(),
+                Err(http) => http,
+            }
+        }
+    }
+}
+
+/// A mock `bitcoind` JSON-RPC server listening on localhost.
+///
+/// Requests to methods without a reply get the "Method not found" error. The server stops when
+/// dropped.
+pub struct MockServer {
+    server: HttpServer,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// Starts a mock server without authentication on an available port.
+    pub fn new() -> io::Result<MockServer> { MockServer::start(HttpServer::builder()) }
+
+    /// Starts a mock server requiring the given credentials, other requests get a `401`.
+    pub fn with_auth(user: &str, pass: &str) -> io::Result<MockServer> {
+        MockServer::start(HttpServer::builder().auth(user, Some(pass)))
+    }
+
+    fn start(builder: jsonrpc::server::http::Builder) -> io::Result<MockServer> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let server = builder.bind("127.0.0.1:0", Handler(Arc::clone(&state)))?;
+        Ok(MockServer { server, state })
+    }
+
+    /// Returns the URL to pass to the clients eg. `client_sync::v30::Client::new`.
+    pub fn url(&self) -> String { self.server.url() }
+
+    /// Replies to every call of `method` with `reply`, whatever the parameters.
+    pub fn on(&self, method: &str, reply: Reply) { self.insert((method.to_owned(), None), reply) }
+
+    /// Replies to the calls of `method` with the given `params` with `reply`.
+    ///
+    /// Takes precedence over the reply set with [`MockServer::on`].
+    pub fn on_params(&self, method: &str, params: Value, reply: Reply) {
+        self.insert((method.to_owned(), Some(params.to_string())), reply)
+    }
+
+    /// Replies to the next call of `method` with `reply`.
+    ///
+    /// Replies queued for the same method are used in order, before the ones set with
+    /// [`MockServer::on`] and [`MockServer::on_params`].
+    pub fn once(&self, method: &str, reply: Reply) {
+        let mut state = self.state.lock().expect("poisoned mutex");
+        state.queued.entry((method.to_owned(), None)).or_default().push_back(reply);
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().expect("poisoned mutex").requests.clone()
+    }
+
+    /// Removes all the replies and recorded requests.
+    pub fn reset(&self) { *self.state.lock().expect("poisoned mutex") = State::default(); }
+
+    fn insert(&self, key: Key, reply: Reply) {
+        self.state.lock().expect("poisoned mutex").replies.insert(key, reply);
+    }
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockServer").field("url", &self.url()).finish()
+    }
+}
+
+#[cfg(all(test, feature = "client-sync"))]
+mod tests {
+    use std::time::Instant;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::client_sync::v30::Client;
+    use crate::client_sync::{Auth, Error};
+
+    #[test]
+    fn canned_replies() {
+        let mock = MockServer::new().unwrap();
+        mock.on("getblockhash", Reply::Result(json!("00".repeat(32))));
+        mock.on_params("getblockhash", json!([1]), Reply::Result(json!("11".repeat(32))));
+        mock.once("getblockcount", Reply::error(-28, "Loading block index..."));
+        mock.on("getblockcount", Reply::Result(json!(7)));
+
+        let client = Client::new(&mock.url());
+        let hash: String = client.call("getblockhash", &[1.into()]).unwrap();
+        assert_eq!(hash, "11".repeat(32));
+        let hash: String = client.call("getblockhash", &[2.into()]).unwrap();
+        assert_eq!(hash, "00".repeat(32));
+
+        match client.get_block_count() {
+            Err(Error::JsonRpc(jsonrpc::Error::Rpc(e))) => assert_eq!(e.code, -28),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(client.get_block_count().unwrap().0, 7);
+        assert!(client.call::<Value>("getnetworkinfo", &[]).is_err());
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 5);
+        assert_eq!(
+            requests[0],
+            RecordedRequest { method: "getblockhash".into(), params: json!([1]) }
+        );
+        assert_eq!(requests[4].method, "getnetworkinfo");
+    }
+
+    #[test]
+    fn http_failures() {
+        let mock = MockServer::with_auth("user", "pass").unwrap();
+        mock.on("getblockcount", Reply::Result(json!(1)));
+
+        let client =
+            Client::new_with_auth(&mock.url(), Auth::UserPass("user".into(), "pass".into()))
+                .unwrap();
+        assert_eq!(client.get_block_count().unwrap().0, 1);
+        let client =
+            Client::new_with_auth(&mock.url(), Auth::UserPass("user".into(), "x".into())).unwrap();
+        assert!(client.get_block_count().is_err());
+
+        let client =
+            Client::new_with_auth(&mock.url(), Auth::UserPass("user".into(), "pass".into()))
+                .unwrap();
+        mock.once(
+            "getblockcount",
+            Reply::Http { status: 500, body: "Work queue depth exceeded".into() },
+        );
+        mock.once("getblockcount", Reply::Malformed("{\"result\":".into()));
+        mock.once("getblockcount", Reply::Result(json!(2)).delayed(Duration::from_millis(200)));
+        assert!(client.get_block_count().is_err());
+        assert!(client.get_block_count().is_err());
+        let start = Instant::now();
+        assert_eq!(client.get_block_count().unwrap().0, 2);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
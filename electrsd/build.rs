@@ -1,4 +1,12 @@
-fn main() { download::start().unwrap(); }
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_LEGACY").is_some() {
+        println!(
+            "cargo:warning=the `legacy` feature of electrsd is deprecated and ignored, the command \
+             line arguments passed to electrs follow `Conf::version`"
+        );
+    }
+    download::start().unwrap();
+}
 
 #[cfg(any(docsrs, not(feature = "download")))]
 mod download {
@@ -10,7 +18,7 @@ mod download {
     use std::fs::File;
     use std::io::{BufRead, BufReader, Cursor};
     use std::os::unix::fs::PermissionsExt;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
     use std::str::FromStr;
 
     use bitcoin_hashes::{sha256, Hash};
@@ -31,7 +39,21 @@ mod download {
         panic!("no sha256 entry for {} in electrsd/sha256", filename);
     }
 
+    /// The directory executables are downloaded in, as found by `download_dir` in the library.
+    fn download_dir(out_dir: &Path) -> PathBuf {
+        if let Some(path) = std::env::var_os("ELECTRSD_DOWNLOAD_DIR") {
+            return PathBuf::from(path);
+        }
+        out_dir.join("electrs")
+    }
+
     pub(crate) fn start() -> anyhow::Result<()> {
+        println!("cargo:rerun-if-changed=build.rs");
+        println!("cargo:rerun-if-changed=sha256");
+        println!("cargo:rerun-if-changed=src/versions.rs");
+        println!("cargo:rerun-if-env-changed=ELECTRSD_DOWNLOAD_DIR");
+        println!("cargo:rerun-if-env-changed=ELECTRSD_DOWNLOAD_ENDPOINT");
+        println!("cargo:rerun-if-env-changed=ELECTRSD_SKIP_DOWNLOAD");
         if std::env::var_os("ELECTRSD_SKIP_DOWNLOAD").is_some() {
             return Ok(());
         }
@@ -40,7 +62,7 @@ mod download {
         let download_filename = format!("{}.zip", download_filename_without_extension);
         let expected_hash = get_expected_sha256(&download_filename)?;
         let out_dir = std::env::var_os("OUT_DIR").unwrap();
-        let electrs_exe_home = Path::new(&out_dir).join("electrs");
+        let electrs_exe_home = download_dir(Path::new(&out_dir));
         let destination_filename =
             electrs_exe_home.join(&download_filename_without_extension).join("electrs");
 
@@ -0,0 +1,387 @@
+//! Async Electrum client
+//!
+//! A minimal tokio based client speaking the Electrum protocol (newline-delimited JSON-RPC over
+//! TCP) to the electrs process, with support for header and script hash subscriptions.
+//!
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use electrum_client::bitcoin::block::Header;
+use electrum_client::bitcoin::hex::FromHex;
+use electrum_client::bitcoin::{consensus, Script};
+use futures_core::Stream;
+use jsonrpc::serde::de::DeserializeOwned;
+use jsonrpc::serde::Deserialize;
+use jsonrpc::serde_json::value::RawValue;
+use jsonrpc::serde_json::{self, Value};
+use jsonrpc::{Request, Response};
+use log::{debug, warn};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::{script_hash, ElectrsD, Error};
+
+const HEADERS_SUBSCRIBE: &str = "blockchain.headers.subscribe";
+const SCRIPTHASH_SUBSCRIBE: &str = "blockchain.scripthash.subscribe";
+
+impl ElectrsD {
+    /// Connect an [AsyncClient] to the electrum endpoint of this electrs process.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub async fn async_client(&self) -> Result<AsyncClient, Error> {
+        AsyncClient::new(&self.electrum_url).await
+    }
+}
+
+/// Async Electrum client, requests are pipelined over a single connection and matched by id.
+pub struct AsyncClient {
+    /// Write half of the connection, requests are written one line at a time
+    writer: tokio::sync::Mutex<OwnedWriteHalf>,
+    /// State shared with the task reading the connection
+    shared: Arc<Shared>,
+    /// Id of the next request
+    nonce: AtomicUsize,
+    /// Task reading responses and notifications from the connection
+    reader: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Requests waiting for a response, `None` once the connection has been closed
+    pending: Mutex<Option<HashMap<u64, oneshot::Sender<Response>>>>,
+    /// Subscribers waiting for notifications
+    subscriptions: Mutex<HashMap<SubscriptionKey, Vec<mpsc::UnboundedSender<Box<RawValue>>>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SubscriptionKey {
+    Headers,
+    ScriptHash(String),
+}
+
+/// Minimal view of an incoming line, used to tell responses and notifications apart
+#[derive(Deserialize)]
+#[serde(crate = "jsonrpc::serde")]
+struct Incoming {
+    id: Option<Value>,
+    method: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "jsonrpc::serde")]
+struct RawNotification {
+    method: String,
+    params: Box<RawValue>,
+}
+
+impl AsyncClient {
+    /// Connect to the electrum server at `addr`, eg. [ElectrsD::electrum_url]
+    ///
+    /// Must be called from within a tokio runtime.
+    pub async fn new(addr: &str) -> Result<AsyncClient, Error> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (read_half, write_half) = stream.into_split();
+
+        let shared = Arc::new(Shared::default());
+        *shared.pending.lock().expect("poisoned mutex") = Some(HashMap::new());
+        let reader = tokio::spawn(read_loop(read_half, shared.clone()));
+
+        Ok(AsyncClient {
+            writer: tokio::sync::Mutex::new(write_half),
+            shared,
+            nonce: AtomicUsize::new(1),
+            reader,
+        })
+    }
+
+    /// Makes a request and deserializes the result
+    pub async fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &[Value],
+    ) -> Result<R, Error> {
+        let response = self.send_request(method, params).await?;
+        Ok(response.result()?)
+    }
+
+    /// Sends a `server.ping` request
+    pub async fn ping(&self) -> Result<(), Error> { self.call("server.ping", &[]).await }
+
+    /// Subscribe to new block headers, returns the current tip and a stream of the following ones
+    pub async fn block_headers_subscribe(
+        &self,
+    ) -> Result<(HeaderNotification, Subscription<HeaderNotification>), Error> {
+        let subscription = self.subscribe(SubscriptionKey::Headers);
+        let tip: RawHeader = self.call(HEADERS_SUBSCRIBE, &[]).await?;
+        Ok((tip.try_into()?, subscription))
+    }
+
+    /// Subscribe to status changes of the given script, returns the current status and a stream
+    /// of the following changes
+    pub async fn script_subscribe(
+        &self,
+        script: &Script,
+    ) -> Result<(Option<String>, Subscription<ScriptNotification>), Error> {
+        let script_hash = script_hash(script);
+        let subscription = self.subscribe(SubscriptionKey::ScriptHash(script_hash.clone()));
+        let status = self.call(SCRIPTHASH_SUBSCRIBE, &[script_hash.into()]).await?;
+        Ok((status, subscription))
+    }
+
+    fn subscribe<T>(&self, key: SubscriptionKey) -> Subscription<T> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut subscriptions = self.shared.subscriptions.lock().expect("poisoned mutex");
+        subscriptions.entry(key).or_default().push(sender);
+        Subscription { receiver, _notification: PhantomData }
+    }
+
+    async fn send_request(&self, method: &str, params: &[Value]) -> Result<Response, Error> {
+        let params = jsonrpc::try_arg(params).map_err(jsonrpc::Error::Json)?;
+        let id = self.nonce.fetch_add(1, Ordering::Relaxed) as u64;
+        let request =
+            Request { method, params: Some(&params), id: Value::from(id), jsonrpc: Some("2.0") };
+        let mut line = serde_json::to_vec(&request).map_err(jsonrpc::Error::Json)?;
+        line.push(b'\n');
+
+        let (sender, receiver) = oneshot::channel();
+        match self.shared.pending.lock().expect("poisoned mutex").as_mut() {
+            Some(pending) => pending.insert(id, sender),
+            None => return Err(Error::ConnectionClosed),
+        };
+
+        let written = {
+            let mut writer = self.writer.lock().await;
+            match writer.write_all(&line).await {
+                Ok(()) => writer.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = written {
+            if let Some(pending) = self.shared.pending.lock().expect("poisoned mutex").as_mut() {
+                pending.remove(&id);
+            }
+            return Err(e.into());
+        }
+
+        receiver.await.map_err(|_| Error::ConnectionClosed)
+    }
+}
+
+impl Drop for AsyncClient {
+    fn drop(&mut self) { self.reader.abort(); }
+}
+
+async fn read_loop(read_half: OwnedReadHalf, shared: Arc<Shared>) {
+    let mut lines = BufReader::new(read_half).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => shared.dispatch(&line),
+            Ok(None) => break,
+            Err(e) => {
+                debug!("electrum connection error: {}", e);
+                break;
+            }
+        }
+    }
+    shared.close();
+}
+
+impl Shared {
+    fn dispatch(&self, line: &str) {
+        let incoming: Incoming = match serde_json::from_str(line) {
+            Ok(incoming) => incoming,
+            Err(e) => {
+                warn!("ignoring invalid electrum message {:?}: {}", line, e);
+                return;
+            }
+        };
+
+        match incoming {
+            Incoming { id: Some(id), .. } if !id.is_null() => {
+                let response: Response = match serde_json::from_str(line) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        warn!("ignoring invalid electrum response {:?}: {}", line, e);
+                        return;
+                    }
+                };
+                let mut pending = self.pending.lock().expect("poisoned mutex");
+                let sender = match (id.as_u64(), pending.as_mut()) {
+                    (Some(id), Some(pending)) => pending.remove(&id),
+                    _ => None,
+                };
+                drop(pending);
+                match sender {
+                    Some(sender) => {
+                        let _ = sender.send(response);
+                    }
+                    None => warn!("ignoring electrum response with unknown id {}", id),
+                }
+            }
+            Incoming { method: Some(_), .. } => match serde_json::from_str(line) {
+                Ok(notification) => self.notify(notification),
+                Err(e) => warn!("ignoring invalid electrum notification {:?}: {}", line, e),
+            },
+            _ => warn!("ignoring electrum message without id nor method: {:?}", line),
+        }
+    }
+
+    fn notify(&self, notification: RawNotification) {
+        let key = match notification.method.as_str() {
+            HEADERS_SUBSCRIBE => SubscriptionKey::Headers,
+            SCRIPTHASH_SUBSCRIBE =>
+                match serde_json::from_str::<(String, Option<String>)>(notification.params.get()) {
+                    Ok((script_hash, _)) => SubscriptionKey::ScriptHash(script_hash),
+                    Err(e) => {
+                        warn!("ignoring invalid script hash notification: {}", e);
+                        return;
+                    }
+                },
+            method => {
+                debug!("ignoring notification for {}", method);
+                return;
+            }
+        };
+
+        let mut subscriptions = self.subscriptions.lock().expect("poisoned mutex");
+        if let Some(senders) = subscriptions.get_mut(&key) {
+            // dropped subscriptions are removed the first time we fail to notify them
+            senders.retain(|sender| sender.send(notification.params.clone()).is_ok());
+            if senders.is_empty() {
+                subscriptions.remove(&key);
+            }
+        }
+    }
+
+    /// Fails all the pending requests and ends all the subscriptions
+    fn close(&self) {
+        self.pending.lock().expect("poisoned mutex").take();
+        self.subscriptions.lock().expect("poisoned mutex").clear();
+    }
+}
+
+/// A notification that can be received from a [Subscription]
+pub trait Notification: Sized {
+    /// Parse the notification from the `params` of the JSON-RPC notification
+    fn from_params(params: &RawValue) -> Result<Self, Error>;
+}
+
+/// Stream of notifications for a subscription made with [AsyncClient]
+///
+/// The stream ends when the connection to the electrum server is closed.
+pub struct Subscription<T> {
+    receiver: mpsc::UnboundedReceiver<Box<RawValue>>,
+    _notification: PhantomData<fn() -> T>,
+}
+
+impl<T: Notification> Subscription<T> {
+    /// Wait for the next notification, returns `None` if the connection has been closed
+    pub async fn next(&mut self) -> Option<Result<T, Error>> {
+        self.receiver.recv().await.map(|params| T::from_params(&params))
+    }
+}
+
+impl<T: Notification> Stream for Subscription<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx).map(|params| params.map(|params| T::from_params(&params)))
+    }
+}
+
+/// New block header notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderNotification {
+    /// Height of the block
+    pub height: usize,
+    /// Header of the block
+    pub header: Header,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "jsonrpc::serde")]
+struct RawHeader {
+    height: usize,
+    hex: String,
+}
+
+impl TryFrom<RawHeader> for HeaderNotification {
+    type Error = Error;
+
+    fn try_from(raw: RawHeader) -> Result<Self, Error> {
+        let bytes = Vec::<u8>::from_hex(&raw.hex)
+            .map_err(|e| Error::InvalidNotification(format!("invalid header hex: {}", e)))?;
+        let header = consensus::deserialize(&bytes)
+            .map_err(|e| Error::InvalidNotification(format!("invalid header: {}", e)))?;
+        Ok(HeaderNotification { height: raw.height, header })
+    }
+}
+
+impl Notification for HeaderNotification {
+    fn from_params(params: &RawValue) -> Result<Self, Error> {
+        let (raw,): (RawHeader,) = serde_json::from_str(params.get())
+            .map_err(|e| Error::InvalidNotification(e.to_string()))?;
+        raw.try_into()
+    }
+}
+
+/// Script status change notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptNotification {
+    /// Electrum script hash of the script, as passed to `blockchain.scripthash.subscribe`
+    pub script_hash: String,
+    /// New status of the script, `None` if the script has no history
+    pub status: Option<String>,
+}
+
+impl Notification for ScriptNotification {
+    fn from_params(params: &RawValue) -> Result<Self, Error> {
+        let (script_hash, status) = serde_json::from_str(params.get())
+            .map_err(|e| Error::InvalidNotification(e.to_string()))?;
+        Ok(ScriptNotification { script_hash, status })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::setup_nodes;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    #[test]
+    fn test_async_client() {
+        let (_, bitcoind, electrsd) = setup_nodes();
+        runtime().block_on(async {
+            let client = electrsd.async_client().await.unwrap();
+            client.ping().await.unwrap();
+
+            let (tip, mut headers) = client.block_headers_subscribe().await.unwrap();
+            assert_eq!(tip.height, 1);
+
+            let address = bitcoind.client.new_address().unwrap();
+            let (status, mut script) =
+                client.script_subscribe(&address.script_pubkey()).await.unwrap();
+            assert_eq!(status, None);
+
+            bitcoind.client.generate_to_address(1, &address).unwrap();
+            electrsd.trigger().unwrap();
+
+            let header = headers.next().await.unwrap().unwrap();
+            assert_eq!(header.height, 2);
+            let notification = script.next().await.unwrap().unwrap();
+            assert_eq!(notification.script_hash, crate::script_hash(&address.script_pubkey()));
+            assert!(notification.status.is_some());
+        });
+    }
+}
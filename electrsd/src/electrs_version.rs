@@ -0,0 +1,111 @@
+//! Runtime selection of the electrs version
+//!
+//! The version features select the executable downloaded at build time, while [ElectrsVersion]
+//! allows running other flavours or versions found in a download directory, for example to
+//! compare electrs and esplora against the same bitcoind.
+//!
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::versions;
+
+/// Flavours and versions of electrs known to this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ElectrsVersion {
+    /// romanz/electrs v0.8.10
+    V0_8_10,
+    /// romanz/electrs v0.9.1
+    V0_9_1,
+    /// romanz/electrs v0.9.11
+    V0_9_11,
+    /// romanz/electrs v0.10.6
+    V0_10_6,
+    /// Blockstream/electrs, the esplora backend, at commit a33e97e1
+    EsploraA33e97e1,
+}
+
+impl ElectrsVersion {
+    /// All the known versions
+    pub const ALL: [ElectrsVersion; 5] = [
+        ElectrsVersion::V0_8_10,
+        ElectrsVersion::V0_9_1,
+        ElectrsVersion::V0_9_11,
+        ElectrsVersion::V0_10_6,
+        ElectrsVersion::EsploraA33e97e1,
+    ];
+
+    /// The version selected by the enabled version feature, `V0_10_6` when building with all
+    /// features
+    pub fn from_features() -> Self {
+        *Self::ALL
+            .iter()
+            .find(|version| version.as_str() == versions::VERSION)
+            .expect("a version feature is enabled")
+    }
+
+    /// The version string, as used in the name of the downloaded executable
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElectrsVersion::V0_8_10 => "v0.8.10",
+            ElectrsVersion::V0_9_1 => "v0.9.1",
+            ElectrsVersion::V0_9_11 => "v0.9.11",
+            ElectrsVersion::V0_10_6 => "v0.10.6",
+            ElectrsVersion::EsploraA33e97e1 => "esplora_a33e97e1a1fc63fa9c20a116bb92579bbf43b254",
+        }
+    }
+
+    /// Returns `true` for the Blockstream (esplora) flavour of electrs
+    pub fn is_esplora(&self) -> bool { matches!(self, ElectrsVersion::EsploraA33e97e1) }
+
+    /// Name of the directory containing the downloaded executable eg. `electrs_linux_v0.10.6`
+    pub fn exe_name(&self) -> String { format!("electrs_{}_{}", versions::OS, self.as_str()) }
+
+    /// Path of the executable in `dir`, laid out like the download directory, ie.
+    /// `<dir>/<exe_name>/electrs`, if it exists
+    pub fn exe_path_in<P: AsRef<Path>>(&self, dir: P) -> Option<PathBuf> {
+        let path = dir.as_ref().join(self.exe_name()).join("electrs");
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Path of the executable in the download directory, if this version has been downloaded
+    ///
+    /// The download directory is `ELECTRSD_DOWNLOAD_DIR` if set at runtime or at build time, so
+    /// that building with different version features and the same `ELECTRSD_DOWNLOAD_DIR` makes
+    /// all of them available at runtime.
+    pub fn downloaded_exe_path(&self) -> Option<PathBuf> { self.exe_path_in(crate::download_dir()) }
+
+    /// Old versions need to be asked to log with `-vvv`
+    pub(crate) fn uses_verbose_flag(&self) -> bool {
+        matches!(
+            self,
+            ElectrsVersion::V0_8_10 | ElectrsVersion::V0_9_1 | ElectrsVersion::EsploraA33e97e1
+        )
+    }
+
+    /// Old versions import blocks with JSON-RPC instead of connecting to bitcoind p2p port
+    pub(crate) fn uses_jsonrpc_import(&self) -> bool {
+        matches!(self, ElectrsVersion::V0_8_10 | ElectrsVersion::EsploraA33e97e1)
+    }
+
+    /// Esplora takes the content of the cookie file instead of its path
+    pub(crate) fn uses_cookie_value(&self) -> bool { self.is_esplora() }
+
+    /// Old electrs servers do not handle the client protocol negotiation reliably
+    pub(crate) fn uses_plaintext_client(&self) -> bool {
+        matches!(self, ElectrsVersion::V0_8_10 | ElectrsVersion::V0_9_1 | ElectrsVersion::V0_9_11)
+    }
+}
+
+impl Default for ElectrsVersion {
+    fn default() -> Self { ElectrsVersion::from_features() }
+}
+
+impl fmt::Display for ElectrsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
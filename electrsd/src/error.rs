@@ -26,6 +26,18 @@ pub enum Error {
 
     /// Returned if both env vars `ELECTRS_EXEC` and `ELECTRS_EXE` are found
     BothEnvVars,
+
+    /// Wrapper of jsonrpc Error, returned by the async client
+    #[cfg(feature = "async")]
+    Jsonrpc(jsonrpc::Error),
+
+    /// Returned by the async client when the connection to electrs has been closed
+    #[cfg(feature = "async")]
+    ConnectionClosed,
+
+    /// Returned by the async client when a notification cannot be parsed
+    #[cfg(feature = "async")]
+    InvalidNotification(String),
 }
 
 impl std::error::Error for Error {
@@ -34,6 +46,8 @@ impl std::error::Error for Error {
             Error::Io(e) => Some(e),
             Error::Bitcoind(e) => Some(e),
             Error::ElectrumClient(e) => Some(e),
+            #[cfg(feature = "async")]
+            Error::Jsonrpc(e) => Some(e),
             // Error::BitcoinCoreRpc(e) => Some(e),
             #[cfg(not(target_os = "windows"))]
             Error::Nix(e) => Some(e),
@@ -59,6 +73,11 @@ impl From<electrum_client::Error> for Error {
     fn from(e: electrum_client::Error) -> Self { Error::ElectrumClient(e) }
 }
 
+#[cfg(feature = "async")]
+impl From<jsonrpc::Error> for Error {
+    fn from(e: jsonrpc::Error) -> Self { Error::Jsonrpc(e) }
+}
+
 #[cfg(not(target_os = "windows"))]
 impl From<nix::Error> for Error {
     fn from(e: nix::Error) -> Self { Error::Nix(e) }
@@ -3,18 +3,24 @@
 //! Utility to run a regtest electrsd process, useful in integration testing environment
 //!
 
+#[cfg(feature = "async")]
+mod async_client;
+mod electrs_version;
 mod error;
 mod ext;
+pub mod mock;
 mod versions;
 
 pub extern crate bitcoind;
 pub extern crate corepc_client;
 pub extern crate electrum_client;
+#[cfg(feature = "async")]
+pub extern crate jsonrpc;
 
 use std::env;
 use std::ffi::OsStr;
 use std::net::TcpStream;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::time::Duration;
 
@@ -22,13 +28,19 @@ use bitcoind::anyhow::Context;
 use bitcoind::serde_json::Value;
 use bitcoind::tempfile::TempDir;
 use bitcoind::{anyhow, get_available_port, BitcoinD};
+use electrum_client::bitcoin::hashes::{sha256, Hash};
+use electrum_client::bitcoin::hex::DisplayHex;
+use electrum_client::bitcoin::Script;
 use electrum_client::raw_client::{ElectrumPlaintextStream, RawClient};
 use log::{debug, error, warn};
 
 #[rustfmt::skip] // Keep public re-exports separate.
 pub use error::Error;
-
-const IS_ALL_FEATURES_BUILD: bool = cfg!(feature = "all_features");
+#[cfg(feature = "async")]
+pub use async_client::{
+    AsyncClient, HeaderNotification, Notification, ScriptNotification, Subscription,
+};
+pub use electrs_version::ElectrsVersion;
 
 /// Electrs configuration parameters, implements a convenient [Default] for most common use.
 ///
@@ -40,6 +52,7 @@ const IS_ALL_FEATURES_BUILD: bool = cfg!(feature = "all_features");
 /// conf.network = "regtest";
 /// conf.tmpdir = None;
 /// conf.staticdir = None;
+/// conf.version = electrsd::ElectrsVersion::from_features();
 /// assert_eq!(conf, electrsd::Conf::default());
 /// ```
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -73,6 +86,11 @@ pub struct Conf<'a> {
     /// Persistent directory path
     pub staticdir: Option<PathBuf>,
 
+    /// Version of the electrs executable, used to pass the command line arguments it understands.
+    /// Defaults to the version selected by the enabled feature, set it when running an executable
+    /// of another version eg. one returned by [ElectrsVersion::downloaded_exe_path]
+    pub version: ElectrsVersion,
+
     /// Try to spawn the process `attempt` time
     ///
     /// The OS is giving available ports to use, however, they aren't booked, so it could rarely
@@ -83,22 +101,14 @@ pub struct Conf<'a> {
 
 impl Default for Conf<'_> {
     fn default() -> Self {
-        #[allow(unused_mut)]
-        let mut args = vec![];
-
-        let should_version_use_verbose_flag =
-            cfg!(any(feature = "electrs_0_9_1", feature = "electrs_0_8_10", feature = "legacy"));
-        if !IS_ALL_FEATURES_BUILD && should_version_use_verbose_flag {
-            args.push("-vvv");
-        }
-
         Conf {
-            args,
+            args: vec![],
             view_stderr: false,
             http_enabled: false,
             network: "regtest",
             tmpdir: None,
             staticdir: None,
+            version: ElectrsVersion::default(),
             attempts: 3,
         }
     }
@@ -116,6 +126,8 @@ pub struct ElectrsD {
     pub electrum_url: String,
     /// Url to connect to esplora protocol (http)
     pub esplora_url: Option<String>,
+    /// Version of the running electrs
+    version: ElectrsVersion,
 }
 
 /// The DataDir struct defining the kind of data directory electrs will use.
@@ -158,6 +170,9 @@ impl ElectrsD {
         }
 
         let mut args = conf.args.clone();
+        if conf.version.uses_verbose_flag() && !args.contains(&"-vvv") {
+            args.push("-vvv");
+        }
 
         let work_dir = match (&conf.tmpdir, &conf.staticdir) {
             (Some(_), Some(_)) => return Err(Error::BothDirsSpecified.into()),
@@ -180,7 +195,7 @@ impl ElectrsD {
         args.push(conf.network);
 
         let cookie_flag;
-        let cookie_val = if !IS_ALL_FEATURES_BUILD && cfg!(feature = "legacy") {
+        let cookie_val = if conf.version.uses_cookie_value() {
             cookie_flag = "--cookie";
             std::fs::read_to_string(&bitcoind.params.cookie_file)?
         } else {
@@ -196,9 +211,7 @@ impl ElectrsD {
 
         let p2p_socket;
 
-        let should_version_use_jsonrpc_import =
-            cfg!(any(feature = "electrs_0_8_10", feature = "esplora_a33e97e1", feature = "legacy"));
-        if !IS_ALL_FEATURES_BUILD && should_version_use_jsonrpc_import {
+        if conf.version.uses_jsonrpc_import() {
             args.push("--jsonrpc-import");
         } else {
             args.push("--daemon-p2p-addr");
@@ -252,12 +265,7 @@ impl ElectrsD {
                     return Err(Error::EarlyExit(status).into());
                 }
             }
-            let client_result = if !IS_ALL_FEATURES_BUILD
-                && cfg!(any(
-                    feature = "electrs_0_8_10",
-                    feature = "electrs_0_9_1",
-                    feature = "electrs_0_9_11"
-                )) {
+            let client_result = if conf.version.uses_plaintext_client() {
                 // Old electrs servers do not handle v0.25 protocol negotiation reliably.
                 // Build RawClient directly from a plaintext stream to preserve previous behavior.
                 TcpStream::connect(&electrum_url).map(RawClient::from).map_err(Into::into)
@@ -271,7 +279,7 @@ impl ElectrsD {
             }
         };
 
-        Ok(ElectrsD { process, client, work_dir, electrum_url, esplora_url })
+        Ok(ElectrsD { process, client, work_dir, electrum_url, esplora_url, version: conf.version })
     }
 
     /// triggers electrs sync by sending the `SIGUSR1` signal, useful to call after a block for example
@@ -289,6 +297,9 @@ impl ElectrsD {
     /// Return the current workdir path of the running electrs
     pub fn workdir(&self) -> PathBuf { self.work_dir.path() }
 
+    /// Return the version of the running electrs
+    pub fn version(&self) -> ElectrsVersion { self.version }
+
     /// terminate the electrs process
     pub fn kill(&mut self) -> anyhow::Result<()> {
         match self.work_dir {
@@ -321,15 +332,35 @@ impl Drop for ElectrsD {
     fn drop(&mut self) { let _ = self.kill(); }
 }
 
+/// Returns the electrum script hash of the given script: its sha256, hex encoded in reverse byte
+/// order.
+pub fn script_hash(script: &Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hash[..].to_lower_hex_string()
+}
+
 /// Provide the electrs executable path if a version feature has been specified and `ELECTRSD_SKIP_DOWNLOAD` is not set.
 pub fn downloaded_exe_path() -> Option<String> {
     if std::env::var_os("ELECTRSD_SKIP_DOWNLOAD").is_none() {
-        Some(format!("{}/electrs/{}/electrs", env!("OUT_DIR"), versions::electrs_name(),))
+        Some(format!("{}/{}/electrs", download_dir().display(), versions::electrs_name()))
     } else {
         None
     }
 }
 
+/// The directory where executables are downloaded: `ELECTRSD_DOWNLOAD_DIR` if set at runtime or
+/// else at build time, otherwise a directory in the build output.
+fn download_dir() -> PathBuf {
+    match std::env::var_os("ELECTRSD_DOWNLOAD_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => match option_env!("ELECTRSD_DOWNLOAD_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(env!("OUT_DIR")).join("electrs"),
+        },
+    }
+}
+
 /// Returns the daemon `electrs` executable with the following precedence:
 ///
 /// 1) If it's specified in the `ELECTRS_EXEC` or in `ELECTRS_EXE` env var (errors if both env vars are present)
@@ -397,7 +428,7 @@ mod test {
     use electrum_client::ElectrumApi;
     use log::{debug, log_enabled, Level};
 
-    use crate::{exe_path, ElectrsD, IS_ALL_FEATURES_BUILD};
+    use crate::{exe_path, ElectrsD, ElectrsVersion};
 
     #[test]
     #[ignore] // launch singularly since env are globals
@@ -445,15 +476,58 @@ mod test {
         assert!(electrsd.client.ping().is_err());
     }
 
+    #[test]
+    #[ignore = "needs electrs 0.10.6 and esplora in the same ELECTRSD_DOWNLOAD_DIR"]
+    fn test_electrs_and_esplora() {
+        // Both flavours must be in the download directory, eg. after building once with each
+        // version feature and the same `ELECTRSD_DOWNLOAD_DIR`
+        let electrs_exe = ElectrsVersion::V0_10_6
+            .downloaded_exe_path()
+            .expect("electrs 0.10.6 isn't in the download directory");
+        let esplora_exe = ElectrsVersion::EsploraA33e97e1
+            .downloaded_exe_path()
+            .expect("esplora isn't in the download directory");
+        let (bitcoind_exe, _) = init();
+        let mut conf = bitcoind::Conf::default();
+        conf.p2p = P2P::Yes;
+        let bitcoind = bitcoind::BitcoinD::with_conf(&bitcoind_exe, &conf).unwrap();
+
+        let mut electrs_conf =
+            crate::Conf { version: ElectrsVersion::V0_10_6, ..Default::default() };
+        let electrs = ElectrsD::with_conf(&electrs_exe, &bitcoind, &electrs_conf).unwrap();
+        electrs_conf.version = ElectrsVersion::EsploraA33e97e1;
+        let esplora = ElectrsD::with_conf(&esplora_exe, &bitcoind, &electrs_conf).unwrap();
+        assert_eq!(esplora.version(), ElectrsVersion::EsploraA33e97e1);
+
+        let address = bitcoind.client.new_address().unwrap();
+        bitcoind.client.generate_to_address(10, &address).unwrap();
+        let script = address.script_pubkey();
+
+        for electrsd in [&electrs, &esplora] {
+            electrsd.trigger().unwrap();
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                if electrsd.client.block_headers_subscribe().unwrap().height >= 11 {
+                    break;
+                }
+            }
+        }
+        let electrs_history = electrs.client.script_get_history(&script).unwrap();
+        let esplora_history = esplora.client.script_get_history(&script).unwrap();
+        assert_eq!(electrs_history.len(), 10);
+        assert_eq!(
+            electrs_history.iter().map(|h| (h.tx_hash, h.height)).collect::<Vec<_>>(),
+            esplora_history.iter().map(|h| (h.tx_hash, h.height)).collect::<Vec<_>>(),
+        );
+    }
+
     pub(crate) fn setup_nodes() -> (String, bitcoind::BitcoinD, ElectrsD) {
         let (bitcoind_exe, electrs_exe) = init();
         debug!("bitcoind: {}", &bitcoind_exe);
         debug!("electrs: {}", &electrs_exe);
         let mut conf = bitcoind::Conf::default();
         conf.view_stdout = log_enabled!(Level::Debug);
-        let should_version_use_p2p =
-            !cfg!(any(feature = "electrs_0_8_10", feature = "esplora_a33e97e1"));
-        if IS_ALL_FEATURES_BUILD || should_version_use_p2p {
+        if !ElectrsVersion::default().uses_jsonrpc_import() {
             conf.p2p = P2P::Yes;
         }
         let bitcoind = bitcoind::BitcoinD::with_conf(&bitcoind_exe, &conf).unwrap();
@@ -0,0 +1,879 @@
+//! Mock electrum server
+//!
+//! An in-process stand-in for electrs speaking the core of the Electrum protocol over TCP, useful
+//! to run lightweight tests where no electrs executable is available. Requests are answered by a
+//! [Backend], either a [BitcoindBackend] indexing a running bitcoind or canned [Fixtures].
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use std::{fmt, thread};
+
+use bitcoind::client::client_sync::Auth;
+use bitcoind::serde_json::{self, json, Value};
+use bitcoind::{anyhow, BitcoinD};
+use electrum_client::bitcoin::hashes::{sha256, sha256d, Hash};
+use electrum_client::bitcoin::hex::{DisplayHex, FromHex};
+use electrum_client::bitcoin::Script;
+use electrum_client::raw_client::{ElectrumPlaintextStream, RawClient};
+use log::{debug, warn};
+
+use crate::script_hash;
+
+/// Protocol version advertised by the mock server
+const PROTOCOL_VERSION: &str = "1.4";
+
+/// Maximum number of headers returned by `blockchain.block.headers`
+const MAX_HEADERS: usize = 2016;
+
+/// Error returned by a [Backend], sent back to the client as the JSON-RPC `error` object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    /// The error code
+    pub code: i32,
+    /// The error message
+    pub message: String,
+}
+
+impl RpcError {
+    /// The method is not supported by the backend
+    pub fn unknown_method(method: &str) -> Self {
+        RpcError { code: -32601, message: format!("unknown method {}", method) }
+    }
+
+    /// The params of the request are not valid for the method
+    pub fn invalid_params(message: impl fmt::Display) -> Self {
+        RpcError { code: -32602, message: format!("invalid params: {}", message) }
+    }
+
+    /// The backend failed to answer the request
+    pub fn internal(message: impl fmt::Display) -> Self {
+        RpcError { code: -32603, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Answers the electrum requests received by a [MockServer]
+///
+/// `server.version` and `server.ping` are handled by the server itself. Subscriptions are
+/// handled by calling the backend again on [MockServer::trigger] and notifying the client if the
+/// result changed.
+pub trait Backend: Send + Sync + 'static {
+    /// Returns the result of the electrum `method` called with `params`
+    fn call(&self, method: &str, params: &[Value]) -> Result<Value, RpcError>;
+}
+
+impl<B: Backend> Backend for Arc<B> {
+    fn call(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        (**self).call(method, params)
+    }
+}
+
+/// In-process electrum server, the stand-in for [crate::ElectrsD] when no electrs is available
+pub struct MockServer {
+    /// Electrum client connected to the mock server
+    pub client: RawClient<ElectrumPlaintextStream>,
+    /// Url to connect to the electrum protocol (tcp)
+    pub electrum_url: String,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    backend: Box<dyn Backend>,
+    sessions: Mutex<Vec<Weak<Session>>>,
+    stopped: AtomicBool,
+}
+
+/// A connected client
+struct Session {
+    writer: Mutex<TcpStream>,
+    /// Subscriptions of the client, keyed by method and serialized params
+    subscriptions: Mutex<HashMap<(String, String), Subscription>>,
+}
+
+struct Subscription {
+    params: Vec<Value>,
+    /// Last result sent to the client
+    last: Value,
+}
+
+impl MockServer {
+    /// Start a mock server answering requests with the given backend
+    pub fn new<B: Backend>(backend: B) -> anyhow::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let electrum_url = listener.local_addr()?.to_string();
+        let shared = Arc::new(Shared {
+            backend: Box::new(backend),
+            sessions: Mutex::new(vec![]),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accept_shared = shared.clone();
+        thread::spawn(move || accept_loop(listener, accept_shared));
+
+        let client = RawClient::new(&electrum_url, Some(Duration::from_secs(3)), None)?;
+        Ok(MockServer { client, electrum_url, shared })
+    }
+
+    /// Start a mock server indexing the given bitcoind
+    pub fn with_bitcoind(bitcoind: &BitcoinD) -> anyhow::Result<MockServer> {
+        MockServer::new(BitcoindBackend::new(bitcoind)?)
+    }
+
+    /// Notify the subscribed clients whose subscription result changed, the equivalent of
+    /// [crate::ElectrsD::trigger], useful to call after a block for example
+    ///
+    /// Clients which can't be notified are disconnected, the others are still notified.
+    pub fn trigger(&self) -> anyhow::Result<()> {
+        'sessions: for session in self.shared.sessions() {
+            let mut subscriptions = session.subscriptions.lock().expect("poisoned mutex");
+            for ((method, _), subscription) in subscriptions.iter_mut() {
+                let result = match self.shared.backend.call(method, &subscription.params) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        warn!("subscription {} failed: {}", method, e);
+                        continue;
+                    }
+                };
+                if result != subscription.last {
+                    let mut notification_params = subscription.params.clone();
+                    notification_params.push(result.clone());
+                    let notification =
+                        json!({"jsonrpc": "2.0", "method": method, "params": notification_params});
+                    if let Err(e) = session.send(&notification) {
+                        warn!("mock server failed to notify {}: {}", method, e);
+                        // ends the session loop, which drops the session
+                        let writer = session.writer.lock().expect("poisoned mutex");
+                        let _ = writer.shutdown(Shutdown::Both);
+                        continue 'sessions;
+                    }
+                    subscription.last = result;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // unblock the accept loop so that it sees the server has been stopped
+        let _ = TcpStream::connect(&self.electrum_url);
+        for session in self.shared.sessions() {
+            let _ = session.writer.lock().expect("poisoned mutex").shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("mock server failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => {
+                warn!("mock server failed to clone connection: {}", e);
+                continue;
+            }
+        };
+        let session =
+            Arc::new(Session { writer: Mutex::new(writer), subscriptions: Mutex::default() });
+        shared.sessions.lock().expect("poisoned mutex").push(Arc::downgrade(&session));
+
+        let session_shared = shared.clone();
+        thread::spawn(move || session_loop(stream, session, session_shared));
+    }
+}
+
+fn session_loop(stream: TcpStream, session: Arc<Session>, shared: Arc<Shared>) {
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                debug!("mock server connection closed: {}", e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(Value::Array(batch)) =>
+                Value::Array(batch.iter().map(|request| shared.handle(&session, request)).collect()),
+            Ok(request) => shared.handle(&session, &request),
+            Err(e) =>
+                error_response(Value::Null, RpcError { code: -32700, message: e.to_string() }),
+        };
+        if let Err(e) = session.send(&response) {
+            debug!("mock server failed to reply: {}", e);
+            break;
+        }
+    }
+}
+
+impl Shared {
+    /// Returns the sessions still connected, forgetting the closed ones
+    fn sessions(&self) -> Vec<Arc<Session>> {
+        let mut sessions = self.sessions.lock().expect("poisoned mutex");
+        sessions.retain(|session| session.strong_count() > 0);
+        sessions.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn handle(&self, session: &Session, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None =>
+                return error_response(id, RpcError { code: -32600, message: "no method".into() }),
+        };
+        let params = match request.get("params") {
+            None | Some(Value::Null) => vec![],
+            Some(Value::Array(params)) => params.clone(),
+            Some(_) => return error_response(id, RpcError::invalid_params("expected an array")),
+        };
+        debug!("mock server request: {} {:?}", method, params);
+
+        let result = match method {
+            "server.version" => Ok(json!(["electrsd-mock", PROTOCOL_VERSION])),
+            "server.ping" => Ok(Value::Null),
+            _ => self.backend.call(method, &params),
+        };
+        match result {
+            Ok(result) => {
+                let mut subscriptions = session.subscriptions.lock().expect("poisoned mutex");
+                if method.ends_with(".subscribe") {
+                    let key = (method.to_string(), Value::from(params.clone()).to_string());
+                    subscriptions.insert(key, Subscription { params, last: result.clone() });
+                } else if let Some(prefix) = method.strip_suffix(".unsubscribe") {
+                    let key = (format!("{}.subscribe", prefix), Value::from(params).to_string());
+                    subscriptions.remove(&key);
+                }
+                json!({"jsonrpc": "2.0", "id": id, "result": result})
+            }
+            Err(e) => error_response(id, e),
+        }
+    }
+}
+
+impl Session {
+    fn send(&self, message: &Value) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        let mut writer = self.writer.lock().expect("poisoned mutex");
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}})
+}
+
+/// A [Backend] answering with canned results, matched on method and params
+///
+/// Share it through an [Arc] to update the results while the server is running.
+#[derive(Debug, Default)]
+pub struct Fixtures {
+    results: Mutex<HashMap<(String, String), Value>>,
+}
+
+impl Fixtures {
+    /// Create an empty set of fixtures
+    pub fn new() -> Self { Fixtures::default() }
+
+    /// Load the fixtures from a JSON file containing an array of
+    /// `{"method": ..., "params": [...], "result": ...}` objects
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let entries: Vec<Value> = serde_json::from_str(&content)?;
+        let fixtures = Fixtures::new();
+        for entry in entries {
+            let method = entry
+                .get("method")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow::anyhow!("fixture without method: {}", entry))?;
+            let params = match entry.get("params") {
+                None | Some(Value::Null) => vec![],
+                Some(Value::Array(params)) => params.clone(),
+                Some(params) => anyhow::bail!("fixture params must be an array: {}", params),
+            };
+            let result = entry.get("result").cloned().unwrap_or(Value::Null);
+            fixtures.insert(method, &params, result);
+        }
+        Ok(fixtures)
+    }
+
+    /// Set the result returned for `method` called with `params`, replacing any previous one
+    pub fn insert(&self, method: &str, params: &[Value], result: Value) {
+        let key = (method.to_string(), Value::from(params).to_string());
+        self.results.lock().expect("poisoned mutex").insert(key, result);
+    }
+}
+
+impl Backend for Fixtures {
+    fn call(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        let key = (method.to_string(), Value::from(params).to_string());
+        match self.results.lock().expect("poisoned mutex").get(&key) {
+            Some(result) => Ok(result.clone()),
+            None => Err(RpcError {
+                code: -32601,
+                message: format!("no fixture for {} {}", method, key.1),
+            }),
+        }
+    }
+}
+
+/// A [Backend] answering from a running bitcoind, like electrs does
+///
+/// Script hash histories are indexed in memory from the blocks of the node, so this is only
+/// suitable for the small chains used in tests.
+pub struct BitcoindBackend {
+    client: bitcoind::Client,
+    index: Mutex<Index>,
+}
+
+impl BitcoindBackend {
+    /// Create a backend connected to the given bitcoind
+    pub fn new(bitcoind: &BitcoinD) -> anyhow::Result<Self> {
+        let auth = Auth::CookieFile(bitcoind.params.cookie_file.clone());
+        let client = bitcoind::Client::new_with_auth(&bitcoind.rpc_url(), auth)?;
+        Ok(BitcoindBackend { client, index: Mutex::default() })
+    }
+
+    fn rpc(&self, method: &str, args: &[Value]) -> Result<Value, RpcError> {
+        self.client.call(method, args).map_err(RpcError::internal)
+    }
+
+    fn rpc_str(&self, method: &str, args: &[Value]) -> Result<String, RpcError> {
+        match self.rpc(method, args)? {
+            Value::String(s) => Ok(s),
+            v => Err(RpcError::internal(format!("{} returned {}, expected a string", method, v))),
+        }
+    }
+
+    fn block_count(&self) -> Result<usize, RpcError> {
+        match self.rpc("getblockcount", &[])?.as_u64() {
+            Some(count) => Ok(count as usize),
+            None => Err(RpcError::internal("getblockcount returned a non number")),
+        }
+    }
+
+    /// Brings the index up to date with the node, returns the index and the current mempool
+    fn sync(&self) -> Result<(std::sync::MutexGuard<'_, Index>, Vec<Tx>), RpcError> {
+        let mut index = self.index.lock().expect("poisoned mutex");
+        let tip = self.block_count()?;
+
+        if let Some(indexed_tip) = index.blocks.len().checked_sub(1) {
+            let reorged = indexed_tip > tip
+                || self.rpc_str("getblockhash", &[indexed_tip.into()])?
+                    != index.blocks[indexed_tip];
+            if reorged {
+                debug!("mock server reindexing after reorg");
+                *index = Index::default();
+            }
+        }
+
+        for height in index.blocks.len()..=tip {
+            let hash = self.rpc_str("getblockhash", &[height.into()])?;
+            let block = self.rpc("getblock", &[hash.clone().into(), 2.into()])?;
+            let txs =
+                block["tx"].as_array().ok_or_else(|| RpcError::internal("block without tx"))?;
+            for tx in txs {
+                index.add(Tx::parse(tx)?, height);
+            }
+            index.blocks.push(hash);
+        }
+
+        let txids = match self.rpc("getrawmempool", &[])? {
+            Value::Array(txids) => txids,
+            v => return Err(RpcError::internal(format!("getrawmempool returned {}", v))),
+        };
+        let mut mempool = Vec::with_capacity(txids.len());
+        for txid in txids {
+            // the transaction may have been evicted or mined in the meantime
+            if let Ok(tx) = self.rpc("getrawtransaction", &[txid, true.into()]) {
+                mempool.push(Tx::parse(&tx)?);
+            }
+        }
+        Ok((index, mempool))
+    }
+
+    fn header(&self, height: usize) -> Result<String, RpcError> {
+        let hash = self.rpc_str("getblockhash", &[height.into()])?;
+        self.rpc_str("getblockheader", &[hash.into(), false.into()])
+    }
+}
+
+impl Backend for BitcoindBackend {
+    fn call(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        match method {
+            "server.banner" => Ok("electrsd mock server".into()),
+            "server.donation_address" => Ok(Value::Null),
+            "server.peers.subscribe" => Ok(json!([])),
+            "server.features" => {
+                let genesis_hash = self.rpc_str("getblockhash", &[0.into()])?;
+                Ok(json!({
+                    "genesis_hash": genesis_hash,
+                    "hosts": {},
+                    "protocol_min": PROTOCOL_VERSION,
+                    "protocol_max": PROTOCOL_VERSION,
+                    "server_version": "electrsd-mock",
+                    "hash_function": "sha256",
+                    "pruning": null,
+                }))
+            }
+            "blockchain.headers.subscribe" => {
+                let height = self.block_count()?;
+                Ok(json!({"height": height, "hex": self.header(height)?}))
+            }
+            "blockchain.block.header" => Ok(self.header(param_usize(params, 0)?)?.into()),
+            "blockchain.block.headers" => {
+                let start = param_usize(params, 0)?;
+                let tip = self.block_count()?;
+                let count =
+                    param_usize(params, 1)?.min(MAX_HEADERS).min((tip + 1).saturating_sub(start));
+                let hex = (start..start + count)
+                    .map(|height| self.header(height))
+                    .collect::<Result<String, _>>()?;
+                Ok(json!({"count": count, "hex": hex, "max": MAX_HEADERS}))
+            }
+            "blockchain.estimatefee" => {
+                let estimate = self.rpc("estimatesmartfee", &[param_usize(params, 0)?.into()])?;
+                Ok(estimate.get("feerate").cloned().unwrap_or_else(|| (-1).into()))
+            }
+            "blockchain.relayfee" => {
+                let info = self.rpc("getnetworkinfo", &[])?;
+                Ok(info["relayfee"].clone())
+            }
+            "blockchain.transaction.broadcast" =>
+                self.rpc("sendrawtransaction", &[param_str(params, 0)?.into()]),
+            "blockchain.transaction.get" => {
+                let txid = param_str(params, 0)?;
+                let verbose = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+                let (index, _) = self.sync()?;
+                let mut args = vec![txid.into(), verbose.into()];
+                // passing the block hash lets bitcoind find confirmed transactions without txindex
+                if let Some(height) = index.tx_heights.get(txid) {
+                    args.push(index.blocks[*height].clone().into());
+                }
+                drop(index);
+                self.rpc("getrawtransaction", &args)
+            }
+            "blockchain.transaction.get_merkle" => {
+                let txid = param_str(params, 0)?;
+                let height = param_usize(params, 1)?;
+                let hash = self.rpc_str("getblockhash", &[height.into()])?;
+                let block = self.rpc("getblock", &[hash.into(), 1.into()])?;
+                let txids = block["tx"]
+                    .as_array()
+                    .ok_or_else(|| RpcError::internal("block without tx"))?
+                    .iter()
+                    .map(|txid| txid.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| RpcError::internal("invalid txid in block"))?;
+                let pos = txids
+                    .iter()
+                    .position(|id| id == txid)
+                    .ok_or_else(|| RpcError::invalid_params("transaction not in block"))?;
+                Ok(
+                    json!({"block_height": height, "merkle": merkle_branch(&txids, pos)?, "pos": pos}),
+                )
+            }
+            "blockchain.scripthash.get_history" => {
+                let (index, mempool) = self.sync()?;
+                let history = index.history(param_str(params, 0)?, &mempool);
+                Ok(history
+                    .into_iter()
+                    .map(|(txid, height)| json!({"tx_hash": txid, "height": height}))
+                    .collect())
+            }
+            "blockchain.scripthash.get_mempool" => {
+                let (index, mempool) = self.sync()?;
+                let history = index.history(param_str(params, 0)?, &mempool);
+                Ok(history
+                    .into_iter()
+                    .filter(|(_, height)| *height <= 0)
+                    .map(|(txid, height)| json!({"tx_hash": txid, "height": height, "fee": 0}))
+                    .collect())
+            }
+            "blockchain.scripthash.get_balance" => {
+                let (index, mempool) = self.sync()?;
+                let (confirmed, unconfirmed) = index.balance(param_str(params, 0)?, &mempool);
+                Ok(json!({"confirmed": confirmed, "unconfirmed": unconfirmed}))
+            }
+            "blockchain.scripthash.listunspent" => {
+                let (index, mempool) = self.sync()?;
+                Ok(index
+                    .unspent(param_str(params, 0)?, &mempool)
+                    .into_iter()
+                    .map(|((txid, vout), value, height)| {
+                        json!({"tx_hash": txid, "tx_pos": vout, "value": value, "height": height})
+                    })
+                    .collect())
+            }
+            "blockchain.scripthash.subscribe" => {
+                let (index, mempool) = self.sync()?;
+                Ok(status(&index.history(param_str(params, 0)?, &mempool)))
+            }
+            "blockchain.scripthash.unsubscribe" => Ok(true.into()),
+            _ => Err(RpcError::unknown_method(method)),
+        }
+    }
+}
+
+type OutPoint = (String, u32);
+
+/// A transaction as decoded by bitcoind, reduced to what the index needs
+struct Tx {
+    txid: String,
+    /// Outpoints spent by the transaction, empty for coinbases
+    inputs: Vec<OutPoint>,
+    /// Index, script hash and value in satoshi of each output
+    outputs: Vec<(u32, String, u64)>,
+}
+
+impl Tx {
+    /// Parse the verbose JSON returned by `getrawtransaction` or `getblock`
+    fn parse(tx: &Value) -> Result<Tx, RpcError> {
+        let invalid = || RpcError::internal(format!("invalid transaction {}", tx));
+        let txid = tx["txid"].as_str().ok_or_else(invalid)?.to_string();
+
+        let mut inputs = vec![];
+        for input in tx["vin"].as_array().ok_or_else(invalid)? {
+            if input.get("coinbase").is_some() {
+                continue;
+            }
+            let prev_txid = input["txid"].as_str().ok_or_else(invalid)?;
+            let vout = input["vout"].as_u64().ok_or_else(invalid)?;
+            inputs.push((prev_txid.to_string(), vout as u32));
+        }
+
+        let mut outputs = vec![];
+        for output in tx["vout"].as_array().ok_or_else(invalid)? {
+            let n = output["n"].as_u64().ok_or_else(invalid)?;
+            let value = output["value"].as_f64().ok_or_else(invalid)?;
+            let script_hex = output["scriptPubKey"]["hex"].as_str().ok_or_else(invalid)?;
+            let script = Vec::<u8>::from_hex(script_hex).map_err(|_| invalid())?;
+            let value = (value * 100_000_000.0).round() as u64;
+            outputs.push((n as u32, script_hash(Script::from_bytes(&script)), value));
+        }
+
+        Ok(Tx { txid, inputs, outputs })
+    }
+}
+
+/// In memory index of the confirmed transactions of a bitcoind
+#[derive(Default)]
+struct Index {
+    /// Block hashes by height
+    blocks: Vec<String>,
+    /// Height of each confirmed transaction
+    tx_heights: HashMap<String, usize>,
+    /// Script hash, value and height of each confirmed output
+    outputs: HashMap<OutPoint, (String, u64, usize)>,
+    /// Confirmed outputs spent by a confirmed transaction
+    spent: HashSet<OutPoint>,
+    /// Confirmed transactions funding or spending each script hash, in block order
+    history: HashMap<String, Vec<(String, usize)>>,
+}
+
+impl Index {
+    fn add(&mut self, tx: Tx, height: usize) {
+        for outpoint in tx.inputs {
+            if let Some((script_hash, _, _)) = self.outputs.get(&outpoint) {
+                push_history(
+                    self.history.entry(script_hash.clone()).or_default(),
+                    &tx.txid,
+                    height,
+                );
+            }
+            self.spent.insert(outpoint);
+        }
+        for (vout, script_hash, value) in tx.outputs {
+            push_history(self.history.entry(script_hash.clone()).or_default(), &tx.txid, height);
+            self.outputs.insert((tx.txid.clone(), vout), (script_hash, value, height));
+        }
+        self.tx_heights.insert(tx.txid, height);
+    }
+
+    /// Script hash and value of the output, confirmed or in the mempool
+    fn output<'a>(&'a self, outpoint: &OutPoint, mempool: &'a [Tx]) -> Option<(&'a str, u64)> {
+        if let Some((script_hash, value, _)) = self.outputs.get(outpoint) {
+            return Some((script_hash, *value));
+        }
+        let tx = mempool.iter().find(|tx| tx.txid == outpoint.0)?;
+        let (_, script_hash, value) = tx.outputs.iter().find(|(vout, _, _)| *vout == outpoint.1)?;
+        Some((script_hash, *value))
+    }
+
+    /// Confirmed history followed by the mempool transactions, height is 0 for the latter, or -1
+    /// if they spend an output of another mempool transaction
+    fn history(&self, script_hash: &str, mempool: &[Tx]) -> Vec<(String, i64)> {
+        let mut history: Vec<_> = self
+            .history
+            .get(script_hash)
+            .into_iter()
+            .flatten()
+            .map(|(txid, height)| (txid.clone(), *height as i64))
+            .collect();
+        for tx in mempool {
+            let funds = tx.outputs.iter().any(|(_, hash, _)| hash == script_hash);
+            let spends = tx.inputs.iter().any(|outpoint| {
+                self.output(outpoint, mempool).is_some_and(|(hash, _)| hash == script_hash)
+            });
+            if funds || spends {
+                let unconfirmed_parent = tx
+                    .inputs
+                    .iter()
+                    .any(|(txid, _)| mempool.iter().any(|parent| &parent.txid == txid));
+                history.push((tx.txid.clone(), if unconfirmed_parent { -1 } else { 0 }));
+            }
+        }
+        history
+    }
+
+    /// Confirmed balance and the unconfirmed change to it, in satoshi
+    fn balance(&self, script_hash: &str, mempool: &[Tx]) -> (u64, i64) {
+        let confirmed = self
+            .outputs
+            .iter()
+            .filter(|(outpoint, (hash, _, _))| {
+                hash == script_hash && !self.spent.contains(outpoint)
+            })
+            .map(|(_, (_, value, _))| value)
+            .sum();
+        let mut unconfirmed = 0i64;
+        for tx in mempool {
+            for (_, hash, value) in &tx.outputs {
+                if hash == script_hash {
+                    unconfirmed += *value as i64;
+                }
+            }
+            for outpoint in &tx.inputs {
+                if let Some((hash, value)) = self.output(outpoint, mempool) {
+                    if hash == script_hash {
+                        unconfirmed -= value as i64;
+                    }
+                }
+            }
+        }
+        (confirmed, unconfirmed)
+    }
+
+    /// Unspent outputs with their value and height, 0 for the mempool ones
+    fn unspent(&self, script_hash: &str, mempool: &[Tx]) -> Vec<(OutPoint, u64, usize)> {
+        let mempool_spent: HashSet<_> = mempool.iter().flat_map(|tx| tx.inputs.iter()).collect();
+        let mut unspent: Vec<_> = self
+            .outputs
+            .iter()
+            .filter(|(outpoint, (hash, _, _))| {
+                hash == script_hash
+                    && !self.spent.contains(*outpoint)
+                    && !mempool_spent.contains(outpoint)
+            })
+            .map(|(outpoint, (_, value, height))| (outpoint.clone(), *value, *height))
+            .collect();
+        unspent.sort_by(|a, b| (a.2, &a.0).cmp(&(b.2, &b.0)));
+        for tx in mempool {
+            for (vout, hash, value) in &tx.outputs {
+                let outpoint = (tx.txid.clone(), *vout);
+                if hash == script_hash && !mempool_spent.contains(&outpoint) {
+                    unspent.push((outpoint, *value, 0));
+                }
+            }
+        }
+        unspent
+    }
+}
+
+fn push_history(history: &mut Vec<(String, usize)>, txid: &str, height: usize) {
+    if history.last().map(|(last, _)| last.as_str()) != Some(txid) {
+        history.push((txid.to_string(), height));
+    }
+}
+
+/// Electrum status of a script hash: sha256 of the concatenated `txid:height:` of its history
+fn status(history: &[(String, i64)]) -> Value {
+    if history.is_empty() {
+        return Value::Null;
+    }
+    let concatenated: String =
+        history.iter().map(|(txid, height)| format!("{}:{}:", txid, height)).collect();
+    sha256::Hash::hash(concatenated.as_bytes()).to_byte_array()[..].to_lower_hex_string().into()
+}
+
+/// Merkle branch of the transaction at `pos`, with hashes hex encoded like txids
+fn merkle_branch(txids: &[String], mut pos: usize) -> Result<Vec<String>, RpcError> {
+    let mut level = txids
+        .iter()
+        .map(|txid| {
+            let mut hash = <[u8; 32]>::from_hex(txid).map_err(RpcError::internal)?;
+            hash.reverse();
+            Ok(hash)
+        })
+        .collect::<Result<Vec<_>, RpcError>>()?;
+
+    let mut branch = vec![];
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        let mut sibling = level[pos ^ 1];
+        sibling.reverse();
+        branch.push(sibling[..].to_lower_hex_string());
+        level = level
+            .chunks(2)
+            .map(|pair| sha256d::Hash::hash(&[pair[0], pair[1]].concat()).to_byte_array())
+            .collect();
+        pos /= 2;
+    }
+    Ok(branch)
+}
+
+fn param_str(params: &[Value], i: usize) -> Result<&str, RpcError> {
+    params
+        .get(i)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("expected a string at {}", i)))
+}
+
+fn param_usize(params: &[Value], i: usize) -> Result<usize, RpcError> {
+    params
+        .get(i)
+        .and_then(Value::as_u64)
+        .map(|n| n as usize)
+        .ok_or_else(|| RpcError::invalid_params(format!("expected a number at {}", i)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Shutdown;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bitcoind::serde_json::json;
+    use electrum_client::bitcoin::Amount;
+    use electrum_client::raw_client::RawClient;
+    use electrum_client::ElectrumApi;
+
+    use super::{Fixtures, Index, MockServer, Tx};
+
+    // regtest genesis block header
+    const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000";
+
+    #[test]
+    fn test_fixtures() {
+        let fixtures = Arc::new(Fixtures::new());
+        fixtures.insert("blockchain.headers.subscribe", &[], json!({"height": 0, "hex": GENESIS}));
+        let server = MockServer::new(fixtures.clone()).unwrap();
+
+        server.client.ping().unwrap();
+        let header = server.client.block_headers_subscribe().unwrap();
+        assert_eq!(header.height, 0);
+        assert!(server.client.relay_fee().is_err());
+
+        fixtures.insert("blockchain.headers.subscribe", &[], json!({"height": 1, "hex": GENESIS}));
+        server.trigger().unwrap();
+        server.client.ping().unwrap();
+        let header = server.client.block_headers_pop().unwrap().unwrap();
+        assert_eq!(header.height, 1);
+    }
+
+    #[test]
+    fn test_trigger_dead_session() {
+        let fixtures = Arc::new(Fixtures::new());
+        fixtures.insert("blockchain.headers.subscribe", &[], json!({"height": 0, "hex": GENESIS}));
+        let server = MockServer::new(fixtures.clone()).unwrap();
+        server.client.block_headers_subscribe().unwrap();
+        let client =
+            RawClient::new(&server.electrum_url, Some(Duration::from_secs(3)), None).unwrap();
+        client.block_headers_subscribe().unwrap();
+
+        // the session of `server.client` was accepted first
+        let sessions = server.shared.sessions();
+        assert_eq!(sessions.len(), 2);
+        sessions[0].writer.lock().unwrap().shutdown(Shutdown::Both).unwrap();
+        drop(sessions);
+
+        fixtures.insert("blockchain.headers.subscribe", &[], json!({"height": 1, "hex": GENESIS}));
+        server.trigger().unwrap();
+        client.ping().unwrap();
+        let header = client.block_headers_pop().unwrap().unwrap();
+        assert_eq!(header.height, 1);
+    }
+
+    #[test]
+    fn test_mempool_history_heights() {
+        let mut index = Index::default();
+        index.blocks.push(String::new());
+        let funding = Tx { txid: "a".into(), inputs: vec![], outputs: vec![(0, "s".into(), 10)] };
+        index.add(funding, 1);
+        let child = Tx {
+            txid: "b".into(),
+            inputs: vec![("a".into(), 0)],
+            outputs: vec![(0, "s".into(), 9)],
+        };
+        let grandchild = Tx {
+            txid: "c".into(),
+            inputs: vec![("b".into(), 0)],
+            outputs: vec![(0, "t".into(), 8)],
+        };
+        let history = index.history("s", &[child, grandchild]);
+        assert_eq!(history, [("a".into(), 1), ("b".into(), 0), ("c".into(), -1)]);
+    }
+
+    #[test]
+    fn test_bitcoind_backend() {
+        let _ = env_logger::try_init();
+        let bitcoind = bitcoind::BitcoinD::new(bitcoind::exe_path().unwrap()).unwrap();
+        let server = MockServer::with_bitcoind(&bitcoind).unwrap();
+        let header = server.client.block_headers_subscribe().unwrap();
+        assert_eq!(header.height, 0);
+
+        let address = bitcoind.client.new_address().unwrap();
+        bitcoind.client.generate_to_address(101, &address).unwrap();
+        let history = server.client.script_get_history(&address.script_pubkey()).unwrap();
+        assert_eq!(history.len(), 101);
+        let balance = server.client.script_get_balance(&address.script_pubkey()).unwrap();
+        assert_eq!(balance.confirmed, 101 * 50 * 100_000_000);
+
+        let address = bitcoind.client.new_address().unwrap();
+        let txid = bitcoind
+            .client
+            .send_to_address(&address, Amount::from_sat(10000))
+            .unwrap()
+            .txid()
+            .unwrap();
+        let history = server.client.script_get_history(&address.script_pubkey()).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].tx_hash, txid);
+        let tx = server.client.transaction_get(&txid).unwrap();
+        assert_eq!(tx.compute_txid(), txid);
+
+        server.trigger().unwrap();
+        server.client.ping().unwrap();
+        let header = server.client.block_headers_pop().unwrap().unwrap();
+        assert_eq!(header.height, 101);
+    }
+}
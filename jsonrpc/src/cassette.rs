@@ -0,0 +1,384 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Record and replay
+//!
+//! [`RecordingTransport`] wraps a transport and records every exchange, single requests and
+//! batches, to a JSON Lines "cassette" file. [`ReplayTransport`] serves the recorded responses
+//! back, matching requests on method and params, so that code using a [`crate::Client`] can be
+//! tested deterministically without the server.
+//!
+//! Each line of a cassette holds one exchange: `{"request":...,"response":...}` where both are
+//! arrays for a batch.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::client::Transport;
+use crate::{Request, Response};
+
+#[derive(Serialize)]
+struct RecordRef<'a, Q: ?Sized, S: ?Sized> {
+    request: &'a Q,
+    response: &'a S,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    request: Value,
+    response: Box<RawValue>,
+}
+
+/// The parts of a recorded request used for matching.
+#[derive(Deserialize)]
+struct RecordedRequest {
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+    /// Absent for notifications.
+    #[serde(default)]
+    id: Value,
+}
+
+/// A transport recording the exchanges with the wrapped transport to a cassette file.
+///
+/// Failed exchanges, ie. transport errors, are not recorded.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    file: Mutex<File>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Wraps `inner`, recording to the file at `path` which is created or truncated.
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> Result<Self, Error> {
+        Ok(RecordingTransport { inner, file: Mutex::new(File::create(path)?) })
+    }
+
+    /// Returns the wrapped transport.
+    pub fn into_inner(self) -> T { self.inner }
+
+    fn record<Q: Serialize + ?Sized, S: Serialize + ?Sized>(
+        &self,
+        request: &Q,
+        response: &S,
+    ) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(&RecordRef { request, response })?;
+        line.push(b'\n');
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        let mut file = self.file.lock().expect("poisoned mutex");
+        file.write_all(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        let resp = self.inner.send_request(req.clone())?;
+        self.record(&req, &resp)?;
+        Ok(resp)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        let resps = self.inner.send_batch(reqs)?;
+        self.record(reqs, &resps)?;
+        Ok(resps)
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        // Notifications have no response to record.
+        self.inner.send_notification(req)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.inner.fmt_target(f) }
+}
+
+/// The recorded responses to the exchanges sharing a key, served in order.
+#[derive(Debug)]
+struct Recorded<R> {
+    responses: Vec<R>,
+    next: usize,
+}
+
+impl<R: Clone> Recorded<R> {
+    /// Returns the next response, repeating the last one once all have been served.
+    fn next(&mut self) -> R {
+        let resp = self.responses[self.next.min(self.responses.len() - 1)].clone();
+        self.next += 1;
+        resp
+    }
+}
+
+/// A recorded batch: the ids of the requests, in order, and the responses.
+type Batch = (Vec<Value>, Vec<Response>);
+
+/// A transport serving the responses recorded in a cassette.
+///
+/// Requests are matched on method and params, ignoring ids which are rewritten to the ones of the
+/// replayed requests. Batches match a recorded batch with the same requests in the same order.
+/// When the same requests have been recorded several times, the responses are served in the order
+/// they were recorded, then the last one is repeated.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    source: PathBuf,
+    singles: Mutex<HashMap<String, Recorded<Response>>>,
+    batches: Mutex<HashMap<String, Recorded<Batch>>>,
+}
+
+impl ReplayTransport {
+    /// Loads the cassette at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path.as_ref())?;
+        let mut tp = ReplayTransport::from_reader(BufReader::new(file))?;
+        tp.source = path.as_ref().to_path_buf();
+        Ok(tp)
+    }
+
+    /// Loads a cassette from `reader`.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut singles = HashMap::<_, Recorded<_>>::new();
+        let mut batches = HashMap::<_, Recorded<_>>::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)?;
+            if record.request.is_array() {
+                let reqs: Vec<RecordedRequest> = serde_json::from_value(record.request)?;
+                let key = batch_key(reqs.iter().map(|r| (r.method.as_str(), r.params.clone())));
+                let ids = reqs.into_iter().map(|r| r.id).collect();
+                let resps: Vec<Response> = serde_json::from_str(record.response.get())?;
+                batches
+                    .entry(key)
+                    .or_insert_with(|| Recorded { responses: vec![], next: 0 })
+                    .responses
+                    .push((ids, resps));
+            } else {
+                let req: RecordedRequest = serde_json::from_value(record.request)?;
+                let key = key(&req.method, req.params);
+                let resp: Response = serde_json::from_str(record.response.get())?;
+                singles
+                    .entry(key)
+                    .or_insert_with(|| Recorded { responses: vec![], next: 0 })
+                    .responses
+                    .push(resp);
+            }
+        }
+        Ok(ReplayTransport {
+            source: PathBuf::new(),
+            singles: Mutex::new(singles),
+            batches: Mutex::new(batches),
+        })
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        let key = key(req.method, params_value(req.params)?);
+        let mut singles = self.singles.lock().expect("poisoned mutex");
+        let mut resp = singles.get_mut(&key).ok_or(Error::NoMatch(key))?.next();
+        resp.id = req.id;
+        Ok(resp)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        let params = reqs.iter().map(|r| params_value(r.params)).collect::<Result<Vec<_>, _>>()?;
+        let key = batch_key(reqs.iter().map(|r| r.method).zip(params));
+        let mut batches = self.batches.lock().expect("poisoned mutex");
+        let (ids, mut resps) = batches.get_mut(&key).ok_or(Error::NoMatch(key))?.next();
+        for resp in resps.iter_mut() {
+            // Notifications have no id to rewrite, and a null one can't be told apart.
+            if resp.id.is_null() {
+                continue;
+            }
+            if let Some(pos) = ids.iter().position(|id| *id == resp.id) {
+                resp.id = reqs[pos].id.clone();
+            }
+        }
+        Ok(resps)
+    }
+
+    fn send_notification(&self, _: Request) -> Result<(), crate::Error> { Ok(()) }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "replay:{}", self.source.display())
+    }
+}
+
+fn params_value(params: Option<&RawValue>) -> Result<Option<Value>, serde_json::Error> {
+    params.map(|p| serde_json::from_str(p.get())).transpose()
+}
+
+/// The matching key of a request, params are normalized by going through [`Value`].
+fn key(method: &str, params: Option<Value>) -> String {
+    serde_json::to_string(&(method, params)).expect("values serialize")
+}
+
+fn batch_key<'a, I: Iterator<Item = (&'a str, Option<Value>)>>(reqs: I) -> String {
+    serde_json::to_string(&reqs.collect::<Vec<_>>()).expect("values serialize")
+}
+
+/// Error that can occur while recording or replaying.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the cassette failed.
+    Io(io::Error),
+    /// JSON error.
+    Json(serde_json::Error),
+    /// No exchange recorded for the request, identified by its method and params.
+    NoMatch(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        use Error::*;
+
+        match *self {
+            Io(ref e) => write!(f, "cassette I/O error: {}", e),
+            Json(ref e) => write!(f, "JSON error: {}", e),
+            NoMatch(ref key) => write!(f, "no recorded exchange for request {}", key),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use self::Error::*;
+
+        match *self {
+            Io(ref e) => Some(e),
+            Json(ref e) => Some(e),
+            NoMatch(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self { Error::Io(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Json(e) }
+}
+
+impl From<Error> for crate::Error {
+    fn from(e: Error) -> crate::Error {
+        match e {
+            Error::Json(e) => crate::Error::Json(e),
+            e => crate::Error::Transport(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::{arg, Client};
+
+    /// Answers `count` with an increasing counter and `echo` with its params.
+    #[derive(Default)]
+    struct CountingTransport(AtomicU64);
+
+    impl CountingTransport {
+        fn respond(&self, req: &Request) -> Response {
+            let result = match req.method {
+                "count" => arg(self.0.fetch_add(1, Ordering::SeqCst)),
+                _ => req.params.map(|p| p.to_owned()).unwrap_or_else(|| arg(())),
+            };
+            Response { result: Some(result), error: None, id: req.id.clone(), jsonrpc: None }
+        }
+    }
+
+    impl Transport for CountingTransport {
+        fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+            Ok(self.respond(&req))
+        }
+
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+            // Answer in reverse order to exercise id matching, and not to notifications.
+            Ok(reqs
+                .iter()
+                .rev()
+                .filter(|r| !r.is_notification())
+                .map(|r| self.respond(r))
+                .collect())
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("counting") }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("jsonrpc-cassette-{}.jsonl", std::process::id()));
+
+        let client = Client::with_transport(
+            RecordingTransport::create(CountingTransport::default(), &path).unwrap(),
+        );
+        assert_eq!(client.call::<u64>("count", None).unwrap(), 0);
+        assert_eq!(client.call::<u64>("count", None).unwrap(), 1);
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&*arg([1]))).unwrap(), [1]);
+        let params = arg([2]);
+        let reqs =
+            [client.build_request("echo", Some(&*params)), client.build_request("count", None)];
+        let resps = client.send_batch(&reqs).unwrap();
+        assert_eq!(resps[1].as_ref().unwrap().result::<u64>().unwrap(), 2);
+        drop(client);
+
+        // A new client starts its ids from scratch.
+        let client = Client::with_transport(ReplayTransport::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let params = arg([2]);
+        let reqs =
+            [client.build_request("echo", Some(&*params)), client.build_request("count", None)];
+        let resps = client.send_batch(&reqs).unwrap();
+        assert_eq!(resps[0].as_ref().unwrap().result::<Vec<u8>>().unwrap(), [2]);
+        assert_eq!(resps[1].as_ref().unwrap().result::<u64>().unwrap(), 2);
+
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&*arg([1]))).unwrap(), [1]);
+        assert_eq!(client.call::<u64>("count", None).unwrap(), 0);
+        assert_eq!(client.call::<u64>("count", None).unwrap(), 1);
+        assert_eq!(client.call::<u64>("count", None).unwrap(), 1);
+
+        match client.call::<Vec<u8>>("echo", Some(&*arg([3]))) {
+            Err(crate::Error::Transport(e)) =>
+                assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NoMatch(_)))),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn record_and_replay_mixed_batch() {
+        let path = std::env::temp_dir()
+            .join(format!("jsonrpc-cassette-mixed-{}.jsonl", std::process::id()));
+
+        let client = Client::with_transport(
+            RecordingTransport::create(CountingTransport::default(), &path).unwrap(),
+        );
+        let reqs = [client.build_request("count", None), client.build_notification("echo", None)];
+        let resps = client.send_batch(&reqs).unwrap();
+        assert_eq!(resps[0].as_ref().unwrap().result::<u64>().unwrap(), 0);
+        assert!(resps[1].is_none());
+        drop(client);
+
+        let client = Client::with_transport(ReplayTransport::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        // Shift the ids from the recorded ones, this call wasn't recorded.
+        client.call::<()>("echo", None).unwrap_err();
+        let reqs = [client.build_request("count", None), client.build_notification("echo", None)];
+        let resps = client.send_batch(&reqs).unwrap();
+        assert_eq!(resps[0].as_ref().unwrap().result::<u64>().unwrap(), 0);
+        assert!(resps[1].is_none());
+    }
+}
@@ -7,15 +7,18 @@
 
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::sync::atomic;
+use std::time::Instant;
+use std::{fmt, io};
 
+use serde::Deserialize;
 use serde_json::value::RawValue;
 use serde_json::Value;
 
-use crate::error::Error;
-use crate::{Request, Response};
+use crate::error::{Error, RpcError};
+use crate::interceptor::{Chain, Interceptor};
+use crate::{Dialect, Request, Response};
 
 /// An interface for a transport over which to use the JSONRPC protocol.
 pub trait Transport: Send + Sync + 'static {
@@ -25,6 +28,31 @@ pub trait Transport: Send + Sync + 'static {
     fn send_batch(&self, _: &[Request]) -> Result<Vec<Response>, Error>;
     /// Formats the target of this transport. I.e. the URL/socket/...
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result;
+
+    /// Sends an RPC request over the transport and passes the response body, of at most
+    /// `max_size` bytes, to `read` as it is received.
+    ///
+    /// The default implementation buffers the response from [`Transport::send_request`].
+    fn send_request_streaming(
+        &self,
+        req: Request,
+        max_size: u64,
+        read: &mut dyn FnMut(&mut dyn io::Read) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let body = serde_json::to_vec(&self.send_request(req)?)?;
+        if body.len() as u64 > max_size {
+            return Err(Error::ResponseTooLarge(max_size));
+        }
+        read(&mut &body[..])
+    }
+
+    /// Sends a notification, a request without id, over the transport.
+    ///
+    /// The default implementation sends it with [`Transport::send_request`] and ignores the
+    /// response, transports to servers not answering notifications must override it.
+    fn send_notification(&self, req: Request) -> Result<(), Error> {
+        self.send_request(req).map(|_| ())
+    }
 }
 
 /// A JSON-RPC client.
@@ -34,26 +62,69 @@ pub trait Transport: Send + Sync + 'static {
 pub struct Client {
     pub(crate) transport: Box<dyn Transport>,
     nonce: atomic::AtomicUsize,
+    interceptors: Chain,
+    dialect: Dialect,
 }
 
 impl Client {
     /// Creates a new client with the given transport.
     pub fn with_transport<T: Transport>(transport: T) -> Client {
-        Client { transport: Box::new(transport), nonce: atomic::AtomicUsize::new(1) }
+        Client {
+            transport: Box::new(transport),
+            nonce: atomic::AtomicUsize::new(1),
+            interceptors: Chain::default(),
+            dialect: Dialect::default(),
+        }
     }
 
+    /// Adds an interceptor seeing the requests of this client and their responses, after the
+    /// interceptors already added.
+    pub fn with_interceptor<I: Interceptor>(mut self, interceptor: I) -> Client {
+        self.interceptors.push(Box::new(interceptor));
+        self
+    }
+
+    /// Sets the JSON-RPC dialect of the requests and responses, [`Dialect::V2`] by default.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Client {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Returns the JSON-RPC dialect of this client.
+    pub fn dialect(&self) -> Dialect { self.dialect }
+
     /// Builds a request.
     ///
     /// To construct the arguments, one can use one of the shorthand methods.
     /// [`crate::arg`] or [`crate::try_arg`].
     pub fn build_request<'a>(&self, method: &'a str, params: Option<&'a RawValue>) -> Request<'a> {
         let nonce = self.nonce.fetch_add(1, atomic::Ordering::Relaxed);
-        Request { method, params, id: serde_json::Value::from(nonce), jsonrpc: Some("2.0") }
+        Request {
+            method,
+            params,
+            id: serde_json::Value::from(nonce),
+            jsonrpc: self.dialect.request_version(),
+        }
+    }
+
+    /// Builds a notification, a request without id which gets no response.
+    ///
+    /// Notifications can be sent with [`Client::notify`] or as part of a batch.
+    pub fn build_notification<'a>(
+        &self,
+        method: &'a str,
+        params: Option<&'a RawValue>,
+    ) -> Request<'a> {
+        Request { method, params, id: Value::Null, jsonrpc: self.dialect.request_version() }
     }
 
     /// Sends a request to a client.
     pub fn send_request(&self, request: Request) -> Result<Response, Error> {
-        self.transport.send_request(request)
+        self.interceptors.on_request(&request);
+        let start = Instant::now();
+        let response = self.transport.send_request(request.clone());
+        self.interceptors.on_response(&request, response.as_ref(), start.elapsed());
+        response
     }
 
     /// Sends a batch of requests to the client.
@@ -64,8 +135,18 @@ impl Client {
     /// # Returns
     ///
     /// The return vector holds the response for the request at the corresponding index. If no
-    /// response was provided, it's [`None`].
+    /// response was provided, it's [`None`]. It is always [`None`] for notifications.
     pub fn send_batch(&self, requests: &[Request]) -> Result<Vec<Option<Response>>, Error> {
+        for request in requests {
+            self.interceptors.on_request(request);
+        }
+        let start = Instant::now();
+        let responses = self.try_send_batch(requests);
+        self.interceptors.on_batch_response(requests, &responses, start.elapsed());
+        responses
+    }
+
+    fn try_send_batch(&self, requests: &[Request]) -> Result<Vec<Option<Response>>, Error> {
         if requests.is_empty() {
             return Err(Error::EmptyBatch);
         }
@@ -79,17 +160,31 @@ impl Client {
 
         //TODO(stevenroose) check if the server preserved order to avoid doing the mapping
 
-        // First index responses by ID and catch duplicate IDs.
+        // First index responses by ID and catch duplicate IDs. Servers answering notifications,
+        // as under JSON-RPC 1.0, answer them with a null ID.
+        let notifications = requests.iter().any(Request::is_notification);
         let mut by_id = HashMap::with_capacity(requests.len());
         for resp in responses.into_iter() {
+            self.dialect.check_response(&resp)?;
+            if notifications && resp.id.is_null() {
+                continue;
+            }
             let id = HashableValue(Cow::Owned(resp.id.clone()));
             if let Some(dup) = by_id.insert(id, resp) {
                 return Err(Error::BatchDuplicateResponseId(dup.id));
             }
         }
-        // Match responses to the requests.
-        let results =
-            requests.iter().map(|r| by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))).collect();
+        // Match responses to the requests, notifications get none.
+        let results = requests
+            .iter()
+            .map(|r| {
+                if r.is_notification() {
+                    None
+                } else {
+                    by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))
+                }
+            })
+            .collect();
 
         // Since we're also just producing the first duplicate ID, we can also just produce the
         // first incorrect ID in case there are multiple.
@@ -100,6 +195,30 @@ impl Client {
         Ok(results)
     }
 
+    /// Sends a notification, a request without id for which the server sends no response.
+    ///
+    /// Under [`Dialect::V1`], the server answers notifications anyway and the response is
+    /// ignored. Interceptors see an empty response once the notification was sent.
+    pub fn notify(&self, method: &str, args: Option<&RawValue>) -> Result<(), Error> {
+        let request = self.build_notification(method, args);
+        self.interceptors.on_request(&request);
+        let start = Instant::now();
+        let result = self.transport.send_notification(request.clone());
+        match result {
+            Ok(()) => {
+                let response = Response {
+                    result: None,
+                    error: None,
+                    id: Value::Null,
+                    jsonrpc: request.jsonrpc.map(From::from),
+                };
+                self.interceptors.on_response(&request, Ok(&response), start.elapsed());
+            }
+            Err(ref e) => self.interceptors.on_response(&request, Err(e), start.elapsed()),
+        }
+        result
+    }
+
     /// Makes a request and deserializes the response.
     ///
     /// To construct the arguments, one can use one of the shorthand methods
@@ -113,15 +232,116 @@ impl Client {
         let id = request.id.clone();
 
         let response = self.send_request(request)?;
-        if response.jsonrpc.is_some() && response.jsonrpc != Some(From::from("2.0")) {
-            return Err(Error::VersionMismatch);
-        }
+        self.dialect.check_response(&response)?;
         if response.id != id {
             return Err(Error::NonceMismatch);
         }
 
         response.result()
     }
+
+    /// Makes a request and deserializes the result as it is received, without buffering the
+    /// whole response.
+    ///
+    /// Meant for huge results, eg. `getblock` with verbosity 3: fails with
+    /// [`Error::ResponseTooLarge`] if the response is larger than `max_size` bytes. Transports
+    /// not supporting streaming buffer the response. Interceptors see the response without its
+    /// result.
+    pub fn call_streaming<R: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        method: &str,
+        args: Option<&RawValue>,
+        max_size: u64,
+    ) -> Result<R, Error> {
+        let request = self.build_request(method, args);
+        let id = request.id.clone();
+
+        self.interceptors.on_request(&request);
+        let start = Instant::now();
+        let response = self.send_request_streaming::<R>(request.clone(), max_size);
+        match response {
+            // The result was deserialized as it was read, interceptors don't see it.
+            Ok(ref response) => {
+                let seen = Response {
+                    result: None,
+                    error: response.error.clone(),
+                    id: response.id.clone(),
+                    jsonrpc: response.jsonrpc.clone(),
+                };
+                self.interceptors.on_response(&request, Ok(&seen), start.elapsed());
+            }
+            Err(ref e) => self.interceptors.on_response(&request, Err(e), start.elapsed()),
+        }
+        let response = response?;
+
+        self.dialect.check(
+            response.jsonrpc.as_deref(),
+            response.result.is_some(),
+            response.error.is_some(),
+        )?;
+        if let Some(e) = response.error {
+            return Err(Error::Rpc(e));
+        }
+        if response.id != id {
+            return Err(Error::NonceMismatch);
+        }
+        match response.result {
+            Some(result) => Ok(result),
+            None => serde_json::from_value(Value::Null).map_err(Error::Json),
+        }
+    }
+
+    fn send_request_streaming<R: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        request: Request,
+        max_size: u64,
+    ) -> Result<StreamedResponse<R>, Error> {
+        let mut response = None;
+        self.transport.send_request_streaming(request, max_size, &mut |reader| {
+            let mut reader = LimitedReader { inner: reader, remaining: max_size, exceeded: false };
+            match serde_json::from_reader::<_, StreamedResponse<R>>(&mut reader) {
+                Ok(streamed) => {
+                    response = Some(streamed);
+                    Ok(())
+                }
+                Err(_) if reader.exceeded => Err(Error::ResponseTooLarge(max_size)),
+                Err(e) => Err(e.into()),
+            }
+        })?;
+        response.ok_or_else(|| {
+            Error::Json(serde::de::Error::custom("the transport returned no response"))
+        })
+    }
+}
+
+/// A response whose result is deserialized as it is read, see [`Client::call_streaming`].
+#[derive(Deserialize)]
+struct StreamedResponse<R> {
+    result: Option<R>,
+    error: Option<RpcError>,
+    id: Value,
+    jsonrpc: Option<String>,
+}
+
+/// Reads at most `remaining` bytes, failing after that.
+struct LimitedReader<'a> {
+    inner: &'a mut dyn io::Read,
+    remaining: u64,
+    exceeded: bool,
+}
+
+impl io::Read for LimitedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len =
+            buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX).saturating_add(1));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n as u64 > self.remaining {
+            self.exceeded = true;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "response too large"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
 }
 
 impl fmt::Debug for Client {
@@ -248,4 +468,141 @@ mod tests {
         coll.insert(m.clone());
         assert!(coll.contains(&m));
     }
+
+    /// Echoes the method as result, buffered.
+    struct EchoTransport;
+    impl Transport for EchoTransport {
+        fn send_request(&self, req: Request) -> Result<Response, Error> {
+            Ok(Response {
+                result: Some(crate::arg(req.method)),
+                error: None,
+                id: req.id,
+                jsonrpc: Some("2.0".into()),
+            })
+        }
+        fn send_batch(&self, _: &[Request]) -> Result<Vec<Response>, Error> { Ok(vec![]) }
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    #[test]
+    fn call_streaming() {
+        let client = Client::with_transport(EchoTransport);
+        let method = "x".repeat(100);
+        let result: String = client.call_streaming(&method, None, 1000).unwrap();
+        assert_eq!(result, method);
+        let error = client.call_streaming::<String>(&method, None, 100).unwrap_err();
+        assert!(matches!(error, Error::ResponseTooLarge(100)));
+    }
+
+    /// Answers with the `jsonrpc` field of the request, and an error too for `both`.
+    struct VersionTransport;
+    impl Transport for VersionTransport {
+        fn send_request(&self, req: Request) -> Result<Response, Error> {
+            let error =
+                crate::error::standard_error(crate::error::StandardError::InternalError, None);
+            Ok(Response {
+                result: Some(crate::arg(req.jsonrpc)),
+                error: if req.method == "both" { Some(error) } else { None },
+                id: req.id,
+                jsonrpc: req.jsonrpc.map(From::from),
+            })
+        }
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, Error> {
+            reqs.iter().map(|req| self.send_request(req.clone())).collect()
+        }
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    #[test]
+    fn dialect() {
+        let client = Client::with_transport(VersionTransport);
+        assert_eq!(client.dialect(), Dialect::V2);
+        assert_eq!(client.call::<Option<String>>("x", None).unwrap().as_deref(), Some("2.0"));
+        assert!(matches!(client.call::<()>("both", None), Err(Error::Rpc(_))));
+
+        let client = client.with_dialect(Dialect::V1);
+        assert_eq!(client.call::<Option<String>>("x", None).unwrap(), None);
+        let batch = [client.build_request("x", None)];
+        assert!(client.send_batch(&batch).unwrap()[0].is_some());
+        let notification = serde_json::to_value(client.build_notification("x", None)).unwrap();
+        assert_eq!(notification.get("id"), Some(&serde_json::Value::Null));
+        let batch = [
+            client.build_request("x", None),
+            client.build_notification("x", None),
+            client.build_notification("x", None),
+            client.build_request("x", None),
+        ];
+        let responses = client.send_batch(&batch).unwrap();
+        assert!(responses[0].is_some() && responses[1].is_none() && responses[2].is_none());
+        assert!(responses[3].is_some());
+
+        let client = client.with_dialect(Dialect::V2Strict);
+        let notification = serde_json::to_value(client.build_notification("x", None)).unwrap();
+        assert_eq!(notification.get("id"), None);
+        assert_eq!(client.call::<Option<String>>("x", None).unwrap().as_deref(), Some("2.0"));
+        assert!(matches!(client.call::<()>("both", None), Err(Error::ResultAndError)));
+        let request = client.build_request("x", None);
+        let response = Response { jsonrpc: None, ..client.send_request(request).unwrap() };
+        assert!(matches!(client.dialect().check_response(&response), Err(Error::VersionMismatch)));
+    }
+
+    /// Records the requests, answering all but notifications.
+    #[derive(Default)]
+    struct NotifyTransport(sync::Arc<sync::Mutex<Vec<String>>>);
+    impl Transport for NotifyTransport {
+        fn send_request(&self, req: Request) -> Result<Response, Error> {
+            self.0.lock().unwrap().push(serde_json::to_string(&req).unwrap());
+            Ok(Response { result: None, error: None, id: req.id, jsonrpc: Some("2.0".into()) })
+        }
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, Error> {
+            let resps = reqs.iter().map(|req| self.send_request(req.clone()));
+            resps.filter(|resp| resp.as_ref().map_or(true, |resp| !resp.id.is_null())).collect()
+        }
+        fn send_notification(&self, req: Request) -> Result<(), Error> {
+            self.send_request(req).map(|_| ())
+        }
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    #[test]
+    fn notifications() {
+        let transport = NotifyTransport::default();
+        let sent = sync::Arc::clone(&transport.0);
+        let client = Client::with_transport(transport);
+
+        client.notify("ping", None).unwrap();
+        let batch = [
+            client.build_request("uptime", None),
+            client.build_notification("ping", None),
+            client.build_request("uptime", None),
+        ];
+        let responses = client.send_batch(&batch).unwrap();
+        assert!(responses[0].is_some() && responses[1].is_none() && responses[2].is_some());
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [
+                r#"{"method":"ping","params":null,"jsonrpc":"2.0"}"#,
+                r#"{"method":"uptime","params":null,"id":1,"jsonrpc":"2.0"}"#,
+                r#"{"method":"ping","params":null,"jsonrpc":"2.0"}"#,
+                r#"{"method":"uptime","params":null,"id":2,"jsonrpc":"2.0"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn limited_reader() {
+        use std::io::Read;
+
+        let mut data: &[u8] = b"0123456789";
+        let mut reader = LimitedReader { inner: &mut data, remaining: 10, exceeded: false };
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"0123456789");
+
+        let mut data: &[u8] = b"0123456789";
+        let mut reader = LimitedReader { inner: &mut data, remaining: 9, exceeded: false };
+        assert!(reader.read_to_end(&mut vec![]).is_err());
+        assert!(reader.exceeded);
+    }
 }
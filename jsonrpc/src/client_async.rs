@@ -12,14 +12,14 @@ use std::future::Future;
 use std::hash::{Hash, Hasher};
 use std::pin::Pin;
 use std::sync::atomic;
+use std::time::Instant;
 
 use serde_json::value::RawValue;
 use serde_json::Value;
 
 use crate::error::Error;
-use crate::{Request, Response};
-
-const JSONRPC_VERSION: &str = "2.0";
+use crate::interceptor::{Chain, Interceptor};
+use crate::{Dialect, Request, Response};
 
 /// Boxed future type used by async transports.
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
@@ -35,6 +35,14 @@ pub trait Transport: Send + Sync + 'static {
     ) -> BoxFuture<'a, Result<Vec<Response>, Error>>;
     /// Formats the target of this transport. I.e. the URL/socket/...
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result;
+
+    /// Sends a notification, a request without id, over the transport.
+    ///
+    /// The default implementation sends it with [`Transport::send_request`] and ignores the
+    /// response, transports to servers not answering notifications must override it.
+    fn send_notification<'a>(&'a self, req: Request<'a>) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { self.send_request(req).await.map(|_| ()) })
+    }
 }
 
 /// An async JSON-RPC client.
@@ -43,14 +51,37 @@ pub trait Transport: Send + Sync + 'static {
 pub struct Client {
     pub(crate) transport: Box<dyn Transport>,
     nonce: atomic::AtomicUsize,
+    interceptors: Chain,
+    dialect: Dialect,
 }
 
 impl Client {
     /// Creates a new client with the given transport.
     pub fn with_transport<T: Transport>(transport: T) -> Client {
-        Client { transport: Box::new(transport), nonce: atomic::AtomicUsize::new(1) }
+        Client {
+            transport: Box::new(transport),
+            nonce: atomic::AtomicUsize::new(1),
+            interceptors: Chain::default(),
+            dialect: Dialect::default(),
+        }
+    }
+
+    /// Adds an interceptor seeing the requests of this client and their responses, after the
+    /// interceptors already added.
+    pub fn with_interceptor<I: Interceptor>(mut self, interceptor: I) -> Client {
+        self.interceptors.push(Box::new(interceptor));
+        self
     }
 
+    /// Sets the JSON-RPC dialect of the requests and responses, [`Dialect::V2`] by default.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Client {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Returns the JSON-RPC dialect of this client.
+    pub fn dialect(&self) -> Dialect { self.dialect }
+
     /// Builds a request.
     ///
     /// To construct the arguments, one can use one of the shorthand methods.
@@ -61,16 +92,33 @@ impl Client {
             method,
             params,
             id: serde_json::Value::from(nonce),
-            jsonrpc: Some(JSONRPC_VERSION),
+            jsonrpc: self.dialect.request_version(),
         }
     }
 
+    /// Builds a notification, a request without id which gets no response.
+    ///
+    /// Notifications can be sent with [`Client::notify`] or as part of a batch.
+    pub fn build_notification<'a>(
+        &self,
+        method: &'a str,
+        params: Option<&'a RawValue>,
+    ) -> Request<'a> {
+        Request { method, params, id: Value::Null, jsonrpc: self.dialect.request_version() }
+    }
+
     /// Sends a request to a client.
     pub fn send_request<'a>(
         &'a self,
         request: Request<'a>,
     ) -> BoxFuture<'a, Result<Response, Error>> {
-        self.transport.send_request(request)
+        Box::pin(async move {
+            self.interceptors.on_request(&request);
+            let start = Instant::now();
+            let response = self.transport.send_request(request.clone()).await;
+            self.interceptors.on_response(&request, response.as_ref(), start.elapsed());
+            response
+        })
     }
 
     /// Sends a batch of requests to the client.
@@ -81,10 +129,23 @@ impl Client {
     /// # Returns
     ///
     /// The return vector holds the response for the request at the corresponding index. If no
-    /// response was provided, it's [`None`].
+    /// response was provided, it's [`None`]. It is always [`None`] for notifications.
     pub async fn send_batch(
         &self,
         requests: &[Request<'_>],
+    ) -> Result<Vec<Option<Response>>, Error> {
+        for request in requests {
+            self.interceptors.on_request(request);
+        }
+        let start = Instant::now();
+        let responses = self.try_send_batch(requests).await;
+        self.interceptors.on_batch_response(requests, &responses, start.elapsed());
+        responses
+    }
+
+    async fn try_send_batch(
+        &self,
+        requests: &[Request<'_>],
     ) -> Result<Vec<Option<Response>>, Error> {
         if requests.is_empty() {
             return Err(Error::EmptyBatch);
@@ -99,17 +160,31 @@ impl Client {
 
         //TODO(stevenroose) check if the server preserved order to avoid doing the mapping
 
-        // First index responses by ID and catch duplicate IDs.
+        // First index responses by ID and catch duplicate IDs. Servers answering notifications,
+        // as under JSON-RPC 1.0, answer them with a null ID.
+        let notifications = requests.iter().any(Request::is_notification);
         let mut by_id = HashMap::with_capacity(requests.len());
         for resp in responses.into_iter() {
+            self.dialect.check_response(&resp)?;
+            if notifications && resp.id.is_null() {
+                continue;
+            }
             let id = HashableValue(Cow::Owned(resp.id.clone()));
             if let Some(dup) = by_id.insert(id, resp) {
                 return Err(Error::BatchDuplicateResponseId(dup.id));
             }
         }
-        // Match responses to the requests.
-        let results =
-            requests.iter().map(|r| by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))).collect();
+        // Match responses to the requests, notifications get none.
+        let results = requests
+            .iter()
+            .map(|r| {
+                if r.is_notification() {
+                    None
+                } else {
+                    by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))
+                }
+            })
+            .collect();
 
         // Since we're also just producing the first duplicate ID, we can also just produce the
         // first incorrect ID in case there are multiple.
@@ -120,6 +195,30 @@ impl Client {
         Ok(results)
     }
 
+    /// Sends a notification, a request without id for which the server sends no response.
+    ///
+    /// Under [`Dialect::V1`], the server answers notifications anyway and the response is
+    /// ignored. Interceptors see an empty response once the notification was sent.
+    pub async fn notify(&self, method: &str, args: Option<&RawValue>) -> Result<(), Error> {
+        let request = self.build_notification(method, args);
+        self.interceptors.on_request(&request);
+        let start = Instant::now();
+        let result = self.transport.send_notification(request.clone()).await;
+        match result {
+            Ok(()) => {
+                let response = Response {
+                    result: None,
+                    error: None,
+                    id: Value::Null,
+                    jsonrpc: request.jsonrpc.map(From::from),
+                };
+                self.interceptors.on_response(&request, Ok(&response), start.elapsed());
+            }
+            Err(ref e) => self.interceptors.on_response(&request, Err(e), start.elapsed()),
+        }
+        result
+    }
+
     /// Makes a request and deserializes the response.
     ///
     /// To construct the arguments, one can use one of the shorthand methods
@@ -133,9 +232,7 @@ impl Client {
         let id = request.id.clone();
 
         let response = self.send_request(request).await?;
-        if response.jsonrpc.is_some() && response.jsonrpc.as_deref() != Some(JSONRPC_VERSION) {
-            return Err(Error::VersionMismatch);
-        }
+        self.dialect.check_response(&response)?;
         if response.id != id {
             return Err(Error::NonceMismatch);
         }
@@ -22,8 +22,10 @@ pub enum Error {
     Rpc(RpcError),
     /// Response to a request did not have the expected nonce.
     NonceMismatch,
-    /// Response to a request had a jsonrpc field other than "2.0".
+    /// Response to a request had a jsonrpc field not allowed by the client's [`crate::Dialect`].
     VersionMismatch,
+    /// Response to a request had both a result and an error, forbidden by strict JSON-RPC 2.0.
+    ResultAndError,
     /// Batches can't be empty.
     EmptyBatch,
     /// Too many responses returned in batch.
@@ -32,6 +34,8 @@ pub enum Error {
     BatchDuplicateResponseId(serde_json::Value),
     /// Batch response contained an ID that didn't correspond to any request ID.
     WrongBatchResponseId(serde_json::Value),
+    /// The response exceeded the maximum size, in bytes, of a streamed call.
+    ResponseTooLarge(u64),
 }
 
 impl From<serde_json::Error> for Error {
@@ -53,9 +57,11 @@ impl fmt::Display for Error {
             BatchDuplicateResponseId(ref v) => write!(f, "duplicate RPC batch response ID: {}", v),
             WrongBatchResponseId(ref v) => write!(f, "wrong RPC batch response ID: {}", v),
             NonceMismatch => write!(f, "nonce of response did not match nonce of request"),
-            VersionMismatch => write!(f, "`jsonrpc` field set to non-\"2.0\""),
+            VersionMismatch => write!(f, "`jsonrpc` field doesn't match the JSON-RPC dialect"),
+            ResultAndError => write!(f, "response has both a result and an error"),
             EmptyBatch => write!(f, "batches can't be empty"),
             WrongBatchResponseSize => write!(f, "too many responses returned in batch"),
+            ResponseTooLarge(max) => write!(f, "response larger than the maximum of {} bytes", max),
         }
     }
 }
@@ -68,10 +74,12 @@ impl error::Error for Error {
             Rpc(_)
             | NonceMismatch
             | VersionMismatch
+            | ResultAndError
             | EmptyBatch
             | WrongBatchResponseSize
             | BatchDuplicateResponseId(_)
-            | WrongBatchResponseId(_) => None,
+            | WrongBatchResponseId(_)
+            | ResponseTooLarge(_) => None,
             Transport(ref e) => Some(&**e),
             Json(ref e) => Some(e),
         }
@@ -12,6 +12,7 @@ use std::{error, fmt};
 
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
+use bitreq::RequestExt;
 
 use crate::client::Transport;
 use crate::{Request, Response};
@@ -22,6 +23,8 @@ const DEFAULT_PORT: u16 = 8332; // the default RPC port for bitcoind.
 const DEFAULT_TIMEOUT_SECONDS: u64 = 15;
 #[cfg(jsonrpc_fuzz)]
 const DEFAULT_TIMEOUT_SECONDS: u64 = 1;
+/// The maximum number of idle connections kept open to the RPC server.
+const MAX_IDLE_CONNECTIONS: usize = 8;
 
 /// An HTTP transport that uses [`bitreq`] and is useful for running a bitcoind RPC client.
 #[derive(Clone, Debug)]
@@ -32,6 +35,8 @@ pub struct BitreqHttpTransport {
     timeout: Duration,
     /// The value of the `Authorization` HTTP header, i.e., a base64 encoding of 'user:password'.
     basic_auth: Option<String>,
+    /// The pool of keep-alive connections to the RPC server.
+    client: bitreq::Client,
 }
 
 impl Default for BitreqHttpTransport {
@@ -40,6 +45,7 @@ impl Default for BitreqHttpTransport {
             url: format!("{}:{}", DEFAULT_URL, DEFAULT_PORT),
             timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
             basic_auth: None,
+            client: bitreq::Client::new(MAX_IDLE_CONNECTIONS),
         }
     }
 }
@@ -51,36 +57,28 @@ impl BitreqHttpTransport {
     /// Returns a builder for [`BitreqHttpTransport`].
     pub fn builder() -> Builder { Builder::new() }
 
-    /// Returns the timeout in whole seconds, rounding positive sub-second values up to one.
-    fn timeout_secs(&self) -> u64 {
-        let secs = self.timeout.as_secs();
-        if secs == 0 && self.timeout > Duration::from_secs(0) {
-            1
-        } else {
-            secs
-        }
-    }
-
     fn request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let timeout_secs = self.timeout_secs();
-
         let req = match &self.basic_auth {
             Some(auth) => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(timeout_secs)
+                .with_total_timeout(self.timeout)
                 .with_header("Authorization", auth)
                 .with_json(&req)?,
             None => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(timeout_secs)
+                .with_total_timeout(self.timeout)
                 .with_json(&req)?,
         };
 
         // Send the request and parse the response. If the response is an error that does not
         // contain valid JSON in its body (for instance if the bitcoind HTTP server work queue
         // depth is exceeded), return the raw HTTP error so users can match against it.
-        let resp = req.send()?;
+        let resp = req.send_with_client(&self.client)?;
+        // A `204 No Content` response, to notifications, parses as `null`.
+        if resp.status_code == 204 {
+            return Ok(serde_json::from_str("null")?);
+        }
         match resp.json() {
             Ok(json) => Ok(json),
             Err(bitreq_err) =>
@@ -102,7 +100,14 @@ impl Transport for BitreqHttpTransport {
     }
 
     fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
-        Ok(self.request(reqs)?)
+        // A JSON-RPC 2.0 server answers a batch of notifications with no content.
+        Ok(self.request::<Option<Vec<Response>>>(reqs)?.unwrap_or_default())
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        // The response has no content, or is ignored under JSON-RPC 1.0.
+        self.request::<Option<serde::de::IgnoredAny>>(req)?;
+        Ok(())
     }
 
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.url) }
@@ -44,16 +44,6 @@ impl BitreqHttpTransport {
     /// Returns a builder for [`BitreqHttpTransport`].
     pub fn builder() -> Builder { Builder::new() }
 
-    /// Returns the timeout in whole seconds, rounding positive sub-second values up to one.
-    fn timeout_secs(&self) -> u64 {
-        let secs = self.timeout.as_secs();
-        if secs == 0 && self.timeout > Duration::from_secs(0) {
-            1
-        } else {
-            secs
-        }
-    }
-
     async fn request<R>(&self, req: impl serde::Serialize) -> Result<R, crate::Error>
     where
         R: for<'a> serde::de::Deserialize<'a>,
@@ -65,15 +55,13 @@ impl BitreqHttpTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let timeout_secs = self.timeout_secs();
-
         let req = match &self.basic_auth {
             Some(auth) => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(timeout_secs)
+                .with_total_timeout(self.timeout)
                 .with_header("Authorization", auth)
                 .with_json(&req)?,
             None => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(timeout_secs)
+                .with_total_timeout(self.timeout)
                 .with_json(&req)?,
         };
 
@@ -81,6 +69,10 @@ impl BitreqHttpTransport {
         // contain valid JSON in its body (for instance if the bitcoind HTTP server work queue
         // depth is exceeded), return the raw HTTP error so users can match against it.
         let resp = req.send_async().await?;
+        // A `204 No Content` response, to notifications, parses as `null`.
+        if resp.status_code == 204 {
+            return Ok(serde_json::from_str("null")?);
+        }
         match resp.json() {
             Ok(json) => Ok(json),
             Err(bitreq_err) =>
@@ -108,7 +100,21 @@ impl Transport for BitreqHttpTransport {
         &'a self,
         reqs: &'a [Request<'a>],
     ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
-        Box::pin(self.request(reqs))
+        // A JSON-RPC 2.0 server answers a batch of notifications with no content.
+        Box::pin(async move {
+            Ok(self.request::<Option<Vec<Response>>>(reqs).await?.unwrap_or_default())
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        // The response has no content, or is ignored under JSON-RPC 1.0.
+        Box::pin(async move {
+            self.request::<Option<serde::de::IgnoredAny>>(req).await?;
+            Ok(())
+        })
     }
 
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.url) }
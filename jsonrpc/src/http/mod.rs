@@ -3,6 +3,9 @@
 #[cfg(feature = "simple_http")]
 pub mod simple_http;
 
+#[cfg(feature = "simple_http_async")]
+pub mod simple_http_async;
+
 #[cfg(feature = "bitreq_http")]
 pub mod bitreq_http;
 
@@ -15,6 +18,6 @@ pub const DEFAULT_PORT: u16 = 8332;
 
 /// The Default SOCKS5 Port to use for proxy connection.
 /// Set to 9050, the default RPC port for tor.
-// Currently only used by `simple_http` module, here for consistency.
+// Currently only used by the `simple_http` modules, here for consistency.
 #[cfg(feature = "proxy")]
 pub const DEFAULT_PROXY_PORT: u16 = 9050;
@@ -5,17 +5,28 @@
 //!
 //! This can be used if minimal dependencies are a goal and
 //! synchronous communication is ok.
+//!
+//! With the `simple_https` feature, `https` URLs are served over TLS using `rustls`, see
+//! [`Builder::tls_root_certificates_pem`] and [`Builder::tls_client_auth_pem`].
 
 use std::io::{BufRead, BufReader, Read, Write};
 #[cfg(not(jsonrpc_fuzz))]
 use std::net::TcpStream;
 use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(feature = "simple_https")]
+use std::sync::OnceLock;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 use std::{error, fmt, io, net, num};
 
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
+#[cfg(feature = "simple_https")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "simple_https")]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+#[cfg(feature = "simple_https")]
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
 #[cfg(feature = "proxy")]
 use socks::Socks5Stream;
 
@@ -26,7 +37,7 @@ use crate::http::DEFAULT_PROXY_PORT;
 use crate::{Request, Response};
 
 /// Absolute maximum content length allowed before cutting off the response.
-const FINAL_RESP_ALLOC: u64 = 1024 * 1024 * 1024;
+pub(crate) const FINAL_RESP_ALLOC: u64 = 1024 * 1024 * 1024;
 
 #[cfg(not(jsonrpc_fuzz))]
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
@@ -48,7 +59,52 @@ pub struct SimpleHttpTransport {
     proxy_addr: net::SocketAddr,
     #[cfg(feature = "proxy")]
     proxy_auth: Option<(String, String)>,
-    sock: Arc<Mutex<Option<BufReader<TcpStream>>>>,
+    /// Whether the URL has the `https` scheme.
+    #[cfg(feature = "simple_https")]
+    https: bool,
+    /// The TLS configuration, [`None`] for the default one.
+    #[cfg(feature = "simple_https")]
+    tls_config: Option<Arc<ClientConfig>>,
+    sock: Arc<Mutex<Option<BufReader<Stream>>>>,
+}
+
+/// The stream to the server, TLS is only supported with the `simple_https` feature.
+#[cfg(not(feature = "simple_https"))]
+type Stream = TcpStream;
+
+/// The stream to the server, either plaintext or TLS.
+#[cfg(feature = "simple_https")]
+#[derive(Debug)]
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+#[cfg(feature = "simple_https")]
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "simple_https")]
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
 }
 
 impl Default for SimpleHttpTransport {
@@ -69,6 +125,10 @@ impl Default for SimpleHttpTransport {
             ),
             #[cfg(feature = "proxy")]
             proxy_auth: None,
+            #[cfg(feature = "simple_https")]
+            https: false,
+            #[cfg(feature = "simple_https")]
+            tls_config: None,
             sock: Arc::new(Mutex::new(None)),
         }
     }
@@ -82,7 +142,13 @@ impl SimpleHttpTransport {
     pub fn builder() -> Builder { Builder::new() }
 
     /// Replaces the URL of the transport.
+    ///
+    /// With the `simple_https` feature, connections to `https` URLs use TLS.
     pub fn set_url(&mut self, url: &str) -> Result<(), Error> {
+        #[cfg(feature = "simple_https")]
+        {
+            self.https = url.starts_with("https://");
+        }
         let url = check_url(url)?;
         self.addr = url.0;
         self.host_header = url.1;
@@ -97,7 +163,50 @@ impl SimpleHttpTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        match self.try_request(req) {
+        self.request_with(req, FINAL_RESP_ALLOC, |head, reader| {
+            let mut body = Vec::new();
+            let n_read = reader.take(FINAL_RESP_ALLOC + 1).read_to_end(&mut body)? as u64;
+            if n_read > FINAL_RESP_ALLOC {
+                return Err(Error::HttpResponseContentLengthTooLarge {
+                    length: n_read,
+                    max: FINAL_RESP_ALLOC,
+                });
+            }
+            if let Some(n) = head.content_length.filter(|_| head.has_body() && !head.chunked) {
+                if n_read < n {
+                    return Err(Error::IncompleteResponse { content_length: n, n_read });
+                }
+            }
+
+            // Attempt to parse the response. Don't check the HTTP error code until
+            // after parsing, since Bitcoin Core will often return a descriptive JSON
+            // error structure which is more useful than the error code.
+            // A `204 No Content` response, to notifications, parses as `null`.
+            let body: &[u8] = if head.status == 204 { b"null" } else { &body };
+            match serde_json::from_slice(body) {
+                Ok(s) => Ok(s),
+                Err(e) => {
+                    // If the response was not 200, assume the parse failed because of that
+                    if head.status != 200 {
+                        Err(Error::HttpErrorCode(head.status))
+                    } else {
+                        // If it was 200 then probably it was legitimately a parse error
+                        Err(e.into())
+                    }
+                }
+            }
+        })
+    }
+
+    /// Sends `req` and passes the body of the response, of at most `max_size` bytes, to
+    /// `read_body`.
+    fn request_with<T, E: From<Error>>(
+        &self,
+        req: impl serde::Serialize,
+        max_size: u64,
+        read_body: impl FnOnce(&ResponseHead, &mut dyn Read) -> Result<T, E>,
+    ) -> Result<T, E> {
+        match self.try_request_with(req, max_size, read_body) {
             Ok(response) => Ok(response),
             Err(err) => {
                 // No part of this codebase should panic, so unwrapping a mutex lock is fine
@@ -107,35 +216,73 @@ impl SimpleHttpTransport {
         }
     }
 
-    #[cfg(feature = "proxy")]
-    fn fresh_socket(&self) -> Result<TcpStream, Error> {
-        let stream = if let Some((username, password)) = &self.proxy_auth {
-            Socks5Stream::connect_with_password(
-                self.proxy_addr,
-                self.addr,
-                username.as_str(),
-                password.as_str(),
-            )?
-        } else {
-            Socks5Stream::connect(self.proxy_addr, self.addr)?
+    #[cfg(not(feature = "simple_https"))]
+    fn fresh_socket(&self) -> Result<Stream, Error> { self.fresh_tcp_socket() }
+
+    #[cfg(feature = "simple_https")]
+    fn fresh_socket(&self) -> Result<Stream, Error> {
+        let stream = self.fresh_tcp_socket()?;
+        if !self.https {
+            return Ok(Stream::Plain(stream));
+        }
+        let config = match self.tls_config {
+            Some(ref config) => Arc::clone(config),
+            None => Arc::clone(DEFAULT_TLS_CONFIG.get_or_init(|| {
+                Arc::new(tls_config(None, None).expect("no client certificate to check"))
+            })),
         };
-        Ok(stream.into_inner())
+        let conn = ClientConnection::new(config, tls_server_name(&self.host_header)?)?;
+        Ok(Stream::Tls(Box::new(StreamOwned::new(conn, stream))))
+    }
+
+    #[cfg(feature = "proxy")]
+    fn fresh_tcp_socket(&self) -> Result<TcpStream, Error> {
+        let auth = self.proxy_auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str()));
+        Ok(socks5_connect(self.proxy_addr, self.addr, auth)?)
     }
 
     #[cfg(not(feature = "proxy"))]
-    fn fresh_socket(&self) -> Result<TcpStream, Error> {
+    fn fresh_tcp_socket(&self) -> Result<TcpStream, Error> {
         let stream = TcpStream::connect_timeout(&self.addr, self.timeout)?;
         stream.set_read_timeout(Some(self.timeout))?;
         stream.set_write_timeout(Some(self.timeout))?;
         Ok(stream)
     }
 
-    fn try_request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
-    where
-        R: for<'a> serde::de::Deserialize<'a>,
-    {
+    fn try_request_with<T, E: From<Error>>(
+        &self,
+        req: impl serde::Serialize,
+        max_size: u64,
+        read_body: impl FnOnce(&ResponseHead, &mut dyn Read) -> Result<T, E>,
+    ) -> Result<T, E> {
         // No part of this codebase should panic, so unwrapping a mutex lock is fine
         let mut sock_lock: MutexGuard<Option<_>> = self.sock.lock().expect("poisoned mutex");
+        let mut head = self.send(&mut sock_lock, req)?;
+        // `send` leaves an open socket, so unwrapping here is fine.
+        let sock: &mut BufReader<_> = sock_lock.as_mut().unwrap();
+
+        let result = {
+            let mut reader = body_reader(&mut head, sock, max_size)?;
+            let result = read_body(&head, &mut reader)?;
+            // Consume what `read_body` left, eg. trailing whitespace, to reuse the connection.
+            io::copy(&mut reader, &mut io::sink()).map_err(Error::from)?;
+            result
+        };
+
+        // The server won't accept another request on this connection.
+        if head.close {
+            *sock_lock = None;
+        }
+        Ok(result)
+    }
+
+    /// Sends `req` on the cached socket, opening one if needed, and reads the head of the
+    /// response.
+    fn send(
+        &self,
+        sock_lock: &mut Option<BufReader<Stream>>,
+        req: impl serde::Serialize,
+    ) -> Result<ResponseHead, Error> {
         if sock_lock.is_none() {
             *sock_lock = Some(BufReader::new(self.fresh_socket()?));
         };
@@ -193,96 +340,261 @@ impl SimpleHttpTransport {
             sock.read_line(&mut header_buf)?;
         }
 
-        if header_buf.len() < 12 {
-            return Err(Error::HttpResponseTooShort { actual: header_buf.len(), needed: 12 });
-        }
-        if !header_buf.as_bytes()[..12].is_ascii() {
-            return Err(Error::HttpResponseNonAsciiHello(header_buf.as_bytes()[..12].to_vec()));
-        }
-        if !header_buf.starts_with("HTTP/1.1 ") {
-            return Err(Error::HttpResponseBadHello {
-                actual: header_buf[0..9].into(),
-                expected: "HTTP/1.1 ".into(),
-            });
-        }
-        let response_code = match header_buf[9..12].parse::<u16>() {
-            Ok(n) => n,
-            Err(e) => return Err(Error::HttpResponseBadStatus(header_buf[9..12].into(), e)),
-        };
+        let mut head = ResponseHead::from_status_line(&header_buf)?;
 
         // Parse response header fields
-        let mut content_length = None;
         loop {
             header_buf.clear();
-            sock.read_line(&mut header_buf)?;
+            if sock.read_line(&mut header_buf)? == 0 {
+                return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+            }
             if header_buf == "\r\n" {
                 break;
             }
-            header_buf.make_ascii_lowercase();
+            head.parse_header(&header_buf)?;
+        }
 
-            const CONTENT_LENGTH: &str = "content-length: ";
-            if let Some(s) = header_buf.strip_prefix(CONTENT_LENGTH) {
-                content_length = Some(
-                    s.trim()
+        if head.status == 401 {
+            // There is no body in a 401 response, so don't try to read it
+            return Err(Error::HttpErrorCode(head.status));
+        }
+        Ok(head)
+    }
+}
+
+/// Returns a reader of the body of the response with the given `head`.
+///
+/// Fails if the body is known to be larger than `max_size`, otherwise reads at most one byte
+/// more than it.
+fn body_reader<'a, R: BufRead + 'a>(
+    head: &mut ResponseHead,
+    sock: &'a mut R,
+    max_size: u64,
+) -> Result<Box<dyn Read + 'a>, Error> {
+    if !head.has_body() {
+        return Ok(Box::new(io::empty()));
+    }
+    if head.chunked {
+        return Ok(Box::new(ChunkedReader::new(sock)));
+    }
+    match head.content_length {
+        // Without content-length header the body is delimited by the server closing the
+        // connection.
+        None => {
+            head.close = true;
+            Ok(Box::new(sock.take(max_size.saturating_add(1))))
+        }
+        Some(n) if n > max_size =>
+            Err(Error::HttpResponseContentLengthTooLarge { length: n, max: max_size }),
+        Some(n) => Ok(Box::new(sock.take(n))),
+    }
+}
+
+/// The parts of the head of an HTTP response the transports care about.
+#[derive(Debug)]
+pub(crate) struct ResponseHead {
+    /// The status code.
+    pub(crate) status: u16,
+    /// The value of the content-length header.
+    pub(crate) content_length: Option<u64>,
+    /// Whether the body uses the chunked transfer encoding.
+    pub(crate) chunked: bool,
+    /// Whether the server closes the connection after this response.
+    pub(crate) close: bool,
+}
+
+impl ResponseHead {
+    /// Parses the status line of an HTTP/1.1 or HTTP/1.0 response.
+    pub(crate) fn from_status_line(line: &str) -> Result<ResponseHead, Error> {
+        if line.len() < 12 {
+            return Err(Error::HttpResponseTooShort { actual: line.len(), needed: 12 });
+        }
+        if !line.as_bytes()[..12].is_ascii() {
+            return Err(Error::HttpResponseNonAsciiHello(line.as_bytes()[..12].to_vec()));
+        }
+        // HTTP/1.0 connections are closed after the response unless asked otherwise.
+        let close = match &line[0..9] {
+            "HTTP/1.1 " => false,
+            "HTTP/1.0 " => true,
+            _ =>
+                return Err(Error::HttpResponseBadHello {
+                    actual: line[0..9].into(),
+                    expected: "HTTP/1.1 ".into(),
+                }),
+        };
+        let status = match line[9..12].parse::<u16>() {
+            Ok(n) => n,
+            Err(e) => return Err(Error::HttpResponseBadStatus(line[9..12].into(), e)),
+        };
+        Ok(ResponseHead { status, content_length: None, chunked: false, close })
+    }
+
+    /// Parses a header field line, ignoring the fields the transports don't care about.
+    pub(crate) fn parse_header(&mut self, line: &str) -> Result<(), Error> {
+        let Some((name, value)) = line.split_once(':') else { return Ok(()) };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => {
+                self.content_length = Some(
+                    value
                         .parse::<u64>()
-                        .map_err(|e| Error::HttpResponseBadContentLength(s.into(), e))?,
+                        .map_err(|e| Error::HttpResponseBadContentLength(value.into(), e))?,
                 );
             }
+            "transfer-encoding" =>
+                self.chunked = value
+                    .rsplit(',')
+                    .next()
+                    .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked")),
+            "connection" =>
+                for option in value.split(',').map(str::trim) {
+                    if option.eq_ignore_ascii_case("close") {
+                        self.close = true;
+                    } else if option.eq_ignore_ascii_case("keep-alive") {
+                        self.close = false;
+                    }
+                },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns whether the response has a body, responses to `POST` with the informational,
+    /// `204 No Content` and `304 Not Modified` status codes never do.
+    pub(crate) fn has_body(&self) -> bool {
+        !(100..200).contains(&self.status) && self.status != 204 && self.status != 304
+    }
+}
 
-            const TRANSFER_ENCODING: &str = "transfer-encoding: ";
-            if let Some(s) = header_buf.strip_prefix(TRANSFER_ENCODING) {
-                const CHUNKED: &str = "chunked";
-                if s.trim() == CHUNKED {
-                    return Err(Error::HttpResponseChunked);
+/// Parses the line starting a chunk, returning the size of the chunk.
+pub(crate) fn parse_chunk_size(line: &str) -> Result<u64, Error> {
+    // Chunk extensions after a `;` are ignored.
+    let size = line.split(';').next().unwrap_or_default().trim();
+    u64::from_str_radix(size, 16).map_err(|_| Error::HttpResponseBadChunk(line.trim().into()))
+}
+
+/// Decodes a body sent with the chunked transfer encoding.
+struct ChunkedReader<R> {
+    inner: R,
+    /// The number of bytes left in the current chunk.
+    remaining: u64,
+    /// Whether the last chunk and the trailer fields were read.
+    done: bool,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    fn new(inner: R) -> Self { ChunkedReader { inner, remaining: 0, done: false } }
+
+    fn read_line(&mut self, line: &mut String) -> Result<(), Error> {
+        line.clear();
+        if self.inner.read_line(line)? == 0 {
+            return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(())
+    }
+
+    /// Reads the start of the next chunk, and the trailer fields if it is the last one.
+    fn next_chunk(&mut self) -> Result<(), Error> {
+        let mut line = String::new();
+        self.read_line(&mut line)?;
+        self.remaining = parse_chunk_size(&line)?;
+        if self.remaining == 0 {
+            // Skip the trailer fields.
+            loop {
+                self.read_line(&mut line)?;
+                if line == "\r\n" {
+                    break;
                 }
             }
+            self.done = true;
         }
+        Ok(())
+    }
 
-        if response_code == 401 {
-            // There is no body in a 401 response, so don't try to read it
-            return Err(Error::HttpErrorCode(response_code));
+    /// Reads the end of the current chunk.
+    fn end_chunk(&mut self) -> Result<(), Error> {
+        let mut line = String::new();
+        self.read_line(&mut line)?;
+        if line != "\r\n" {
+            return Err(Error::HttpResponseBadChunk(line.trim().into()));
         }
+        Ok(())
+    }
+}
 
-        // Read up to `content_length` bytes. Note that if there is no content-length
-        // header, we will assume an effectively infinite content length, i.e. we will
-        // just keep reading from the socket until it is closed.
-        let mut reader = match content_length {
-            None => sock.take(FINAL_RESP_ALLOC),
-            Some(n) if n > FINAL_RESP_ALLOC => {
-                return Err(Error::HttpResponseContentLengthTooLarge {
-                    length: n,
-                    max: FINAL_RESP_ALLOC,
-                });
-            }
-            Some(n) => sock.take(n),
-        };
-
-        // Attempt to parse the response. Don't check the HTTP error code until
-        // after parsing, since Bitcoin Core will often return a descriptive JSON
-        // error structure which is more useful than the error code.
-        match serde_json::from_reader(&mut reader) {
-            Ok(s) => {
-                if content_length.is_some() {
-                    reader.bytes().count(); // consume any trailing bytes
-                }
-                Ok(s)
-            }
-            Err(e) => {
-                // If the response was not 200, assume the parse failed because of that
-                if response_code != 200 {
-                    Err(Error::HttpErrorCode(response_code))
-                } else {
-                    // If it was 200 then probably it was legitimately a parse error
-                    Err(e.into())
-                }
-            }
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 && !self.done {
+            self.next_chunk()?;
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0);
         }
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+        if self.remaining == 0 {
+            self.end_chunk()?;
+        }
+        Ok(n)
     }
 }
 
+/// The TLS configuration used when none is set on the [`Builder`].
+#[cfg(feature = "simple_https")]
+static DEFAULT_TLS_CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+/// Builds a TLS configuration trusting `roots`, or the Mozilla root certificates if [`None`], and
+/// authenticating with `client_auth` if set.
+#[cfg(feature = "simple_https")]
+fn tls_config(
+    roots: Option<RootCertStore>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> Result<ClientConfig, Error> {
+    let roots =
+        roots.unwrap_or_else(|| RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() });
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    Ok(match client_auth {
+        Some((cert_chain, key)) => builder.with_client_auth_cert(cert_chain, key)?,
+        None => builder.with_no_client_auth(),
+    })
+}
+
+/// Returns the name to verify the certificate of the server against, the host part of the
+/// `host_header`.
+#[cfg(feature = "simple_https")]
+fn tls_server_name(host_header: &str) -> Result<ServerName<'static>, Error> {
+    let host = match host_header.strip_prefix('[') {
+        // IPv6 literals are bracketed.
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host_header.split(':').next().unwrap_or_default(),
+    };
+    ServerName::try_from(host.to_owned())
+        .map_err(|_| Error::url(host_header, "invalid hostname for TLS"))
+}
+
+/// Connects to `target` through the SOCKS5 proxy at `proxy`, with optional username/password
+/// authentication.
+#[cfg(feature = "proxy")]
+pub(crate) fn socks5_connect(
+    proxy: SocketAddr,
+    target: SocketAddr,
+    auth: Option<(&str, &str)>,
+) -> io::Result<TcpStream> {
+    let stream = match auth {
+        Some((username, password)) =>
+            Socks5Stream::connect_with_password(proxy, target, username, password)?,
+        None => Socks5Stream::connect(proxy, target)?,
+    };
+    Ok(stream.into_inner())
+}
+
 /// Does some very basic manual URL parsing because the uri/url crates
 /// all have unicode-normalization as a dependency and that's broken.
-fn check_url(url: &str) -> Result<(SocketAddr, String, String), Error> {
+pub(crate) fn check_url(url: &str) -> Result<(SocketAddr, String, String), Error> {
     // The fallback port in case no port was provided.
     // This changes when the http or https scheme was provided.
     let mut fallback_port = DEFAULT_PORT;
@@ -350,11 +662,46 @@ impl Transport for SimpleHttpTransport {
     }
 
     fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
-        Ok(self.request(reqs)?)
+        // A JSON-RPC 2.0 server answers a batch of notifications with no content.
+        Ok(self.request::<Option<Vec<Response>>>(reqs)?.unwrap_or_default())
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        // The response has no content, or is ignored under JSON-RPC 1.0.
+        self.request::<Option<serde::de::IgnoredAny>>(req)?;
+        Ok(())
+    }
+
+    fn send_request_streaming(
+        &self,
+        req: Request,
+        max_size: u64,
+        read: &mut dyn FnMut(&mut dyn Read) -> Result<(), crate::Error>,
+    ) -> Result<(), crate::Error> {
+        let result = self.request_with(req, max_size, |head, reader| match read(reader) {
+            // As for buffered responses, a non-200 status only matters if the body isn't JSON.
+            Err(crate::Error::Json(_)) if head.status != 200 =>
+                Err(Error::HttpErrorCode(head.status).into()),
+            result => result,
+        });
+        // A body known to be too large from its content length is reported as any other.
+        match result {
+            Err(crate::Error::Transport(e))
+                if matches!(
+                    e.downcast_ref(),
+                    Some(Error::HttpResponseContentLengthTooLarge { .. })
+                ) =>
+                Err(crate::Error::ResponseTooLarge(max_size)),
+            result => result,
+        }
     }
 
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "http://{}:{}{}", self.addr.ip(), self.addr.port(), self.path)
+        #[cfg(feature = "simple_https")]
+        let scheme = if self.https { "https" } else { "http" };
+        #[cfg(not(feature = "simple_https"))]
+        let scheme = "http";
+        write!(f, "{}://{}:{}{}", scheme, self.addr.ip(), self.addr.port(), self.path)
     }
 }
 
@@ -362,11 +709,40 @@ impl Transport for SimpleHttpTransport {
 #[derive(Clone, Debug)]
 pub struct Builder {
     tp: SimpleHttpTransport,
+    #[cfg(feature = "simple_https")]
+    tls: TlsOptions,
+}
+
+/// The TLS options set on a [`Builder`].
+#[cfg(feature = "simple_https")]
+#[derive(Debug, Default)]
+struct TlsOptions {
+    roots: Option<RootCertStore>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+#[cfg(feature = "simple_https")]
+impl Clone for TlsOptions {
+    fn clone(&self) -> Self {
+        TlsOptions {
+            roots: self.roots.clone(),
+            client_auth: self
+                .client_auth
+                .as_ref()
+                .map(|(cert_chain, key)| (cert_chain.clone(), key.clone_key())),
+        }
+    }
 }
 
 impl Builder {
     /// Constructs a new [`Builder`] with default configuration.
-    pub fn new() -> Builder { Builder { tp: SimpleHttpTransport::new() } }
+    pub fn new() -> Builder {
+        Builder {
+            tp: SimpleHttpTransport::new(),
+            #[cfg(feature = "simple_https")]
+            tls: TlsOptions::default(),
+        }
+    }
 
     /// Sets the timeout after which requests will abort if they aren't finished.
     pub fn timeout(mut self, timeout: Duration) -> Self {
@@ -413,8 +789,41 @@ impl Builder {
         self
     }
 
+    /// Trusts the root certificates in `pem` for `https` URLs, instead of the Mozilla ones.
+    ///
+    /// Can be called several times to trust more certificates.
+    #[cfg(feature = "simple_https")]
+    pub fn tls_root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, Error> {
+        let roots = self.tls.roots.get_or_insert_with(RootCertStore::empty);
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            roots.add(cert?)?;
+        }
+        Ok(self)
+    }
+
+    /// Authenticates to the server with the certificate chain and private key in PEM format for
+    /// `https` URLs.
+    #[cfg(feature = "simple_https")]
+    pub fn tls_client_auth_pem(mut self, cert_chain: &[u8], key: &[u8]) -> Result<Self, Error> {
+        let cert_chain =
+            CertificateDer::pem_slice_iter(cert_chain).collect::<Result<Vec<_>, _>>()?;
+        let key = PrivateKeyDer::from_pem_slice(key)?;
+        // Checks the key matches the certificate so that `build` doesn't fail.
+        tls_config(Some(RootCertStore::empty()), Some((cert_chain.clone(), key.clone_key())))?;
+        self.tls.client_auth = Some((cert_chain, key));
+        Ok(self)
+    }
+
     /// Builds the final [`SimpleHttpTransport`].
-    pub fn build(self) -> SimpleHttpTransport { self.tp }
+    pub fn build(self) -> SimpleHttpTransport {
+        #[cfg(feature = "simple_https")]
+        if self.tls.roots.is_some() || self.tls.client_auth.is_some() {
+            let config = tls_config(self.tls.roots, self.tls.client_auth)
+                .expect("client certificate checked in tls_client_auth_pem");
+            return SimpleHttpTransport { tls_config: Some(Arc::new(config)), ..self.tp };
+        }
+        self.tp
+    }
 }
 
 impl Default for Builder {
@@ -497,7 +906,10 @@ pub enum Error {
         max: u64,
     },
     /// The server is replying with chunked encoding which is not supported.
+    #[deprecated(since = "TBD", note = "chunked responses are supported, this is never returned")]
     HttpResponseChunked,
+    /// The body of a response with chunked encoding was malformed.
+    HttpResponseBadChunk(String),
     /// Unexpected HTTP error code (non-200).
     HttpErrorCode(u16),
     /// Received EOF before getting as many bytes as were indicated by the content-length header.
@@ -509,6 +921,12 @@ pub enum Error {
     },
     /// JSON parsing error.
     Json(serde_json::Error),
+    /// TLS error, eg. an invalid certificate.
+    #[cfg(feature = "simple_https")]
+    Tls(rustls::Error),
+    /// Invalid PEM certificate or key.
+    #[cfg(feature = "simple_https")]
+    Pem(rustls::pki_types::pem::Error),
 }
 
 impl Error {
@@ -552,9 +970,17 @@ impl fmt::Display for Error {
                 )
             }
             Json(ref e) => write!(f, "JSON error: {}", e),
+            #[cfg(feature = "simple_https")]
+            Tls(ref e) => write!(f, "TLS error: {}", e),
+            #[cfg(feature = "simple_https")]
+            Pem(ref e) => write!(f, "invalid PEM: {}", e),
+            #[allow(deprecated)]
             HttpResponseChunked => {
                 write!(f, "The server replied with a chunked response which is not supported")
             }
+            HttpResponseBadChunk(ref line) => {
+                write!(f, "HTTP response had a malformed chunk at `{}`.", line)
+            }
         }
     }
 }
@@ -573,21 +999,52 @@ impl error::Error for Error {
             | HttpResponseContentLengthTooLarge { .. }
             | HttpErrorCode(_)
             | IncompleteResponse { .. }
-            | HttpResponseChunked => None,
+            | HttpResponseBadChunk(_) => None,
+            #[allow(deprecated)]
+            HttpResponseChunked => None,
             SocketError(ref e) => Some(e),
             Json(ref e) => Some(e),
+            #[cfg(feature = "simple_https")]
+            Tls(ref e) => Some(e),
+            #[cfg(feature = "simple_https")]
+            Pem(ref e) => Some(e),
         }
     }
 }
 
 impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self { Error::SocketError(e) }
+    fn from(e: io::Error) -> Self {
+        // The body readers return our errors wrapped in `io::Error`s.
+        match e.get_ref().map(|inner| inner.is::<Error>()) {
+            Some(true) => *e.into_inner().expect("has inner").downcast().expect("checked type"),
+            _ => Error::SocketError(e),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::SocketError(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self { Error::Json(e) }
 }
 
+#[cfg(feature = "simple_https")]
+impl From<rustls::Error> for Error {
+    fn from(e: rustls::Error) -> Self { Error::Tls(e) }
+}
+
+#[cfg(feature = "simple_https")]
+impl From<rustls::pki_types::pem::Error> for Error {
+    fn from(e: rustls::pki_types::pem::Error) -> Self { Error::Pem(e) }
+}
+
 impl From<Error> for crate::Error {
     fn from(e: Error) -> crate::Error {
         match e {
@@ -857,4 +1314,293 @@ mod tests {
         let expected = format!("localhost:{}", port);
         assert_eq!(server.join().unwrap().as_deref(), Some(expected.as_str()));
     }
+
+    /// Serves one connection per element of `connections`, answering its requests with the given
+    /// raw responses in order. Connections are left open until the server is joined.
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    fn serve_raw(
+        connections: Vec<Vec<&'static str>>,
+    ) -> (net::SocketAddr, std::thread::JoinHandle<()>) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut streams = vec![];
+            for responses in connections {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                for response in responses {
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some(len) = line.strip_prefix("Content-Length: ") {
+                            content_length = len.trim().parse().unwrap();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    reader.by_ref().take(content_length).read_to_end(&mut vec![]).unwrap();
+                    reader.get_mut().write_all(response.as_bytes()).unwrap();
+                }
+                streams.push(reader);
+            }
+        });
+        (addr, server)
+    }
+
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    #[test]
+    fn chunked_response() {
+        let (addr, server) = serve_raw(vec![vec![
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             10;ext=1\r\n{\"result\":42,\"er\r\n\
+             c\r\nror\":null,\"i\r\n\
+             6\r\nd\":1}\n\r\n\
+             0\r\nX-Trailer: yes\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             21\r\n{\"result\":43,\"error\":null,\"id\":2}\r\n0\r\n\r\n",
+        ]]);
+
+        let client = Client::simple_http(&addr.to_string(), None, None).unwrap();
+        for expected in [42, 43] {
+            let response = client.send_request(client.build_request("uptime", None)).unwrap();
+            assert_eq!(response.result::<u64>().unwrap(), expected);
+        }
+        server.join().unwrap();
+    }
+
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    #[test]
+    fn no_content_batch() {
+        let (addr, server) = serve_raw(vec![vec![
+            "HTTP/1.1 204 No Content\r\n\r\n",
+            "HTTP/1.1 204 No Content\r\n\r\n",
+        ]]);
+
+        let client = Client::simple_http(&addr.to_string(), None, None).unwrap();
+        let batch = [client.build_request("uptime", None), client.build_request("uptime", None)];
+        assert!(client.send_batch(&batch).unwrap().iter().all(Option::is_none));
+        let error = client.send_request(client.build_request("uptime", None)).unwrap_err();
+        assert!(matches!(error, crate::Error::Transport(_)), "{}", error);
+        server.join().unwrap();
+    }
+
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    #[test]
+    fn notification() {
+        let (addr, server) = serve_raw(vec![vec![
+            "HTTP/1.1 204 No Content\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 38\r\n\r\n\
+             {\"result\":null,\"error\":null,\"id\":null}",
+            "HTTP/1.1 200 OK\r\nContent-Length: 33\r\n\r\n{\"result\":42,\"error\":null,\"id\":1}",
+        ]]);
+
+        let client = Client::simple_http(&addr.to_string(), None, None).unwrap();
+        client.notify("ping", None).unwrap();
+        // A JSON-RPC 1.0 server answers notifications.
+        client.notify("ping", None).unwrap();
+        assert_eq!(client.call::<u64>("uptime", None).unwrap(), 42);
+        server.join().unwrap();
+    }
+
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    #[test]
+    fn connection_close_and_http_1_0() {
+        let body = r#"{"result":1,"error":null,"id":1}"#;
+        let (addr, server) = serve_raw(vec![
+            vec![Box::leak(
+                format!(
+                    "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .into_boxed_str(),
+            )],
+            vec![Box::leak(
+                format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+                    .into_boxed_str(),
+            )],
+            vec![Box::leak(
+                format!(
+                    "HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .into_boxed_str(),
+            )],
+        ]);
+
+        // The server leaves the connections open, reusing them would time out.
+        let tp =
+            Builder::new().url(&addr.to_string()).unwrap().timeout(Duration::from_secs(2)).build();
+        let client = Client::with_transport(tp);
+        for _ in 0..3 {
+            let response = client.send_request(client.build_request("uptime", None)).unwrap();
+            assert_eq!(response.result::<u64>().unwrap(), 1);
+        }
+        assert!(client.send_request(client.build_request("uptime", None)).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn response_head() {
+        let mut head = ResponseHead::from_status_line("HTTP/1.0 204 No Content\r\n").unwrap();
+        assert!(head.close && !head.has_body());
+        head.parse_header("Connection: Keep-Alive\r\n").unwrap();
+        head.parse_header("transfer-encoding: gzip, chunked\r\n").unwrap();
+        assert!(!head.close && head.chunked);
+        assert!(head.parse_header("Content-Length: x\r\n").is_err());
+        assert!(ResponseHead::from_status_line("HTTP/2.0 200 OK\r\n").is_err());
+
+        assert_eq!(parse_chunk_size("1a;name=value\r\n").unwrap(), 26);
+        assert!(parse_chunk_size("xyz\r\n").is_err());
+    }
+
+    /// Serves `requests` requests on a single TLS connection, requiring a client certificate if
+    /// `client_auth`.
+    #[cfg(all(feature = "simple_https", not(feature = "proxy")))]
+    fn serve_tls(client_auth: bool, requests: usize) -> (String, std::thread::JoinHandle<()>) {
+        use rustls::server::WebPkiClientVerifier;
+        use rustls::{ServerConfig, ServerConnection};
+
+        let ca = CertificateDer::from_pem_slice(include_bytes!("../../tests/data/ca.pem")).unwrap();
+        let cert =
+            CertificateDer::from_pem_slice(include_bytes!("../../tests/data/server.pem")).unwrap();
+        let key =
+            PrivateKeyDer::from_pem_slice(include_bytes!("../../tests/data/server.key")).unwrap();
+        let builder = ServerConfig::builder();
+        let builder = if client_auth {
+            let mut roots = RootCertStore::empty();
+            roots.add(ca).unwrap();
+            builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(Arc::new(roots)).build().unwrap(),
+            )
+        } else {
+            builder.with_no_client_auth()
+        };
+        let config = Arc::new(builder.with_single_cert(vec![cert], key).unwrap());
+
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(config).unwrap();
+            let mut reader = BufReader::new(StreamOwned::new(conn, stream));
+            for _ in 0..requests {
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).is_err() {
+                        // The client rejected the handshake.
+                        return;
+                    }
+                    if let Some(len) = line.strip_prefix("Content-Length: ") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                reader.by_ref().take(content_length).read_to_end(&mut vec![]).unwrap();
+                let body = r#"{"result":"tls","error":null,"id":1}"#;
+                let response =
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                reader.get_mut().flush().unwrap();
+            }
+        });
+        (url, server)
+    }
+
+    #[cfg(all(feature = "simple_https", not(feature = "proxy")))]
+    #[test]
+    fn https_custom_roots() {
+        let (url, server) = serve_tls(false, 2);
+        let tp = Builder::new()
+            .url(&url)
+            .unwrap()
+            .tls_root_certificates_pem(include_bytes!("../../tests/data/ca.pem"))
+            .unwrap()
+            .build();
+        let client = Client::with_transport(tp);
+        for _ in 0..2 {
+            let response = client.send_request(client.build_request("uptime", None)).unwrap();
+            assert_eq!(response.result::<String>().unwrap(), "tls");
+        }
+        server.join().unwrap();
+
+        // The test CA isn't trusted by default.
+        let (url, server) = serve_tls(false, 1);
+        let client = Client::simple_http(&url, None, None).unwrap();
+        assert!(client.send_request(client.build_request("uptime", None)).is_err());
+        server.join().unwrap();
+    }
+
+    #[cfg(all(feature = "simple_https", not(feature = "proxy")))]
+    #[test]
+    fn https_client_auth() {
+        let builder = |url: &str| {
+            Builder::new()
+                .url(url)
+                .unwrap()
+                .tls_root_certificates_pem(include_bytes!("../../tests/data/ca.pem"))
+                .unwrap()
+        };
+
+        let (url, server) = serve_tls(true, 1);
+        let tp = builder(&url)
+            .tls_client_auth_pem(
+                include_bytes!("../../tests/data/client.pem"),
+                include_bytes!("../../tests/data/client.key"),
+            )
+            .unwrap()
+            .build();
+        let client = Client::with_transport(tp);
+        let response = client.send_request(client.build_request("uptime", None)).unwrap();
+        assert_eq!(response.result::<String>().unwrap(), "tls");
+        server.join().unwrap();
+
+        let (url, server) = serve_tls(true, 1);
+        let client = Client::with_transport(builder(&url).build());
+        assert!(client.send_request(client.build_request("uptime", None)).is_err());
+        server.join().unwrap();
+
+        // The key doesn't match the certificate.
+        assert!(builder(&url)
+            .tls_client_auth_pem(
+                include_bytes!("../../tests/data/client.pem"),
+                include_bytes!("../../tests/data/server.key"),
+            )
+            .is_err());
+    }
+
+    #[cfg(all(not(feature = "proxy"), not(jsonrpc_fuzz)))]
+    #[test]
+    fn streaming_response() {
+        let body = format!(r#"{{"result":"{}","error":null,"id":1}}"#, "a".repeat(1000));
+        let response: &'static str = Box::leak(
+            format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                body.len(),
+                body
+            )
+            .into_boxed_str(),
+        );
+        let length: &'static str = Box::leak(
+            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+                .into_boxed_str(),
+        );
+        let (addr, server) = serve_raw(vec![vec![response, response], vec![length]]);
+
+        let client = Client::simple_http(&addr.to_string(), None, None).unwrap();
+        let result: String = client.call_streaming("getblock", None, 2000).unwrap();
+        assert_eq!(result.len(), 1000);
+        // The connection is reused after a streamed response, and closed when it's too large.
+        let error = client.call_streaming::<String>("getblock", None, 500).unwrap_err();
+        assert!(matches!(error, crate::Error::ResponseTooLarge(500)), "{:?}", error);
+        let error = client.call_streaming::<String>("getblock", None, 500).unwrap_err();
+        assert!(matches!(error, crate::Error::ResponseTooLarge(500)), "{:?}", error);
+        server.join().unwrap();
+    }
 }
@@ -0,0 +1,494 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements the [`crate::client_async::Transport`] trait with the same minimal HTTP
+//! round-tripper as [`crate::simple_http`], on top of [`tokio`].
+//!
+//! This can be used if minimal dependencies are a goal and asynchronous communication is needed.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::client_async::{BoxFuture, Transport};
+#[cfg(feature = "proxy")]
+use crate::http::simple_http::socks5_connect;
+use crate::http::simple_http::{
+    check_url, parse_chunk_size, Error, ResponseHead, FINAL_RESP_ALLOC,
+};
+use crate::http::DEFAULT_PORT;
+#[cfg(feature = "proxy")]
+use crate::http::DEFAULT_PROXY_PORT;
+use crate::{Request, Response};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Simple async HTTP transport that implements the necessary subset of HTTP for running a
+/// bitcoind RPC client.
+#[derive(Clone, Debug)]
+pub struct SimpleHttpTransport {
+    addr: SocketAddr,
+    host_header: String,
+    path: String,
+    timeout: Duration,
+    /// The value of the `Authorization` HTTP header.
+    basic_auth: Option<String>,
+    #[cfg(feature = "proxy")]
+    proxy_addr: SocketAddr,
+    #[cfg(feature = "proxy")]
+    proxy_auth: Option<(String, String)>,
+    sock: Arc<Mutex<Option<BufReader<TcpStream>>>>,
+}
+
+impl Default for SimpleHttpTransport {
+    fn default() -> Self {
+        SimpleHttpTransport {
+            addr: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+            host_header: format!("127.0.0.1:{}", DEFAULT_PORT),
+            path: "/".to_owned(),
+            timeout: DEFAULT_TIMEOUT,
+            basic_auth: None,
+            #[cfg(feature = "proxy")]
+            proxy_addr: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PROXY_PORT)),
+            #[cfg(feature = "proxy")]
+            proxy_auth: None,
+            sock: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl SimpleHttpTransport {
+    /// Constructs a new [`SimpleHttpTransport`] with default parameters.
+    pub fn new() -> Self { SimpleHttpTransport::default() }
+
+    /// Returns a builder for [`SimpleHttpTransport`].
+    pub fn builder() -> Builder { Builder::new() }
+
+    /// Replaces the URL of the transport.
+    pub fn set_url(&mut self, url: &str) -> Result<(), Error> {
+        let url = check_url(url)?;
+        self.addr = url.0;
+        self.host_header = url.1;
+        self.path = url.2;
+        Ok(())
+    }
+
+    /// Replaces only the path part of the URL.
+    pub fn set_url_path(&mut self, path: String) { self.path = path; }
+
+    async fn request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let mut sock = self.sock.lock().await;
+        // The socket is only put back once the response was read, so that dropping the future
+        // doesn't leave a half-read response on it.
+        let mut slot = sock.take();
+        let result =
+            match tokio::time::timeout(self.timeout, self.try_request(&mut slot, req)).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::SocketError(io::ErrorKind::TimedOut.into())),
+            };
+        if result.is_ok() {
+            *sock = slot;
+        }
+        result
+    }
+
+    #[cfg(feature = "proxy")]
+    async fn fresh_socket(&self) -> Result<TcpStream, Error> {
+        let (proxy, target, auth) = (self.proxy_addr, self.addr, self.proxy_auth.clone());
+        // The SOCKS5 handshake of the synchronous transport blocks, keep it off the runtime.
+        let stream = tokio::task::spawn_blocking(move || {
+            let auth = auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str()));
+            socks5_connect(proxy, target, auth)
+        })
+        .await
+        .map_err(io::Error::other)??;
+        stream.set_nonblocking(true)?;
+        Ok(TcpStream::from_std(stream)?)
+    }
+
+    #[cfg(not(feature = "proxy"))]
+    async fn fresh_socket(&self) -> Result<TcpStream, Error> {
+        Ok(TcpStream::connect(self.addr).await?)
+    }
+
+    async fn try_request<R>(
+        &self,
+        sock_slot: &mut Option<BufReader<TcpStream>>,
+        req: impl serde::Serialize,
+    ) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let fresh = sock_slot.is_none();
+        if fresh {
+            *sock_slot = Some(BufReader::new(self.fresh_socket().await?));
+        }
+        // In the immediately preceding block, we made sure that `sock` is non-`None`,
+        // so unwrapping here is fine.
+        let sock = sock_slot.as_mut().unwrap();
+
+        // Serialize the body first so we can set the Content-Length header.
+        let body = serde_json::to_vec(&req)?;
+
+        let mut request_bytes = Vec::new();
+        request_bytes.extend_from_slice(b"POST ");
+        request_bytes.extend_from_slice(self.path.as_bytes());
+        request_bytes.extend_from_slice(b" HTTP/1.1\r\n");
+        // Write headers
+        request_bytes.extend_from_slice(b"host: ");
+        request_bytes.extend_from_slice(self.host_header.as_bytes());
+        request_bytes.extend_from_slice(b"\r\n");
+        request_bytes.extend_from_slice(b"Content-Type: application/json\r\n");
+        request_bytes.extend_from_slice(b"Content-Length: ");
+        request_bytes.extend_from_slice(body.len().to_string().as_bytes());
+        request_bytes.extend_from_slice(b"\r\n");
+        if let Some(ref auth) = self.basic_auth {
+            request_bytes.extend_from_slice(b"Authorization: ");
+            request_bytes.extend_from_slice(auth.as_bytes());
+            request_bytes.extend_from_slice(b"\r\n");
+        }
+        // Write body
+        request_bytes.extend_from_slice(b"\r\n");
+        request_bytes.extend_from_slice(&body);
+
+        // Send HTTP request
+        let write_success = sock.get_mut().write_all(&request_bytes).await.is_ok()
+            && sock.get_mut().flush().await.is_ok();
+
+        // This indicates the socket is broken so let's retry the send once with a fresh socket
+        if !write_success {
+            *sock = BufReader::new(self.fresh_socket().await?);
+            sock.get_mut().write_all(&request_bytes).await?;
+            sock.get_mut().flush().await?;
+        }
+
+        // Parse first HTTP response header line
+        let mut header_buf = String::new();
+        let read_success = sock.read_line(&mut header_buf).await.is_ok();
+
+        // This is another possible indication that a reused socket is broken so let's retry the
+        // send once with a fresh socket IF the write attempt has not already experienced a failure
+        if (!read_success || header_buf.is_empty()) && write_success && !fresh {
+            *sock = BufReader::new(self.fresh_socket().await?);
+            sock.get_mut().write_all(&request_bytes).await?;
+            sock.get_mut().flush().await?;
+
+            header_buf.clear();
+            sock.read_line(&mut header_buf).await?;
+        }
+
+        let mut head = ResponseHead::from_status_line(&header_buf)?;
+
+        // Parse response header fields
+        loop {
+            header_buf.clear();
+            if sock.read_line(&mut header_buf).await? == 0 {
+                return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+            }
+            if header_buf == "\r\n" {
+                break;
+            }
+            head.parse_header(&header_buf)?;
+        }
+
+        if head.status == 401 {
+            // There is no body in a 401 response, so don't try to read it
+            return Err(Error::HttpErrorCode(head.status));
+        }
+
+        let body = if !head.has_body() {
+            Vec::new()
+        } else if head.chunked {
+            read_chunked(sock).await?
+        } else {
+            match head.content_length {
+                // Without content-length header the body is delimited by the server closing the
+                // connection.
+                None => {
+                    head.close = true;
+                    let mut body = Vec::new();
+                    (&mut *sock).take(FINAL_RESP_ALLOC).read_to_end(&mut body).await?;
+                    body
+                }
+                Some(n) if n > FINAL_RESP_ALLOC => {
+                    return Err(Error::HttpResponseContentLengthTooLarge {
+                        length: n,
+                        max: FINAL_RESP_ALLOC,
+                    });
+                }
+                Some(n) => {
+                    let mut body = Vec::new();
+                    let n_read = (&mut *sock).take(n).read_to_end(&mut body).await? as u64;
+                    if n_read < n {
+                        return Err(Error::IncompleteResponse { content_length: n, n_read });
+                    }
+                    body
+                }
+            }
+        };
+
+        // The server won't accept another request on this connection.
+        if head.close {
+            *sock_slot = None;
+        }
+
+        // Attempt to parse the response. Don't check the HTTP error code until
+        // after parsing, since Bitcoin Core will often return a descriptive JSON
+        // error structure which is more useful than the error code.
+        // A `204 No Content` response, to notifications, parses as `null`.
+        let body: &[u8] = if head.status == 204 { b"null" } else { &body };
+        match serde_json::from_slice(body) {
+            Ok(s) => Ok(s),
+            Err(e) =>
+                if head.status != 200 {
+                    Err(Error::HttpErrorCode(head.status))
+                } else {
+                    Err(e.into())
+                },
+        }
+    }
+}
+
+/// Reads a body sent with the chunked transfer encoding, up to [`FINAL_RESP_ALLOC`] bytes.
+async fn read_chunked(reader: &mut BufReader<TcpStream>) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let size = parse_chunk_size(&line)?;
+        if size == 0 {
+            break;
+        }
+        let length = body.len() as u64 + size;
+        if length > FINAL_RESP_ALLOC {
+            return Err(Error::HttpResponseContentLengthTooLarge { length, max: FINAL_RESP_ALLOC });
+        }
+        if (&mut *reader).take(size).read_to_end(&mut body).await? as u64 != size {
+            return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        line.clear();
+        reader.read_line(&mut line).await?;
+        if line != "\r\n" {
+            return Err(Error::HttpResponseBadChunk(line.trim().into()));
+        }
+    }
+    // Skip the trailer fields.
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Error::SocketError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        if line == "\r\n" {
+            return Ok(body);
+        }
+    }
+}
+
+impl Transport for SimpleHttpTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        Box::pin(async move { Ok(self.request(req).await?) })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        // A JSON-RPC 2.0 server answers a batch of notifications with no content.
+        Box::pin(async move {
+            Ok(self.request::<Option<Vec<Response>>>(reqs).await?.unwrap_or_default())
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        // The response has no content, or is ignored under JSON-RPC 1.0.
+        Box::pin(async move {
+            self.request::<Option<serde::de::IgnoredAny>>(req).await?;
+            Ok(())
+        })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.addr.ip(), self.addr.port(), self.path)
+    }
+}
+
+/// Builder for simple bitcoind [`SimpleHttpTransport`].
+#[derive(Clone, Debug)]
+pub struct Builder {
+    tp: SimpleHttpTransport,
+}
+
+impl Builder {
+    /// Constructs a new [`Builder`] with default configuration.
+    pub fn new() -> Builder { Builder { tp: SimpleHttpTransport::new() } }
+
+    /// Sets the timeout after which requests will abort if they aren't finished.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.tp.timeout = timeout;
+        self
+    }
+
+    /// Sets the URL of the server to the transport.
+    pub fn url(mut self, url: &str) -> Result<Self, Error> {
+        self.tp.set_url(url)?;
+        Ok(self)
+    }
+
+    /// Adds authentication information to the transport.
+    pub fn auth<S: AsRef<str>>(mut self, user: S, pass: Option<S>) -> Self {
+        let mut auth = user.as_ref().to_owned();
+        auth.push(':');
+        if let Some(ref pass) = pass {
+            auth.push_str(pass.as_ref());
+        }
+        self.tp.basic_auth = Some(format!("Basic {}", &BASE64.encode(auth.as_bytes())));
+        self
+    }
+
+    /// Adds authentication information to the transport using a cookie string ('user:pass').
+    pub fn cookie_auth<S: AsRef<str>>(mut self, cookie: S) -> Self {
+        self.tp.basic_auth = Some(format!("Basic {}", &BASE64.encode(cookie.as_ref().as_bytes())));
+        self
+    }
+
+    /// Adds proxy address to the transport for SOCKS5 proxy.
+    #[cfg(feature = "proxy")]
+    pub fn proxy_addr<S: AsRef<str>>(mut self, proxy_addr: S) -> Result<Self, Error> {
+        // We don't expect path in proxy address.
+        self.tp.proxy_addr = check_url(proxy_addr.as_ref())?.0;
+        Ok(self)
+    }
+
+    /// Adds optional proxy authentication as ('username', 'password').
+    #[cfg(feature = "proxy")]
+    pub fn proxy_auth<S: AsRef<str>>(mut self, user: S, pass: S) -> Self {
+        self.tp.proxy_auth =
+            Some((user, pass)).map(|(u, p)| (u.as_ref().to_string(), p.as_ref().to_string()));
+        self
+    }
+
+    /// Builds the final [`SimpleHttpTransport`].
+    pub fn build(self) -> SimpleHttpTransport { self.tp }
+}
+
+impl Default for Builder {
+    fn default() -> Self { Builder::new() }
+}
+
+impl crate::client_async::Client {
+    /// Creates a new async JSON-RPC client using a bare-minimum HTTP transport.
+    pub fn simple_http(
+        url: &str,
+        user: Option<String>,
+        pass: Option<String>,
+    ) -> Result<crate::client_async::Client, Error> {
+        let mut builder = Builder::new().url(url)?;
+        if let Some(user) = user {
+            builder = builder.auth(user, pass);
+        }
+        Ok(crate::client_async::Client::with_transport(builder.build()))
+    }
+}
+
+#[cfg(all(test, not(feature = "proxy")))]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::client_async::Client;
+
+    /// Serves `responses` one after the other on the same connection, returns the requests.
+    async fn serve(listener: TcpListener, responses: Vec<&'static str>) -> Vec<String> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        let mut requests = vec![];
+        for response in responses {
+            let mut content_length = 0;
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if let Some(len) = line.strip_prefix("Content-Length: ") {
+                    content_length = len.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            stream.read_exact(&mut body).await.unwrap();
+            request.push_str(std::str::from_utf8(&body).unwrap());
+            requests.push(request);
+            stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+        }
+        requests
+    }
+
+    #[tokio::test]
+    async fn request_and_reuse_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/wallet/w", listener.local_addr().unwrap());
+        let body = r#"{"result":42,"error":null,"id":1}"#;
+        let response: &'static str = Box::leak(
+            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+                .into_boxed_str(),
+        );
+        let server = tokio::spawn(serve(listener, vec![response, response]));
+
+        let client = Client::simple_http(&url, Some("user".into()), Some("pass".into())).unwrap();
+        for _ in 0..2 {
+            let req = client.build_request("getblockcount", None);
+            let resp = client.send_request(req).await.unwrap();
+            assert_eq!(resp.result::<u64>().unwrap(), 42);
+        }
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /wallet/w HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+    }
+
+    #[tokio::test]
+    async fn chunked_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        10\r\n{\"result\":42,\"er\r\n11\r\nror\":null,\"id\":1}\r\n0\r\n\r\n";
+        let server = tokio::spawn(serve(listener, vec![response, response]));
+
+        let client = Client::simple_http(&url, None, None).unwrap();
+        for _ in 0..2 {
+            let resp = client.send_request(client.build_request("uptime", None)).await.unwrap();
+            assert_eq!(resp.result::<u64>().unwrap(), 42);
+        }
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let tp = Builder::new().url(&url).unwrap().timeout(Duration::from_millis(50)).build();
+        let client = Client::with_transport(tp);
+        let req = client.build_request("uptime", None);
+        let err = client.send_request(req).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+    }
+}
@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Interceptors
+//!
+//! Hooks seeing each request sent by a [`crate::Client`], or a `client_async::Client`, and its
+//! response with the time it took. They are the place for metrics, eg. latency per method,
+//! tracing spans and logs, correlated by the request `id`.
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::time::Duration;
+//!
+//! use jsonrpc::interceptor::{self, Interceptor};
+//! use jsonrpc::{Error, Request, Response};
+//!
+//! #[derive(Default)]
+//! struct Metrics {
+//!     errors: AtomicUsize,
+//! }
+//!
+//! impl Interceptor for Metrics {
+//!     fn on_request(&self, request: &Request) {
+//!         let params = if interceptor::is_sensitive(request.method) {
+//!             "<redacted>"
+//!         } else {
+//!             request.params.map_or("[]", |params| params.get())
+//!         };
+//!         println!("-> {} {} {}", request.id, request.method, params);
+//!     }
+//!
+//!     fn on_response(&self, request: &Request, response: Result<&Response, &Error>, elapsed: Duration) {
+//!         if response.is_err() {
+//!             self.errors.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!         println!("<- {} {} in {:?}", request.id, request.method, elapsed);
+//!     }
+//! }
+//!
+//! # #[cfg(feature = "simple_http")] {
+//! let client = jsonrpc::Client::simple_http("localhost:8332", None, None)
+//!     .unwrap()
+//!     .with_interceptor(Metrics::default());
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::{Error, Request, Response};
+
+/// Sees the requests sent by a client and their responses.
+///
+/// Interceptors see requests in the order they were added to the client, and responses in the
+/// reverse order. Each request of a batch is seen individually.
+pub trait Interceptor: Send + Sync + 'static {
+    /// Called before `request` is sent.
+    fn on_request(&self, _request: &Request) {}
+
+    /// Called with the response to `request`, or the error sending it, `elapsed` after it was
+    /// sent.
+    ///
+    /// Not called for the requests of a batch the server didn't respond to. The response to a
+    /// notification is empty, and the one to
+    /// [`Client::call_streaming`](crate::Client::call_streaming) doesn't hold the result, which
+    /// was deserialized as it was read.
+    fn on_response(
+        &self,
+        _request: &Request,
+        _response: Result<&Response, &Error>,
+        _elapsed: Duration,
+    ) {
+    }
+}
+
+/// Methods of Bitcoin Core whose parameters contain secrets, eg. passphrases or private keys.
+pub const SENSITIVE_METHODS: &[&str] = &[
+    "createwallet",
+    "dumpprivkey",
+    "encryptwallet",
+    "importdescriptors",
+    "importmulti",
+    "importprivkey",
+    "sethdseed",
+    "signmessagewithprivkey",
+    "signrawtransactionwithkey",
+    "walletpassphrase",
+    "walletpassphrasechange",
+];
+
+/// Returns whether the parameters of `method` contain secrets and shouldn't be logged.
+pub fn is_sensitive(method: &str) -> bool { SENSITIVE_METHODS.contains(&method) }
+
+/// The interceptors of a client.
+#[derive(Default)]
+pub(crate) struct Chain(Vec<Box<dyn Interceptor>>);
+
+impl Chain {
+    pub(crate) fn push(&mut self, interceptor: Box<dyn Interceptor>) { self.0.push(interceptor) }
+
+    pub(crate) fn on_request(&self, request: &Request) {
+        for interceptor in &self.0 {
+            interceptor.on_request(request);
+        }
+    }
+
+    pub(crate) fn on_response(
+        &self,
+        request: &Request,
+        response: Result<&Response, &Error>,
+        elapsed: Duration,
+    ) {
+        for interceptor in self.0.iter().rev() {
+            interceptor.on_response(request, response, elapsed);
+        }
+    }
+
+    /// Calls [`Chain::on_response`] for each request of a batch.
+    pub(crate) fn on_batch_response(
+        &self,
+        requests: &[Request],
+        responses: &Result<Vec<Option<Response>>, Error>,
+        elapsed: Duration,
+    ) {
+        match responses {
+            Ok(responses) =>
+                for (request, response) in requests.iter().zip(responses) {
+                    if let Some(response) = response {
+                        self.on_response(request, Ok(response), elapsed);
+                    }
+                },
+            Err(e) =>
+                for request in requests {
+                    self.on_response(request, Err(e), elapsed);
+                },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::client::Transport;
+    use crate::Client;
+
+    struct EchoTransport;
+    impl Transport for EchoTransport {
+        fn send_request(&self, req: Request) -> Result<Response, Error> {
+            if req.method == "fail" {
+                return Err(Error::NonceMismatch);
+            }
+            Ok(Response { result: None, error: None, id: req.id, jsonrpc: Some("2.0".into()) })
+        }
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, Error> {
+            reqs.iter().map(|req| self.send_request(req.clone())).collect()
+        }
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    struct Recorder(&'static str, Arc<Mutex<Vec<String>>>);
+    impl Interceptor for Recorder {
+        fn on_request(&self, request: &Request) {
+            self.1.lock().unwrap().push(format!("{} -> {} {}", self.0, request.method, request.id));
+        }
+        fn on_response(&self, request: &Request, response: Result<&Response, &Error>, _: Duration) {
+            let ok = if response.is_ok() { "ok" } else { "err" };
+            self.1.lock().unwrap().push(format!("{} <- {} {}", self.0, request.method, ok));
+        }
+    }
+
+    #[test]
+    fn chain_order() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let client = Client::with_transport(EchoTransport)
+            .with_interceptor(Recorder("a", Arc::clone(&log)))
+            .with_interceptor(Recorder("b", Arc::clone(&log)));
+
+        client.call::<()>("getinfo", None).unwrap();
+        client.call::<()>("fail", None).unwrap_err();
+        let batch = [client.build_request("x", None), client.build_request("y", None)];
+        client.send_batch(&batch).unwrap();
+        client.notify("ping", None).unwrap();
+        client.call_streaming::<()>("stream", None, 1000).unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "a -> getinfo 1",
+                "b -> getinfo 1",
+                "b <- getinfo ok",
+                "a <- getinfo ok",
+                "a -> fail 2",
+                "b -> fail 2",
+                "b <- fail err",
+                "a <- fail err",
+                "a -> x 3",
+                "b -> x 3",
+                "a -> y 4",
+                "b -> y 4",
+                "b <- x ok",
+                "a <- x ok",
+                "b <- y ok",
+                "a <- y ok",
+                "a -> ping null",
+                "b -> ping null",
+                "b <- ping ok",
+                "a <- ping ok",
+                "a -> stream 5",
+                "b -> stream 5",
+                "b <- stream ok",
+                "a <- stream ok",
+            ]
+        );
+    }
+
+    #[test]
+    fn sensitive() {
+        assert!(is_sensitive("walletpassphrase"));
+        assert!(!is_sensitive("getblockcount"));
+    }
+}
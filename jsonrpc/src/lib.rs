@@ -20,11 +20,15 @@ pub extern crate base64;
 #[cfg(feature = "bitreq")]
 pub extern crate bitreq;
 
+#[cfg(feature = "cassette")]
+pub mod cassette;
 pub mod client;
 #[cfg(feature = "client_async")]
 pub mod client_async;
 pub mod error;
 pub mod http;
+pub mod interceptor;
+pub mod retry;
 
 #[cfg(feature = "bitreq_http")]
 pub use http::bitreq_http;
@@ -32,13 +36,30 @@ pub use http::bitreq_http;
 pub use http::bitreq_http_async;
 #[cfg(feature = "simple_http")]
 pub use http::simple_http;
+#[cfg(feature = "simple_http_async")]
+pub use http::simple_http_async;
+
+#[cfg(feature = "mux")]
+pub mod mux;
 
 #[cfg(feature = "simple_tcp")]
 pub mod simple_tcp;
 
+#[cfg(feature = "simple_tcp_async")]
+pub mod simple_tcp_async;
+
 #[cfg(all(feature = "simple_uds", not(windows)))]
 pub mod simple_uds;
 
+#[cfg(all(feature = "simple_uds_async", not(windows)))]
+pub mod simple_uds_async;
+
+#[cfg(any(feature = "simple_tcp_async", all(feature = "simple_uds_async", not(windows))))]
+mod stream_async;
+
+#[cfg(feature = "server")]
+pub mod server;
+
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 
@@ -66,19 +87,104 @@ pub fn arg<T: serde::Serialize>(arg: T) -> Box<RawValue> {
     }
 }
 
+/// The JSON-RPC dialect spoken by a [`Client`](crate::Client).
+///
+/// Since version 28.0, Bitcoin Core follows the JSON-RPC 2.0 specification for requests with a
+/// `"jsonrpc": "2.0"` field: errors are returned with HTTP status 200, notifications get no
+/// response and responses have either a `result` or an `error`. Requests without the field get
+/// the legacy JSON-RPC 1.0 behaviour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    /// JSON-RPC 1.0: requests have no `jsonrpc` field and responses must not have one other
+    /// than `"1.0"`.
+    V1,
+    /// JSON-RPC 2.0, leniently: requests have a `"jsonrpc": "2.0"` field and responses must not
+    /// have one other than `"2.0"`.
+    #[default]
+    V2,
+    /// JSON-RPC 2.0, strictly: requests have a `"jsonrpc": "2.0"` field, responses must have it
+    /// too and can't have both a `result` and an `error`.
+    V2Strict,
+}
+
+impl Dialect {
+    /// Returns the `jsonrpc` field of the requests of this dialect.
+    pub fn request_version(self) -> Option<&'static str> {
+        match self {
+            Dialect::V1 => None,
+            Dialect::V2 | Dialect::V2Strict => Some("2.0"),
+        }
+    }
+
+    /// Checks that `response` follows the rules of this dialect.
+    pub fn check_response(self, response: &Response) -> Result<(), Error> {
+        self.check(response.jsonrpc.as_deref(), response.result.is_some(), response.error.is_some())
+    }
+
+    /// Checks the `jsonrpc` field of a response and whether it has a non-null `result` and
+    /// `error`.
+    pub(crate) fn check(
+        self,
+        jsonrpc: Option<&str>,
+        has_result: bool,
+        has_error: bool,
+    ) -> Result<(), Error> {
+        let version_ok = matches!(
+            (self, jsonrpc),
+            (Dialect::V1, None | Some("1.0"))
+                | (Dialect::V2, None | Some("2.0"))
+                | (Dialect::V2Strict, Some("2.0"))
+        );
+        if !version_ok {
+            return Err(Error::VersionMismatch);
+        }
+        if self == Dialect::V2Strict && has_result && has_error {
+            return Err(Error::ResultAndError);
+        }
+        Ok(())
+    }
+}
+
 /// A JSONRPC request object.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone)]
 pub struct Request<'a> {
     /// The name of the RPC call.
     pub method: &'a str,
     /// Parameters to the RPC call.
     pub params: Option<&'a RawValue>,
     /// Identifier for this request, which should appear in the response.
+    ///
+    /// Requests with a null id are notifications, which get no response. They are sent without
+    /// id, or with a null one under [`Dialect::V1`].
     pub id: serde_json::Value,
-    /// jsonrpc field, MUST be "2.0".
+    /// jsonrpc field, "2.0" or [`None`], see [`Dialect`].
     pub jsonrpc: Option<&'a str>,
 }
 
+impl Request<'_> {
+    /// Returns whether this request is a notification, ie. has no id and gets no response.
+    pub fn is_notification(&self) -> bool { self.id.is_null() }
+}
+
+impl Serialize for Request<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        // JSON-RPC 1.0 notifications have a null id, JSON-RPC 2.0 ones none.
+        let with_id = !self.id.is_null() || self.jsonrpc.is_none();
+        let mut request = serializer.serialize_struct("Request", 3 + usize::from(with_id))?;
+        request.serialize_field("method", self.method)?;
+        request.serialize_field("params", &self.params)?;
+        if with_id {
+            request.serialize_field("id", &self.id)?;
+        } else {
+            request.skip_field("id")?;
+        }
+        request.serialize_field("jsonrpc", &self.jsonrpc)?;
+        request.end()
+    }
+}
+
 /// A JSONRPC response object.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Response {
@@ -88,7 +194,7 @@ pub struct Response {
     pub error: Option<error::RpcError>,
     /// Identifier for this response, which should match that of the request.
     pub id: serde_json::Value,
-    /// jsonrpc field, MUST be "2.0".
+    /// jsonrpc field, see [`Dialect`].
     pub jsonrpc: Option<String>,
 }
 
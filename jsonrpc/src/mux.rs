@@ -0,0 +1,449 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements an asynchronous transport multiplexing requests over a single
+//! persistent TCP, or Unix Domain Socket, connection.
+//!
+//! Requests and responses are line-delimited JSON, as spoken by Electrum servers. Requests are
+//! written as soon as they are sent, without waiting for the responses to the previous ones,
+//! and responses are matched to them by id in whatever order they come. Requests in flight must
+//! thus have distinct ids, as built by [`Client::build_request`](crate::client_async::Client).
+//!
+//! Messages from the server which don't answer a request in flight, eg. the notifications of
+//! Electrum subscriptions, are ignored. The connection is reopened by the next request after it
+//! was closed, failing the requests in flight with [`Error::Disconnected`].
+
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{error, fmt, io, net, time};
+
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf,
+};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::client_async::{BoxFuture, Transport};
+use crate::{Request, Response};
+
+/// A stream the transport can run over.
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin + 'static> Stream for S {}
+
+/// Where to connect to.
+#[derive(Debug, Clone)]
+enum Target {
+    Tcp(net::SocketAddr),
+    #[cfg(unix)]
+    Uds(PathBuf),
+}
+
+/// Asynchronous transport pipelining requests over one connection.
+///
+/// Clones share the connection.
+#[derive(Clone)]
+pub struct MuxTransport {
+    target: Target,
+    /// The timeout of a whole request, including connecting.
+    pub timeout: Option<time::Duration>,
+    conn: Arc<tokio::sync::Mutex<Option<Connection>>>,
+}
+
+impl MuxTransport {
+    /// Creates a new [`MuxTransport`] to the TCP socket `addr`, without timeouts.
+    pub fn tcp(addr: net::SocketAddr) -> MuxTransport { MuxTransport::new(Target::Tcp(addr)) }
+
+    /// Creates a new [`MuxTransport`] to the Unix Domain Socket at `sockpath`, without timeouts.
+    #[cfg(unix)]
+    pub fn uds<P: AsRef<std::path::Path>>(sockpath: P) -> MuxTransport {
+        MuxTransport::new(Target::Uds(sockpath.as_ref().to_path_buf()))
+    }
+
+    fn new(target: Target) -> MuxTransport {
+        MuxTransport { target, timeout: None, conn: Arc::new(tokio::sync::Mutex::new(None)) }
+    }
+
+    async fn connect(&self) -> io::Result<Connection> {
+        let stream: Box<dyn Stream> = match self.target {
+            Target::Tcp(addr) => Box::new(tokio::net::TcpStream::connect(addr).await?),
+            #[cfg(unix)]
+            Target::Uds(ref path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        };
+        let (reader, writer) = tokio::io::split(stream);
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let reader = tokio::spawn(read_responses(reader, Arc::clone(&pending)));
+        Ok(Connection { writer, pending, reader })
+    }
+
+    /// Writes `req` on the connection and returns the response to the requests with `ids`, or
+    /// nothing if there are none.
+    async fn request(
+        &self,
+        req: impl serde::Serialize,
+        ids: Vec<String>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut line = serde_json::to_vec(&req)?;
+        line.push(b'\n');
+        let exchange = async {
+            let (waiting, rx) = {
+                let mut conn = self.conn.lock().await;
+                if conn.as_ref().map_or(true, Connection::is_closed) {
+                    *conn = Some(self.connect().await?);
+                }
+                // In the immediately preceding block, we made sure that `conn` is non-`None`,
+                // so unwrapping here is fine.
+                let open = conn.as_mut().unwrap();
+                let waiting = match ids.first() {
+                    Some(id) => {
+                        let rx = open.pending().register(&ids)?;
+                        Some((Waiting { pending: Arc::clone(&open.pending), id: id.clone() }, rx))
+                    }
+                    None => None,
+                };
+                let written = match open.writer.write_all(&line).await {
+                    Ok(()) => open.writer.flush().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = written {
+                    *conn = None;
+                    return Err(e.into());
+                }
+                match waiting {
+                    Some((waiting, rx)) => (waiting, rx),
+                    None => return Ok(vec![]),
+                }
+            };
+            let response = rx.await.map_err(|_| Error::Disconnected);
+            drop(waiting);
+            response
+        };
+        match self.timeout {
+            Some(timeout) =>
+                tokio::time::timeout(timeout, exchange).await.unwrap_or(Err(Error::Timeout)),
+            None => exchange.await,
+        }
+    }
+}
+
+impl Transport for MuxTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        Box::pin(async move {
+            let line = self.request(&req, vec![req.id.to_string()]).await?;
+            Ok(serde_json::from_slice(&line)?)
+        })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        Box::pin(async move {
+            let ids =
+                reqs.iter().filter(|req| !req.is_notification()).map(|req| req.id.to_string());
+            let ids = ids.collect::<Vec<_>>();
+            if ids.is_empty() {
+                self.request(reqs, ids).await?;
+                return Ok(vec![]);
+            }
+            let line = self.request(reqs, ids).await?;
+            Ok(serde_json::from_slice(&line)?)
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        Box::pin(async move {
+            self.request(&req, vec![]).await?;
+            Ok(())
+        })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            Target::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Target::Uds(ref path) => write!(f, "{}", path.to_string_lossy()),
+        }
+    }
+}
+
+impl fmt::Debug for MuxTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "jsonrpc::mux::MuxTransport(")?;
+        self.fmt_target(f)?;
+        write!(f, ")")
+    }
+}
+
+/// An open connection and the requests in flight on it.
+struct Connection {
+    writer: WriteHalf<Box<dyn Stream>>,
+    pending: Arc<Mutex<Pending>>,
+    reader: JoinHandle<()>,
+}
+
+impl Connection {
+    fn pending(&self) -> std::sync::MutexGuard<'_, Pending> {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        self.pending.lock().expect("poisoned mutex")
+    }
+
+    fn is_closed(&self) -> bool { self.pending().closed }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.pending().close();
+    }
+}
+
+/// The requests waiting for a response on a connection.
+#[derive(Default)]
+struct Pending {
+    /// The key of the waiter of each id in flight.
+    ids: HashMap<String, u64>,
+    waiters: HashMap<u64, Waiter>,
+    next_key: u64,
+    closed: bool,
+}
+
+/// Waits for the response to the requests with `ids`, a single one or a batch.
+struct Waiter {
+    ids: Vec<String>,
+    tx: oneshot::Sender<Vec<u8>>,
+}
+
+impl Pending {
+    fn register(&mut self, ids: &[String]) -> Result<oneshot::Receiver<Vec<u8>>, Error> {
+        if let Some(id) = ids.iter().find(|id| self.ids.contains_key(*id)) {
+            return Err(Error::DuplicateId(id.clone()));
+        }
+        let (tx, rx) = oneshot::channel();
+        let key = self.next_key;
+        self.next_key += 1;
+        for id in ids {
+            self.ids.insert(id.clone(), key);
+        }
+        self.waiters.insert(key, Waiter { ids: ids.to_vec(), tx });
+        Ok(rx)
+    }
+
+    /// Removes the waiter of the request with `id`, and of the rest of its batch.
+    fn remove(&mut self, id: &str) -> Option<Waiter> {
+        let key = self.ids.get(id)?;
+        let waiter = self.waiters.remove(key)?;
+        for id in &waiter.ids {
+            self.ids.remove(id);
+        }
+        Some(waiter)
+    }
+
+    /// Passes `line` to the waiter of the response it holds, if any.
+    fn dispatch(&mut self, line: Vec<u8>) {
+        #[derive(Deserialize)]
+        struct Id {
+            #[serde(default)]
+            id: Value,
+        }
+
+        let id = match line.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'[') => serde_json::from_slice::<Vec<Id>>(&line)
+                .ok()
+                .and_then(|resps| resps.into_iter().find(|resp| !resp.id.is_null())),
+            _ => serde_json::from_slice::<Id>(&line).ok(),
+        };
+        if let Some(waiter) = id.and_then(|id| self.remove(&id.id.to_string())) {
+            // The receiver is gone if the request timed out.
+            let _ = waiter.tx.send(line);
+        }
+    }
+
+    /// Fails the requests in flight and marks the connection closed.
+    fn close(&mut self) {
+        self.closed = true;
+        self.ids.clear();
+        self.waiters.clear();
+    }
+}
+
+/// Forgets a request in flight when done waiting for its response, eg. on timeout.
+struct Waiting {
+    pending: Arc<Mutex<Pending>>,
+    id: String,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        self.pending.lock().expect("poisoned mutex").remove(&self.id);
+    }
+}
+
+/// Reads the lines sent by the server and passes them to the requests they answer, until the
+/// connection is closed.
+async fn read_responses(reader: ReadHalf<Box<dyn Stream>>, pending: Arc<Mutex<Pending>>) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            // No part of this codebase should panic, so unwrapping a mutex lock is fine
+            Ok(_) => pending.lock().expect("poisoned mutex").dispatch(line),
+        }
+    }
+    pending.lock().expect("poisoned mutex").close();
+}
+
+/// Error that can occur while using the multiplexing transport.
+#[derive(Debug)]
+pub enum Error {
+    /// An error occurred on the socket layer.
+    SocketError(io::Error),
+    /// We didn't receive a complete response till the deadline ran out.
+    Timeout,
+    /// JSON parsing error.
+    Json(serde_json::Error),
+    /// The connection was closed before the response was received.
+    Disconnected,
+    /// A request with the same id is already in flight.
+    DuplicateId(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        use Error::*;
+
+        match *self {
+            SocketError(ref e) => write!(f, "couldn't connect to host: {}", e),
+            Timeout => f.write_str("didn't receive response data in time, timed out."),
+            Json(ref e) => write!(f, "JSON error: {}", e),
+            Disconnected => f.write_str("connection closed before the response was received"),
+            DuplicateId(ref id) => write!(f, "a request with id {} is already in flight", id),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use self::Error::*;
+
+        match *self {
+            SocketError(ref e) => Some(e),
+            Timeout | Disconnected | DuplicateId(_) => None,
+            Json(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self { Error::SocketError(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Json(e) }
+}
+
+impl From<Error> for crate::Error {
+    fn from(e: Error) -> crate::Error {
+        match e {
+            Error::Json(e) => crate::Error::Json(e),
+            e => crate::Error::Transport(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::client_async::Client;
+
+    /// Reads `count` requests, then answers them in reverse order, with a subscription
+    /// notification first.
+    async fn serve_reversed(listener: TcpListener, count: usize) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut requests = vec![];
+        while requests.len() < count {
+            let line = lines.next_line().await.unwrap().unwrap();
+            requests.push(serde_json::from_str::<Value>(&line).unwrap());
+        }
+        let mut out = String::from("{\"method\":\"blockchain.headers.subscribe\",\"params\":[]}\n");
+        for req in requests.iter().rev() {
+            let resp = match req {
+                Value::Array(batch) => Value::Array(
+                    batch
+                        .iter()
+                        .map(|req| serde_json::json!({"result": req["method"], "id": req["id"]}))
+                        .collect(),
+                ),
+                req => serde_json::json!({"result": req["method"], "id": req["id"]}),
+            };
+            out.push_str(&resp.to_string());
+            out.push('\n');
+        }
+        writer.write_all(out.as_bytes()).await.unwrap();
+        // Wait for the client to close the connection.
+        let _ = lines.into_inner().read_to_end(&mut vec![]).await;
+    }
+
+    #[tokio::test]
+    async fn pipelined_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_reversed(listener, 3));
+
+        let client = Client::with_transport(MuxTransport::tcp(addr));
+        let batch = [client.build_request("b1", None), client.build_request("b2", None)];
+        let (first, second, batch) = tokio::join!(
+            client.call::<String>("first", None),
+            client.call::<String>("second", None),
+            client.send_batch(&batch),
+        );
+        assert_eq!(first.unwrap(), "first");
+        assert_eq!(second.unwrap(), "second");
+        let batch = batch.unwrap();
+        assert_eq!(batch[0].as_ref().unwrap().result::<String>().unwrap(), "b1");
+        assert_eq!(batch[1].as_ref().unwrap().result::<String>().unwrap(), "b2");
+
+        drop(client);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn disconnect_and_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // Close the first connection without answering, never answer on the second.
+            let (stream, _) = listener.accept().await.unwrap();
+            BufReader::new(stream).read_line(&mut String::new()).await.unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = BufReader::new(stream).read_to_end(&mut vec![]).await;
+        });
+
+        let mut tp = MuxTransport::tcp(addr);
+        tp.timeout = Some(time::Duration::from_millis(200));
+        let client = Client::with_transport(tp);
+        let err = client.call::<String>("uptime", None).await.unwrap_err();
+        assert!(err.to_string().contains("connection closed"), "{}", err);
+        let err = client.call::<String>("uptime", None).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+
+        drop(client);
+        server.await.unwrap();
+    }
+}
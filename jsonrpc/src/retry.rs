@@ -0,0 +1,489 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Retry and failover
+//!
+//! Transport wrappers making a [`crate::Client`] resilient: [`RetryTransport`] retries failed
+//! requests with exponential backoff, [`FailoverTransport`] rotates across several transports,
+//! eg. to different nodes, skipping the unhealthy ones. They compose: a retry transport wrapping a
+//! failover transport retries on the next healthy node.
+//!
+//! Note that retrying a request the server may have processed, for instance after a read timeout,
+//! sends it again; only use them with calls safe to repeat.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{error, fmt, thread};
+
+use crate::client::Transport;
+use crate::{Request, Response};
+
+/// Bitcoin Core's error code while it is starting up, "warming up".
+pub const RPC_IN_WARMUP: i32 = -28;
+
+/// When and how often to retry.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of retries, after the first attempt.
+    pub max_retries: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The maximum delay between two attempts.
+    pub max_backoff: Duration,
+    /// The factor the delay is multiplied by after each retry.
+    pub multiplier: u32,
+    /// The fraction of the delay, between 0 and 1, randomly removed from it to spread retries.
+    pub jitter: f64,
+    /// Whether to retry on transport errors eg. connection refused or timeouts.
+    pub retry_transport_errors: bool,
+    /// The RPC error codes to retry on.
+    pub retry_codes: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2,
+            jitter: 0.5,
+            retry_transport_errors: true,
+            retry_codes: vec![RPC_IN_WARMUP],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retry number `retry`, starting at 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(retry);
+        let backoff = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+        let jitter = if self.jitter.is_nan() { 0.0 } else { self.jitter.clamp(0.0, 1.0) };
+        // Computed from the removed part, which can't overflow unlike the rest of the delay, as
+        // converting a float as large as `Duration::MAX` back can.
+        let removed = backoff.as_secs_f64() * jitter * random_fraction();
+        backoff.saturating_sub(Duration::try_from_secs_f64(removed).unwrap_or(backoff))
+    }
+
+    fn should_retry_error(&self, e: &crate::Error) -> bool {
+        match e {
+            crate::Error::Transport(_) => self.retry_transport_errors,
+            crate::Error::Rpc(e) => self.retry_codes.contains(&e.code),
+            _ => false,
+        }
+    }
+
+    fn should_retry_response(&self, resp: &Response) -> bool {
+        resp.error.as_ref().is_some_and(|e| self.retry_codes.contains(&e.code))
+    }
+}
+
+/// Returns a random number in `[0, 1)`, good enough for jitter.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A transport retrying the requests failed on the wrapped transport according to a
+/// [`RetryPolicy`].
+///
+/// Responses with a retryable RPC error are retried too, a batch is retried as a whole when any of
+/// its responses has one. Once retries are exhausted, the last result is returned.
+#[derive(Debug)]
+pub struct RetryTransport<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T: Transport> RetryTransport<T> {
+    /// Wraps `inner`, retrying according to `policy`.
+    pub fn new(inner: T, policy: RetryPolicy) -> Self { RetryTransport { inner, policy } }
+
+    /// Returns the retry policy.
+    pub fn policy(&self) -> &RetryPolicy { &self.policy }
+
+    /// Returns the wrapped transport.
+    pub fn into_inner(self) -> T { self.inner }
+
+    fn with_retries<R, F, S>(&self, mut send: F, should_retry: S) -> Result<R, crate::Error>
+    where
+        F: FnMut() -> Result<R, crate::Error>,
+        S: Fn(&R) -> bool,
+    {
+        let mut retry = 0;
+        loop {
+            let result = send();
+            let retryable = match result {
+                Ok(ref resp) => should_retry(resp),
+                Err(ref e) => self.policy.should_retry_error(e),
+            };
+            if !retryable || retry >= self.policy.max_retries {
+                return result;
+            }
+            thread::sleep(self.policy.backoff(retry));
+            retry += 1;
+        }
+    }
+}
+
+impl<T: Transport> Transport for RetryTransport<T> {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        self.with_retries(
+            || self.inner.send_request(req.clone()),
+            |resp| self.policy.should_retry_response(resp),
+        )
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        self.with_retries(
+            || self.inner.send_batch(reqs),
+            |resps| resps.iter().any(|resp| self.policy.should_retry_response(resp)),
+        )
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        self.with_retries(|| self.inner.send_notification(req.clone()), |_| false)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.inner.fmt_target(f) }
+}
+
+/// The health of one of the transports of a [`FailoverTransport`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Health {
+    /// The number of transport errors since the last success.
+    pub consecutive_failures: u32,
+    /// Whether the transport is used, it is skipped during the cooldown after too many failures.
+    pub healthy: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    current: usize,
+    failures: Vec<u32>,
+    unhealthy_until: Vec<Option<Instant>>,
+}
+
+/// A transport sending requests to the first healthy of several transports, rotating to the next
+/// one on transport errors.
+///
+/// A transport is marked unhealthy, and skipped, for a cooldown after `max_failures` consecutive
+/// transport errors. When all are unhealthy they are all tried anyway. The transport that last
+/// succeeded is used first for the next requests.
+pub struct FailoverTransport {
+    transports: Vec<Box<dyn Transport>>,
+    max_failures: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl FailoverTransport {
+    /// Creates a failover transport over `transports`, tried in order.
+    ///
+    /// Defaults to marking transports unhealthy after 3 consecutive failures, for 30 seconds.
+    pub fn new<I>(transports: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Box<dyn Transport>>,
+    {
+        let transports: Vec<_> = transports.into_iter().collect();
+        if transports.is_empty() {
+            return Err(Error::NoTransport);
+        }
+        let state = State {
+            current: 0,
+            failures: vec![0; transports.len()],
+            unhealthy_until: vec![None; transports.len()],
+        };
+        Ok(FailoverTransport {
+            transports,
+            max_failures: 3,
+            cooldown: Duration::from_secs(30),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Sets the number of consecutive failures after which a transport is marked unhealthy.
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    /// Sets how long an unhealthy transport is skipped.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Returns the health of each transport, in order.
+    pub fn health(&self) -> Vec<Health> {
+        let state = self.state.lock().expect("poisoned mutex");
+        let now = Instant::now();
+        (0..self.transports.len())
+            .map(|i| Health {
+                consecutive_failures: state.failures[i],
+                healthy: state.unhealthy_until[i].map_or(true, |until| until <= now),
+            })
+            .collect()
+    }
+
+    /// Returns the transports to try, in order: healthy ones from the current one, then the others.
+    fn order(&self) -> Vec<usize> {
+        let state = self.state.lock().expect("poisoned mutex");
+        let now = Instant::now();
+        let len = self.transports.len();
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) = (0..len)
+            .map(|i| (state.current + i) % len)
+            .partition(|&i| state.unhealthy_until[i].map_or(true, |until| until <= now));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    fn record(&self, index: usize, success: bool) {
+        let mut state = self.state.lock().expect("poisoned mutex");
+        if success {
+            state.current = index;
+            state.failures[index] = 0;
+            state.unhealthy_until[index] = None;
+        } else {
+            state.failures[index] += 1;
+            if state.failures[index] >= self.max_failures {
+                state.unhealthy_until[index] = Some(Instant::now() + self.cooldown);
+            }
+        }
+    }
+
+    fn failover<R, F>(&self, send: F) -> Result<R, crate::Error>
+    where
+        F: Fn(&dyn Transport) -> Result<R, crate::Error>,
+    {
+        let mut last_error = None;
+        for index in self.order() {
+            match send(&*self.transports[index]) {
+                Err(e @ crate::Error::Transport(_)) => {
+                    self.record(index, false);
+                    last_error = Some(e);
+                }
+                result => {
+                    self.record(index, true);
+                    return result;
+                }
+            }
+        }
+        Err(last_error.expect("there is at least one transport"))
+    }
+}
+
+impl Transport for FailoverTransport {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        self.failover(|tp| tp.send_request(req.clone()))
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        self.failover(|tp| tp.send_batch(reqs))
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        self.failover(|tp| tp.send_notification(req.clone()))
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("failover[")?;
+        for (i, tp) in self.transports.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            tp.fmt_target(f)?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl fmt::Debug for FailoverTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "jsonrpc::retry::FailoverTransport(")?;
+        self.fmt_target(f)?;
+        write!(f, ")")
+    }
+}
+
+/// Error that can occur while setting up the failover transport.
+#[derive(Debug)]
+pub enum Error {
+    /// No transport to fail over between.
+    NoTransport,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::NoTransport => f.write_str("failover transport needs at least one transport"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for crate::Error {
+    fn from(e: Error) -> crate::Error { crate::Error::Transport(Box::new(e)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::error::RpcError;
+    use crate::{arg, Client};
+
+    /// Fails the first `failures` requests, with an RPC error if `code` is set, then succeeds.
+    struct Flaky {
+        name: &'static str,
+        failures: u32,
+        code: Option<i32>,
+        calls: Arc<AtomicU32>,
+    }
+
+    impl Flaky {
+        fn new(name: &'static str, failures: u32, code: Option<i32>) -> (Flaky, Arc<AtomicU32>) {
+            let calls = Arc::new(AtomicU32::new(0));
+            (Flaky { name, failures, code, calls: Arc::clone(&calls) }, calls)
+        }
+    }
+
+    impl Transport for Flaky {
+        fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call >= self.failures {
+                let result = Some(arg(self.name));
+                return Ok(Response { result, error: None, id: req.id, jsonrpc: None });
+            }
+            match self.code {
+                Some(code) => {
+                    let error = Some(RpcError { code, message: "failed".into(), data: None });
+                    Ok(Response { result: None, error, id: req.id, jsonrpc: None })
+                }
+                None => Err(crate::Error::Transport("connection refused".into())),
+            }
+        }
+
+        fn send_batch(&self, _: &[Request]) -> Result<Vec<Response>, crate::Error> {
+            Err(crate::Error::EmptyBatch)
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.name) }
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy { max_retries, initial_backoff: Duration::from_millis(1), ..Default::default() }
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy { jitter: 0.0, ..Default::default() };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(100), Duration::from_secs(10));
+        let policy = RetryPolicy::default();
+        for _ in 0..100 {
+            let backoff = policy.backoff(1);
+            assert!(backoff > Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+        }
+
+        let policy = RetryPolicy { jitter: f64::NAN, ..Default::default() };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        let policy = RetryPolicy {
+            initial_backoff: Duration::MAX,
+            max_backoff: Duration::MAX,
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::MAX);
+        let policy = RetryPolicy { jitter: 1.0, ..policy };
+        for _ in 0..100 {
+            policy.backoff(1);
+        }
+    }
+
+    #[test]
+    fn retry() {
+        let (flaky, calls) = Flaky::new("a", 2, None);
+        let client = Client::with_transport(RetryTransport::new(flaky, policy(2)));
+        assert_eq!(client.call::<String>("test", None).unwrap(), "a");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (flaky, calls) = Flaky::new("a", 2, Some(RPC_IN_WARMUP));
+        let client = Client::with_transport(RetryTransport::new(flaky, policy(1)));
+        assert!(matches!(client.call::<String>("test", None), Err(crate::Error::Rpc(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (flaky, calls) = Flaky::new("a", 2, Some(-8));
+        let client = Client::with_transport(RetryTransport::new(flaky, policy(5)));
+        assert!(client.call::<String>("test", None).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failover() {
+        let (down, down_calls) = Flaky::new("down", u32::MAX, None);
+        let (up, up_calls) = Flaky::new("up", 0, None);
+        let failover = FailoverTransport::new([Box::new(down) as Box<dyn Transport>, Box::new(up)])
+            .unwrap()
+            .max_failures(2)
+            .cooldown(Duration::from_millis(100));
+        let failover = Arc::new(failover);
+        let client = Client::with_transport(ArcTransport(Arc::clone(&failover)));
+
+        for _ in 0..3 {
+            assert_eq!(client.call::<String>("test", None).unwrap(), "up");
+        }
+        // The transport that succeeded is used first.
+        assert_eq!(down_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(up_calls.load(Ordering::SeqCst), 3);
+        let health = failover.health();
+        assert_eq!(health[0], Health { consecutive_failures: 1, healthy: true });
+        assert_eq!(health[1], Health { consecutive_failures: 0, healthy: true });
+
+        assert!(FailoverTransport::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn failover_health() {
+        let (down, down_calls) = Flaky::new("down", u32::MAX, None);
+        let (flaky, _) = Flaky::new("flaky", 2, None);
+        let failover =
+            FailoverTransport::new([Box::new(flaky) as Box<dyn Transport>, Box::new(down)])
+                .unwrap()
+                .max_failures(1)
+                .cooldown(Duration::from_millis(50));
+        let failover = Arc::new(failover);
+        let client = Client::with_transport(ArcTransport(Arc::clone(&failover)));
+
+        // Both fail and are marked unhealthy, they are still tried.
+        assert!(client.call::<String>("test", None).is_err());
+        assert!(failover.health().iter().all(|h| !h.healthy));
+        assert!(client.call::<String>("test", None).is_err());
+        assert_eq!(down_calls.load(Ordering::SeqCst), 2);
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(client.call::<String>("test", None).unwrap(), "flaky");
+        assert_eq!(failover.health()[0], Health { consecutive_failures: 0, healthy: true });
+        assert_eq!(down_calls.load(Ordering::SeqCst), 2);
+    }
+
+    struct ArcTransport(Arc<FailoverTransport>);
+
+    impl Transport for ArcTransport {
+        fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+            self.0.send_request(req)
+        }
+
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+            self.0.send_batch(reqs)
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.0.fmt_target(f) }
+    }
+}
@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a minimal HTTP/1.1 JSON-RPC server, the counterpart of the
+//! `simple_http` transport.
+//!
+//! Requests are `POST`s with a `Content-Length`, connections are kept alive unless the client asks
+//! otherwise. Responses to notifications only are sent as `204 No Content`. Implement
+//! [`HttpHandler`] instead of [`Handler`] to choose the HTTP status and body of responses.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::{fmt, net, time};
+
+#[cfg(feature = "base64")]
+use base64::engine::general_purpose::STANDARD as BASE64;
+#[cfg(feature = "base64")]
+use base64::Engine;
+
+use super::{parse_error, Handler, Listener};
+
+/// The maximum size of a request body.
+const MAX_REQUEST_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Handles the body of requests received by an [`HttpServer`] at the HTTP level.
+///
+/// Implemented for all [`Handler`]s.
+pub trait HttpHandler: Send + Sync + 'static {
+    /// Handles the body of a request, returning the HTTP response to send back.
+    fn handle_http(&self, body: &str) -> HttpResponse;
+}
+
+impl<H: Handler> HttpHandler for H {
+    fn handle_http(&self, body: &str) -> HttpResponse {
+        match self.handle(body) {
+            Some(response) => HttpResponse::json(response),
+            None => HttpResponse::no_content(),
+        }
+    }
+}
+
+/// An HTTP response to send back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The status code.
+    pub status: u16,
+    /// The body, sent as `application/json` if not empty.
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// A `200 OK` response with the given JSON body.
+    pub fn json<S: Into<String>>(body: S) -> HttpResponse {
+        HttpResponse { status: 200, body: body.into() }
+    }
+
+    /// A `204 No Content` response.
+    pub fn no_content() -> HttpResponse { HttpResponse::status(204) }
+
+    /// A response with the given status and an empty body.
+    pub fn status(status: u16) -> HttpResponse { HttpResponse { status, body: String::new() } }
+}
+
+/// Serves an [`HttpHandler`] over HTTP, each connection on its own thread.
+///
+/// The server stops, and closes the open connections, when dropped.
+pub struct HttpServer {
+    addr: net::SocketAddr,
+    _listener: Listener<net::TcpStream>,
+}
+
+impl HttpServer {
+    /// Returns a builder for [`HttpServer`].
+    pub fn builder() -> Builder { Builder::new() }
+
+    /// Binds to `addr` and starts serving `handler`, without authentication.
+    ///
+    /// Use port 0 to bind to an available port, see [`HttpServer::local_addr`].
+    pub fn bind<A: net::ToSocketAddrs, H: HttpHandler>(
+        addr: A,
+        handler: H,
+    ) -> io::Result<HttpServer> {
+        Builder::new().bind(addr, handler)
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> net::SocketAddr { self.addr }
+
+    /// Returns the URL of the server, suitable for the `simple_http` transport.
+    pub fn url(&self) -> String { format!("http://{}", self.addr) }
+}
+
+impl fmt::Debug for HttpServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpServer").field("addr", &self.addr).finish()
+    }
+}
+
+/// Builder for [`HttpServer`].
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    basic_auth: Option<String>,
+    timeout: Option<time::Duration>,
+}
+
+impl Builder {
+    /// Constructs a new [`Builder`] for a server without authentication nor timeout.
+    pub fn new() -> Builder { Builder::default() }
+
+    /// Requires clients to authenticate with the given credentials, others get a
+    /// `401 Unauthorized`.
+    #[cfg(feature = "base64")]
+    pub fn auth<S: AsRef<str>>(mut self, user: S, pass: Option<S>) -> Self {
+        let mut auth = user.as_ref().to_owned();
+        auth.push(':');
+        if let Some(ref pass) = pass {
+            auth.push_str(pass.as_ref());
+        }
+        self.basic_auth = Some(format!("Basic {}", &BASE64.encode(auth.as_bytes())));
+        self
+    }
+
+    /// Sets the read and write timeout of the connections, idle connections are closed after it.
+    pub fn timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Binds to `addr` and starts serving `handler`.
+    pub fn bind<A: net::ToSocketAddrs, H: HttpHandler>(
+        self,
+        addr: A,
+        handler: H,
+    ) -> io::Result<HttpServer> {
+        let listener = net::TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let listener = Listener::spawn(
+            move || listener.accept().map(|(stream, _)| stream),
+            move |stream| {
+                let _ = serve(stream, &handler, &self);
+            },
+            move || {
+                let _ = net::TcpStream::connect(addr);
+            },
+        );
+        Ok(HttpServer { addr, _listener: listener })
+    }
+}
+
+/// The parts of an HTTP request the server cares about.
+struct HttpRequest {
+    method: String,
+    content_length: Option<u64>,
+    authorization: Option<String>,
+    close: bool,
+}
+
+/// Reads the request line and headers, returns [`None`] if the connection was closed.
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Option<HttpRequest>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let version = parts.nth(1).unwrap_or_default();
+    let mut request = HttpRequest {
+        method,
+        content_length: None,
+        authorization: None,
+        close: version != "HTTP/1.1",
+    };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(Some(request));
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => request.content_length = value.parse().ok(),
+            "authorization" => request.authorization = Some(value.to_owned()),
+            "connection" if value.eq_ignore_ascii_case("close") => request.close = true,
+            "connection" if value.eq_ignore_ascii_case("keep-alive") => request.close = false,
+            _ => {}
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+fn write_response<W: Write>(
+    writer: &mut W,
+    status: u16,
+    body: Option<&str>,
+    close: bool,
+) -> io::Result<()> {
+    let body = body.unwrap_or_default();
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    if !body.is_empty() {
+        response.push_str("Content-Type: application/json\r\n");
+    }
+    response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    if close {
+        response.push_str("Connection: close\r\n");
+    }
+    if status == 401 {
+        response.push_str("WWW-Authenticate: Basic realm=\"jsonrpc\"\r\n");
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    writer.write_all(response.as_bytes())?;
+    writer.flush()
+}
+
+fn serve<H: HttpHandler>(stream: net::TcpStream, handler: &H, conf: &Builder) -> io::Result<()> {
+    stream.set_read_timeout(conf.timeout)?;
+    stream.set_write_timeout(conf.timeout)?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_head(&mut reader)? {
+        if request.method != "POST" {
+            return write_response(reader.get_mut(), 405, None, true);
+        }
+        let length = match request.content_length {
+            Some(length) if length <= MAX_REQUEST_SIZE => length,
+            Some(_) => return write_response(reader.get_mut(), 413, None, true),
+            None => return write_response(reader.get_mut(), 411, None, true),
+        };
+        let mut body = Vec::new();
+        (&mut reader).take(length).read_to_end(&mut body)?;
+        if body.len() as u64 != length {
+            return Ok(());
+        }
+
+        if conf.basic_auth.is_some() && conf.basic_auth != request.authorization {
+            write_response(reader.get_mut(), 401, None, request.close)?;
+        } else {
+            let response = match std::str::from_utf8(&body) {
+                Ok(body) => handler.handle_http(body),
+                Err(_) =>
+                    HttpResponse::json(serde_json::to_string(&parse_error()).expect("serializes")),
+            };
+            write_response(reader.get_mut(), response.status, Some(&response.body), request.close)?;
+        }
+        if request.close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "simple_http", not(feature = "proxy")))]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::server::{parse_params, Router};
+    use crate::simple_http::SimpleHttpTransport;
+    use crate::{arg, Client};
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.register("double", |params| {
+            let (n,): (u64,) = parse_params(params)?;
+            Ok((n * 2).into())
+        });
+        router
+    }
+
+    fn client(url: &str, auth: Option<(&str, &str)>) -> Client {
+        let mut builder = SimpleHttpTransport::builder().url(url).unwrap();
+        if let Some((user, pass)) = auth {
+            builder = builder.auth(user, Some(pass));
+        }
+        Client::with_transport(builder.build())
+    }
+
+    #[test]
+    fn http_server() {
+        let server = HttpServer::bind("127.0.0.1:0", router()).unwrap();
+        let client = client(&server.url(), None);
+        for n in 0..3u64 {
+            let doubled: u64 = client.call("double", Some(&*arg([n]))).unwrap();
+            assert_eq!(doubled, n * 2);
+        }
+        let error = client.call::<u64>("double", Some(&*arg(["x"]))).unwrap_err();
+        assert!(matches!(error, crate::Error::Rpc(e) if e.code == -32602));
+    }
+
+    #[test]
+    fn http_server_auth() {
+        let server =
+            HttpServer::builder().auth("user", Some("pass")).bind("127.0.0.1:0", router()).unwrap();
+        let doubled: u64 =
+            client(&server.url(), Some(("user", "pass"))).call("double", Some(&*arg([2]))).unwrap();
+        assert_eq!(doubled, 4);
+        let error =
+            client(&server.url(), Some(("user", "wrong"))).call::<u64>("double", Some(&*arg([2])));
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn http_server_notification() {
+        let server = HttpServer::bind("127.0.0.1:0", router()).unwrap();
+        let mut stream = net::TcpStream::connect(server.local_addr()).unwrap();
+        let body = r#"{"jsonrpc":"2.0","method":"double","params":[1]}"#;
+        write!(stream, "POST / HTTP/1.0\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", response);
+    }
+}
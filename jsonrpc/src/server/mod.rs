@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Server support
+//!
+//! A minimal JSON-RPC server: a [`Router`] dispatching requests to the registered method handlers,
+//! and listeners serving it over HTTP, raw TCP and Unix Domain Sockets, the counterparts of the
+//! `simple_http`, `simple_tcp` and `simple_uds` transports.
+
+pub mod http;
+pub mod tcp;
+#[cfg(not(windows))]
+pub mod uds;
+
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{fmt, net, thread};
+
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::error::{result_to_response, standard_error, RpcError, StandardError};
+use crate::Response;
+
+/// Handles the body of JSON-RPC messages received by a listener.
+pub trait Handler: Send + Sync + 'static {
+    /// Handles a request, or a batch of requests, returning the response to send back or
+    /// [`None`] if there is nothing to respond, ie. when only notifications were received.
+    fn handle(&self, request: &str) -> Option<String>;
+}
+
+impl<H: Handler> Handler for Arc<H> {
+    fn handle(&self, request: &str) -> Option<String> { (**self).handle(request) }
+}
+
+type Method = Box<dyn Fn(Option<&RawValue>) -> Result<Value, RpcError> + Send + Sync>;
+
+/// Dispatches JSON-RPC requests to the method handlers registered by name.
+///
+/// Requests to unknown methods are answered with the standard "Method not found" error, malformed
+/// requests with "Parse error" or "Invalid Request".
+#[derive(Default)]
+pub struct Router {
+    methods: HashMap<String, Method>,
+}
+
+impl Router {
+    /// Creates a router without any method.
+    pub fn new() -> Router { Router::default() }
+
+    /// Registers `handler` for `method`, replacing any previous handler for the same method.
+    ///
+    /// The handler receives the raw request parameters, see [`parse_params`] to deserialize them.
+    pub fn register<F>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        F: Fn(Option<&RawValue>) -> Result<Value, RpcError> + Send + Sync + 'static,
+    {
+        self.methods.insert(method.to_owned(), Box::new(handler));
+        self
+    }
+
+    /// Returns whether a handler is registered for `method`.
+    pub fn contains(&self, method: &str) -> bool { self.methods.contains_key(method) }
+
+    /// Handles a single request, returning [`None`] for a notification.
+    pub fn handle_request(&self, request: ServerRequest) -> Option<Response> {
+        let result = match self.methods.get(&request.method) {
+            Some(method) => method(request.params.as_deref()),
+            None => Err(standard_error(StandardError::MethodNotFound, None)),
+        };
+        request.id.map(|id| result_to_response(result, id))
+    }
+
+    fn handle_value(&self, request: &RawValue) -> Option<Response> {
+        match serde_json::from_str::<ServerRequest>(request.get()) {
+            Ok(request) => self.handle_request(request),
+            Err(_) => Some(invalid_request()),
+        }
+    }
+}
+
+impl Handler for Router {
+    fn handle(&self, request: &str) -> Option<String> {
+        let response = if request.trim_start().starts_with('[') {
+            match serde_json::from_str::<Vec<&RawValue>>(request) {
+                Ok(batch) if batch.is_empty() => serde_json::to_string(&invalid_request()),
+                Ok(batch) => {
+                    let responses: Vec<_> =
+                        batch.into_iter().filter_map(|r| self.handle_value(r)).collect();
+                    if responses.is_empty() {
+                        return None;
+                    }
+                    serde_json::to_string(&responses)
+                }
+                Err(_) => serde_json::to_string(&parse_error()),
+            }
+        } else {
+            match serde_json::from_str::<&RawValue>(request) {
+                Ok(request) => serde_json::to_string(&self.handle_value(request)?),
+                Err(_) => serde_json::to_string(&parse_error()),
+            }
+        };
+        Some(response.expect("responses serialize"))
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Router").field("methods", &self.methods.keys()).finish()
+    }
+}
+
+/// A JSON-RPC request as received by a server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerRequest {
+    /// The name of the RPC call.
+    pub method: String,
+    /// Parameters to the RPC call.
+    #[serde(default)]
+    pub params: Option<Box<RawValue>>,
+    /// Identifier of the request, [`None`] for a notification.
+    #[serde(default, deserialize_with = "deserialize_id")]
+    pub id: Option<Value>,
+    /// jsonrpc field, "2.0" for JSON-RPC 2.0 requests.
+    #[serde(default)]
+    pub jsonrpc: Option<String>,
+}
+
+impl ServerRequest {
+    /// Returns whether this request is a notification, ie. it expects no response.
+    pub fn is_notification(&self) -> bool { self.id.is_none() }
+}
+
+/// A present `id` member is never a notification, even if `null`.
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// Deserializes request parameters, mapping failures to the standard "Invalid params" error.
+///
+/// Missing parameters are deserialized from an empty array.
+pub fn parse_params<T: for<'a> Deserialize<'a>>(params: Option<&RawValue>) -> Result<T, RpcError> {
+    let params = params.map(RawValue::get).unwrap_or("[]");
+    serde_json::from_str(params)
+        .map_err(|e| standard_error(StandardError::InvalidParams, Some(crate::arg(e.to_string()))))
+}
+
+fn parse_error() -> Response {
+    result_to_response(Err(standard_error(StandardError::ParseError, None)), Value::Null)
+}
+
+fn invalid_request() -> Response {
+    result_to_response(Err(standard_error(StandardError::InvalidRequest, None)), Value::Null)
+}
+
+/// A stream accepted by a listener.
+trait Connection: io::Read + io::Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl Connection for net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> { net::TcpStream::try_clone(self) }
+
+    fn shutdown(&self) -> io::Result<()> { net::TcpStream::shutdown(self, net::Shutdown::Both) }
+}
+
+#[cfg(not(windows))]
+impl Connection for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> { std::os::unix::net::UnixStream::try_clone(self) }
+
+    fn shutdown(&self) -> io::Result<()> {
+        std::os::unix::net::UnixStream::shutdown(self, net::Shutdown::Both)
+    }
+}
+
+/// How long the accept loop waits after failing to accept a connection.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Accepts connections on a background thread, serving each of them on its own thread.
+///
+/// When stopped, the accept loop is woken up by `wake` and the open connections are shut down.
+struct Listener<S: Connection> {
+    stopped: Arc<AtomicBool>,
+    connections: Arc<Mutex<HashMap<usize, S>>>,
+    wake: Box<dyn Fn() + Send + Sync>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl<S: Connection> Listener<S> {
+    fn spawn<A, F, W>(mut accept: A, serve: F, wake: W) -> Listener<S>
+    where
+        A: FnMut() -> io::Result<S> + Send + 'static,
+        F: Fn(S) + Send + Sync + 'static,
+        W: Fn() + Send + Sync + 'static,
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let serve = Arc::new(serve);
+        let thread = {
+            let (stopped, connections) = (Arc::clone(&stopped), Arc::clone(&connections));
+            thread::spawn(move || {
+                let mut next_id = 0;
+                while !stopped.load(Ordering::SeqCst) {
+                    let stream = match accept() {
+                        Ok(stream) => stream,
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+                            ) =>
+                            continue,
+                        // Eg. out of file descriptors, give the connections time to close.
+                        Err(_) => {
+                            thread::sleep(ACCEPT_ERROR_BACKOFF);
+                            continue;
+                        }
+                    };
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let id = next_id;
+                    next_id += 1;
+                    if let Ok(clone) = stream.try_clone() {
+                        connections.lock().unwrap().insert(id, clone);
+                    }
+                    let (serve, connections) = (Arc::clone(&serve), Arc::clone(&connections));
+                    thread::spawn(move || {
+                        serve(stream);
+                        connections.lock().unwrap().remove(&id);
+                    });
+                }
+            })
+        };
+        Listener { stopped, connections, wake: Box::new(wake), thread: Some(thread) }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stopped.store(true, Ordering::SeqCst);
+            (self.wake)();
+            let _ = thread.join();
+            for (_, stream) in self.connections.lock().unwrap().drain() {
+                let _ = stream.shutdown();
+            }
+        }
+    }
+}
+
+impl<S: Connection> Drop for Listener<S> {
+    fn drop(&mut self) { self.stop() }
+}
+
+/// Serves JSON messages, not delimited, read one after the other on a raw stream.
+fn serve_stream<S: Connection, H: Handler>(stream: S, handler: &H) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let next =
+            serde_json::Deserializer::from_reader(&mut reader).into_iter::<Box<RawValue>>().next();
+        match next {
+            Some(Ok(request)) =>
+                if let Some(response) = handler.handle(request.get()) {
+                    if reader.get_mut().write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                },
+            // The stream can't be resynchronized after invalid JSON.
+            Some(Err(e)) if e.is_syntax() || e.is_data() => {
+                let response = serde_json::to_string(&parse_error()).expect("serializes");
+                let _ = reader.get_mut().write_all(response.as_bytes());
+                return;
+            }
+            _ => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router
+            .register("add", |params| {
+                let (a, b): (i64, i64) = parse_params(params)?;
+                Ok(json!(a + b))
+            })
+            .register("fail", |_| Err(standard_error(StandardError::InternalError, None)));
+        router
+    }
+
+    fn handle(router: &Router, request: &str) -> Option<Value> {
+        router.handle(request).map(|r| serde_json::from_str(&r).unwrap())
+    }
+
+    #[test]
+    fn single_request() {
+        let router = router();
+        let resp = handle(&router, r#"{"jsonrpc":"2.0","method":"add","params":[1,2],"id":1}"#);
+        assert_eq!(resp.unwrap(), json!({"jsonrpc":"2.0","result":3,"error":null,"id":1}));
+
+        let resp = handle(&router, r#"{"method":"fail","id":"a"}"#).unwrap();
+        assert_eq!(resp["error"]["code"], -32603);
+        assert_eq!(resp["id"], "a");
+
+        let resp = handle(&router, r#"{"method":"add","params":["x"],"id":null}"#).unwrap();
+        assert_eq!(resp["error"]["code"], -32602);
+        assert_eq!(resp["id"], Value::Null);
+
+        let resp = handle(&router, r#"{"method":"sub","params":[],"id":2}"#).unwrap();
+        assert_eq!(resp["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn malformed_request() {
+        let router = router();
+        let resp = handle(&router, r#"{"method":"add","#).unwrap();
+        assert_eq!(resp["error"]["code"], -32700);
+        let resp = handle(&router, r#"{"params":[1,2],"id":1}"#).unwrap();
+        assert_eq!(resp["error"]["code"], -32600);
+        let resp = handle(&router, "[]").unwrap();
+        assert_eq!(resp["error"]["code"], -32600);
+        let resp = handle(&router, "[1,").unwrap();
+        assert_eq!(resp["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn batch_and_notifications() {
+        let router = router();
+        assert!(handle(&router, r#"{"method":"add","params":[1,2]}"#).is_none());
+        assert!(handle(&router, r#"[{"method":"add","params":[1,2]},{"method":"x"}]"#).is_none());
+
+        let resp = handle(
+            &router,
+            r#"[
+                {"method":"add","params":[1,2],"id":1},
+                {"method":"add","params":[3,4]},
+                1,
+                {"method":"add","params":[5,6],"id":2}
+            ]"#,
+        )
+        .unwrap();
+        let resp = resp.as_array().unwrap();
+        assert_eq!(resp.len(), 3);
+        assert_eq!(resp[0]["result"], 3);
+        assert_eq!(resp[1]["error"]["code"], -32600);
+        assert_eq!(resp[2]["result"], 11);
+        assert_eq!(resp[2]["id"], 2);
+    }
+
+    #[test]
+    fn accept_errors_back_off() {
+        use std::sync::atomic::AtomicUsize;
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&attempts);
+        let mut listener = Listener::<net::TcpStream>::spawn(
+            move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Err(io::Error::other("too many open files"))
+            },
+            |_| {},
+            || {},
+        );
+        thread::sleep(Duration::from_millis(200));
+        listener.stop();
+        assert!(attempts.load(Ordering::SeqCst) <= 5, "{:?}", attempts);
+    }
+}
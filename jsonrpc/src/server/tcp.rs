@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a JSON-RPC server over a raw [`std::net::TcpListener`], the counterpart
+//! of the `simple_tcp` transport.
+
+use std::{fmt, io, net};
+
+use super::{serve_stream, Handler, Listener};
+
+/// Serves a [`Handler`] over raw TCP, each connection on its own thread.
+///
+/// The server stops, and closes the open connections, when dropped.
+pub struct TcpServer {
+    addr: net::SocketAddr,
+    _listener: Listener<net::TcpStream>,
+}
+
+impl TcpServer {
+    /// Binds to `addr` and starts serving `handler`.
+    ///
+    /// Use port 0 to bind to an available port, see [`TcpServer::local_addr`].
+    pub fn bind<A: net::ToSocketAddrs, H: Handler>(addr: A, handler: H) -> io::Result<TcpServer> {
+        let listener = net::TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let listener = Listener::spawn(
+            move || listener.accept().map(|(stream, _)| stream),
+            move |stream| serve_stream(stream, &handler),
+            move || {
+                let _ = net::TcpStream::connect(addr);
+            },
+        );
+        Ok(TcpServer { addr, _listener: listener })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> net::SocketAddr { self.addr }
+}
+
+impl fmt::Debug for TcpServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TcpServer").field("addr", &self.addr).finish()
+    }
+}
+
+#[cfg(all(test, feature = "simple_tcp"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::Router;
+    use crate::simple_tcp::TcpTransport;
+    use crate::{arg, Client};
+
+    #[test]
+    fn tcp_server() {
+        let mut router = Router::new();
+        router.register("echo", |params| Ok(serde_json::from_str(params.unwrap().get()).unwrap()));
+        let server = TcpServer::bind("127.0.0.1:0", router).unwrap();
+
+        let mut transport = TcpTransport::new(server.local_addr());
+        transport.timeout = Some(Duration::from_secs(5));
+        let client = Client::with_transport(transport);
+        let echoed: Vec<u32> = client.call("echo", Some(&*arg([1, 2]))).unwrap();
+        assert_eq!(echoed, [1, 2]);
+
+        let params = arg(["batch"]);
+        let requests =
+            [client.build_request("echo", Some(&*params)), client.build_request("unknown", None)];
+        let responses = client.send_batch(&requests).unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().result::<Vec<String>>().unwrap(), ["batch"]);
+        let error = responses[1].as_ref().unwrap().clone().check_error().unwrap_err();
+        assert!(matches!(error, crate::Error::Rpc(e) if e.code == -32601));
+
+        let addr = server.local_addr();
+        drop(server);
+        assert!(net::TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a JSON-RPC server over a raw
+//! [`std::os::unix::net::UnixListener`], the counterpart of the `simple_uds` transport.
+
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::{fmt, fs, io, path};
+
+use super::{serve_stream, Handler, Listener};
+
+/// Serves a [`Handler`] over a Unix Domain Socket, each connection on its own thread.
+///
+/// The server stops, closes the open connections and removes the socket file when dropped.
+pub struct UdsServer {
+    sockpath: path::PathBuf,
+    listener: Listener<UnixStream>,
+}
+
+impl UdsServer {
+    /// Binds to the socket at `sockpath`, which must not exist, and starts serving `handler`.
+    pub fn bind<P: AsRef<path::Path>, H: Handler>(
+        sockpath: P,
+        handler: H,
+    ) -> io::Result<UdsServer> {
+        let sockpath = sockpath.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&sockpath)?;
+        let wake_path = sockpath.clone();
+        let listener = Listener::spawn(
+            move || listener.accept().map(|(stream, _)| stream),
+            move |stream| serve_stream(stream, &handler),
+            move || {
+                let _ = UnixStream::connect(&wake_path);
+            },
+        );
+        Ok(UdsServer { sockpath, listener })
+    }
+
+    /// Returns the path of the socket the server is listening on.
+    pub fn sockpath(&self) -> &path::Path { &self.sockpath }
+}
+
+impl Drop for UdsServer {
+    fn drop(&mut self) {
+        self.listener.stop();
+        let _ = fs::remove_file(&self.sockpath);
+    }
+}
+
+impl fmt::Debug for UdsServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UdsServer").field("sockpath", &self.sockpath).finish()
+    }
+}
+
+#[cfg(all(test, feature = "simple_uds"))]
+mod tests {
+    use std::process;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::Router;
+    use crate::simple_uds::UdsTransport;
+    use crate::{arg, Client};
+
+    #[test]
+    fn uds_server() {
+        let sockpath = std::env::temp_dir().join(format!("jsonrpc-uds-server-{}", process::id()));
+        let mut router = Router::new();
+        router.register("echo", |params| Ok(serde_json::from_str(params.unwrap().get()).unwrap()));
+        let server = UdsServer::bind(&sockpath, router).unwrap();
+
+        let mut transport = UdsTransport::new(server.sockpath());
+        transport.timeout = Some(Duration::from_secs(5));
+        let client = Client::with_transport(transport);
+        let echoed: String = client.call("echo", Some(&*arg("uds"))).unwrap();
+        assert_eq!(echoed, "uds");
+
+        drop(server);
+        assert!(!sockpath.exists());
+    }
+}
@@ -38,6 +38,14 @@ impl TcpTransport {
             .ok_or(Error::Timeout)??;
         Ok(resp)
     }
+
+    /// Sends notifications, to which the server doesn't respond.
+    fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let mut sock = net::TcpStream::connect(self.addr)?;
+        sock.set_write_timeout(self.timeout)?;
+        serde_json::to_writer(&mut sock, &req)?;
+        Ok(())
+    }
 }
 
 impl Transport for TcpTransport {
@@ -46,9 +54,15 @@ impl Transport for TcpTransport {
     }
 
     fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        if reqs.iter().all(Request::is_notification) {
+            self.notify(reqs)?;
+            return Ok(vec![]);
+        }
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> { Ok(self.notify(req)?) }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.addr) }
 }
 
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements an asynchronous transport over a raw [`tokio::net::TcpStream`], the
+//! async counterpart of [`crate::simple_tcp`].
+//!
+//! Unlike the synchronous transport, the connection is kept open and reused for the following
+//! requests.
+
+use std::sync::Arc;
+use std::{fmt, net, time};
+
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::client_async::{BoxFuture, Transport};
+use crate::simple_tcp::Error;
+use crate::stream_async::{notify, request, Connection};
+use crate::{Request, Response};
+
+/// Simple asynchronous TCP transport.
+#[derive(Debug, Clone)]
+pub struct TcpTransport {
+    /// The internet socket address to connect to.
+    pub addr: net::SocketAddr,
+    /// The timeout of a whole request, including connecting.
+    pub timeout: Option<time::Duration>,
+    sock: Arc<Mutex<Option<Connection<TcpStream>>>>,
+}
+
+impl TcpTransport {
+    /// Creates a new `TcpTransport` without timeouts.
+    pub fn new(addr: net::SocketAddr) -> TcpTransport {
+        TcpTransport { addr, timeout: None, sock: Arc::new(Mutex::new(None)) }
+    }
+
+    async fn request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let body = serde_json::to_vec(&req)?;
+        let mut sock = self.sock.lock().await;
+        let request = request(&mut sock, &body, || TcpStream::connect(self.addr));
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, request).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout),
+            },
+            None => request.await,
+        }
+    }
+
+    /// Sends notifications, to which the server doesn't respond, on a connection of their own.
+    async fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let body = serde_json::to_vec(&req)?;
+        let notify = notify(&body, || TcpStream::connect(self.addr));
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, notify).await {
+                Ok(result) => Ok(result?),
+                Err(_) => Err(Error::Timeout),
+            },
+            None => Ok(notify.await?),
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        Box::pin(async move { Ok(self.request(req).await?) })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        Box::pin(async move {
+            if reqs.iter().all(Request::is_notification) {
+                self.notify(reqs).await?;
+                return Ok(vec![]);
+            }
+            Ok(self.request(reqs).await?)
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        Box::pin(async move { Ok(self.notify(req).await?) })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.addr) }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::client_async::Client;
+    use crate::stream_async::read_json;
+    use crate::Dialect;
+
+    /// Answers each request on the connection with its own id as result, `count` times.
+    async fn serve(stream: TcpStream, count: usize) {
+        let mut conn = Connection::new(stream);
+        for _ in 0..count {
+            let req: Value = match read_json(&mut conn).await.unwrap() {
+                Some(req) => req,
+                None => return,
+            };
+            let resp = serde_json::json!({"result": req["id"], "error": null, "id": req["id"]});
+            conn.stream_mut().write_all(resp.to_string().as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn sanity_check_async_tcp_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // The first connection is closed after one request, the client reconnects.
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, 1).await;
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, 2).await;
+        });
+
+        let client = Client::with_transport(TcpTransport::new(addr));
+        for _ in 0..3 {
+            let req = client.build_request("uptime", None);
+            let id = req.id.clone();
+            let resp = client.send_request(req).await.unwrap();
+            assert_eq!(resp.result::<Value>().unwrap(), id);
+        }
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_tcp_notification() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // Each notification comes on a connection of its own.
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut conn = Connection::new(stream);
+                let notification: Value = read_json(&mut conn).await.unwrap().unwrap();
+                assert!(notification.get("id").is_none());
+            }
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(stream);
+            let req: Value = read_json(&mut conn).await.unwrap().unwrap();
+            let resp = serde_json::json!({"result": req["method"], "id": req["id"]});
+            conn.stream_mut().write_all(resp.to_string().as_bytes()).await.unwrap();
+        });
+
+        let client = Client::with_transport(TcpTransport::new(addr));
+        client.notify("ping", None).await.unwrap();
+        client.send_batch(&[client.build_notification("ping", None)]).await.unwrap();
+        assert_eq!(client.call::<String>("uptime", None).await.unwrap(), "uptime");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_tcp_v1_notification() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // A JSON-RPC 1.0 server answers notifications too.
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut conn = Connection::new(stream);
+                    while let Ok(Some(req)) = read_json::<_, Value>(&mut conn).await {
+                        let resp = serde_json::json!({"result": req["method"], "error": null, "id": req["id"]});
+                        conn.stream_mut().write_all(resp.to_string().as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let client = Client::with_transport(TcpTransport::new(addr)).with_dialect(Dialect::V1);
+        assert_eq!(client.call::<String>("uptime", None).await.unwrap(), "uptime");
+        client.notify("ping", None).await.unwrap();
+        assert_eq!(client.call::<String>("getinfo", None).await.unwrap(), "getinfo");
+    }
+
+    #[tokio::test]
+    async fn async_tcp_dropped_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // The first response comes in two halves, the second after the client gave up.
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(stream);
+            let req: Value = read_json(&mut conn).await.unwrap().unwrap();
+            let resp = serde_json::json!({"result": req["id"], "error": null, "id": req["id"]});
+            let resp = resp.to_string();
+            let (head, tail) = resp.split_at(resp.len() / 2);
+            conn.stream_mut().write_all(head.as_bytes()).await.unwrap();
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+            let _ = conn.stream_mut().write_all(tail.as_bytes()).await;
+
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, 1).await;
+        });
+
+        let client = Client::with_transport(TcpTransport::new(addr));
+        let call = client.call::<Value>("uptime", None);
+        assert!(tokio::time::timeout(time::Duration::from_millis(50), call).await.is_err());
+        let req = client.build_request("uptime", None);
+        let id = req.id.clone();
+        let resp = client.send_request(req).await.unwrap();
+        assert_eq!(resp.result::<Value>().unwrap(), id);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_tcp_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut tp = TcpTransport::new(listener.local_addr().unwrap());
+        tp.timeout = Some(time::Duration::from_millis(50));
+        let client = Client::with_transport(tp);
+        let req = client.build_request("uptime", None);
+        let err = client.send_request(req).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+    }
+}
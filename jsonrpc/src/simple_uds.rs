@@ -40,6 +40,14 @@ impl UdsTransport {
             .ok_or(Error::Timeout)??;
         Ok(resp)
     }
+
+    /// Sends notifications, to which the server doesn't respond.
+    fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let mut sock = UnixStream::connect(&self.sockpath)?;
+        sock.set_write_timeout(self.timeout)?;
+        serde_json::to_writer(&mut sock, &req)?;
+        Ok(())
+    }
 }
 
 impl Transport for UdsTransport {
@@ -48,9 +56,17 @@ impl Transport for UdsTransport {
     }
 
     fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::error::Error> {
+        if reqs.iter().all(Request::is_notification) {
+            self.notify(reqs)?;
+            return Ok(vec![]);
+        }
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::error::Error> {
+        Ok(self.notify(req)?)
+    }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.sockpath.to_string_lossy())
     }
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements an asynchronous transport over a raw [`tokio::net::UnixStream`], the
+//! async counterpart of [`crate::simple_uds`].
+//!
+//! Unlike the synchronous transport, the connection is kept open and reused for the following
+//! requests.
+
+use std::sync::Arc;
+use std::{fmt, path, time};
+
+use tokio::net::UnixStream;
+use tokio::sync::Mutex;
+
+use crate::client_async::{BoxFuture, Transport};
+use crate::simple_uds::Error;
+use crate::stream_async::{notify, request, Connection};
+use crate::{Request, Response};
+
+/// Simple asynchronous UDS transport.
+#[derive(Debug, Clone)]
+pub struct UdsTransport {
+    /// The path to the Unix Domain Socket.
+    pub sockpath: path::PathBuf,
+    /// The timeout of a whole request, including connecting.
+    pub timeout: Option<time::Duration>,
+    sock: Arc<Mutex<Option<Connection<UnixStream>>>>,
+}
+
+impl UdsTransport {
+    /// Creates a new [`UdsTransport`] without timeouts to use.
+    pub fn new<P: AsRef<path::Path>>(sockpath: P) -> UdsTransport {
+        UdsTransport {
+            sockpath: sockpath.as_ref().to_path_buf(),
+            timeout: None,
+            sock: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn request<R>(&self, req: impl serde::Serialize) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let body = serde_json::to_vec(&req)?;
+        let mut sock = self.sock.lock().await;
+        let request = request(&mut sock, &body, || UnixStream::connect(&self.sockpath));
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, request).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout),
+            },
+            None => request.await,
+        }
+    }
+
+    /// Sends notifications, to which the server doesn't respond, on a connection of their own.
+    async fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let body = serde_json::to_vec(&req)?;
+        let notify = notify(&body, || UnixStream::connect(&self.sockpath));
+        match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, notify).await {
+                Ok(result) => Ok(result?),
+                Err(_) => Err(Error::Timeout),
+            },
+            None => Ok(notify.await?),
+        }
+    }
+}
+
+impl Transport for UdsTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        Box::pin(async move { Ok(self.request(req).await?) })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        Box::pin(async move {
+            if reqs.iter().all(Request::is_notification) {
+                self.notify(reqs).await?;
+                return Ok(vec![]);
+            }
+            Ok(self.request(reqs).await?)
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        Box::pin(async move { Ok(self.notify(req).await?) })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.sockpath.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use serde_json::Value;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::UnixListener;
+
+    use super::*;
+    use crate::client_async::Client;
+    use crate::stream_async::read_json;
+
+    #[tokio::test]
+    async fn sanity_check_async_uds_transport() {
+        let socket_path: path::PathBuf =
+            format!("uds_async_scratch_{}.socket", process::id()).into();
+        // Any leftover?
+        fs::remove_file(&socket_path).unwrap_or(());
+
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Connection::new(stream);
+            while let Some(req) = read_json::<_, Value>(&mut conn).await.unwrap() {
+                let resp = serde_json::json!({"result": req["method"], "id": req["id"]});
+                conn.stream_mut().write_all(resp.to_string().as_bytes()).await.unwrap();
+            }
+        });
+
+        let mut tp = UdsTransport::new(&socket_path);
+        tp.timeout = Some(time::Duration::from_secs(5));
+        let client = Client::with_transport(tp);
+        for method in ["getinfo", "uptime"] {
+            let resp = client.send_request(client.build_request(method, None)).await.unwrap();
+            assert_eq!(resp.result::<String>().unwrap(), method);
+        }
+        drop(client);
+        server.await.unwrap();
+
+        // Clean up
+        fs::remove_file(&socket_path).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Helpers shared by the asynchronous transports over raw streams, `simple_tcp_async` and
+//! `simple_uds_async`.
+
+use std::future::Future;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Absolute maximum size of a response, past which the connection is dropped.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024 * 1024;
+
+/// An open stream and the bytes read past the last response.
+#[derive(Debug)]
+pub(crate) struct Connection<S> {
+    stream: S,
+    buf: Vec<u8>,
+    /// How far the JSON value at the start of `buf` has been scanned.
+    scan: Scan,
+}
+
+impl<S> Connection<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Connection { stream, buf: vec![], scan: Scan::default() }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn stream_mut(&mut self) -> &mut S { &mut self.stream }
+}
+
+/// Error reading a JSON value from a stream.
+#[derive(Debug)]
+pub(crate) enum ReadError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self { ReadError::Io(e) }
+}
+
+/// Sends `body` on the connection, opening one with `connect` if there is none, and reads back a
+/// single JSON value.
+///
+/// A reused connection found closed by the peer is reopened once. The connection is taken out of
+/// `sock` while in use and only put back once the response was read, so that an error or dropping
+/// the future doesn't leave a half-read response on it.
+pub(crate) async fn request<S, C, F, R, E>(
+    sock: &mut Option<Connection<S>>,
+    body: &[u8],
+    connect: C,
+) -> Result<R, E>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    C: Fn() -> F,
+    F: Future<Output = io::Result<S>>,
+    R: for<'a> serde::de::Deserialize<'a>,
+    E: From<io::Error> + From<serde_json::Error>,
+{
+    let closed = || E::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+
+    let (mut conn, fresh) = match sock.take() {
+        Some(conn) => (conn, false),
+        None => (Connection::new(connect().await?), true),
+    };
+
+    let sent = conn.stream.write_all(body).await.is_ok() && conn.stream.flush().await.is_ok();
+    match read_json(&mut conn).await {
+        Ok(Some(resp)) => {
+            *sock = Some(conn);
+            return Ok(resp);
+        }
+        Err(ReadError::Json(e)) => return Err(e.into()),
+        Ok(None) if fresh => return Err(closed()),
+        Err(ReadError::Io(e)) if fresh || !sent => return Err(e.into()),
+        // The reused connection is broken, retry once with a fresh one.
+        _ => {}
+    }
+
+    let mut conn = Connection::new(connect().await?);
+    conn.stream.write_all(body).await?;
+    conn.stream.flush().await?;
+    match read_json(&mut conn).await {
+        Ok(Some(resp)) => {
+            *sock = Some(conn);
+            Ok(resp)
+        }
+        Ok(None) => Err(closed()),
+        Err(ReadError::Io(e)) => Err(e.into()),
+        Err(ReadError::Json(e)) => Err(e.into()),
+    }
+}
+
+/// Sends `body` on a connection of its own, opened with `connect`, without reading a response.
+///
+/// Like the synchronous transports, notifications don't go on the reused connection: a server
+/// answering them anyway, as under [`crate::Dialect::V1`], would otherwise have its reply read as
+/// the response to the next request.
+pub(crate) async fn notify<S, C, F>(body: &[u8], connect: C) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+    C: FnOnce() -> F,
+    F: Future<Output = io::Result<S>>,
+{
+    let mut stream = connect().await?;
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+/// Reads a single JSON value, returns [`None`] if the stream was closed before any byte of it.
+///
+/// The bytes read are scanned once for the end of the value, which is only parsed when complete.
+pub(crate) async fn read_json<S, R>(conn: &mut Connection<S>) -> Result<Option<R>, ReadError>
+where
+    S: AsyncRead + Unpin,
+    R: for<'a> serde::de::Deserialize<'a>,
+{
+    let mut chunk = [0u8; 8192];
+    loop {
+        if let Some(end) = conn.scan.advance(&conn.buf) {
+            let value = serde_json::from_slice(&conn.buf[..end]).map_err(ReadError::Json)?;
+            conn.buf.drain(..end);
+            conn.scan = Scan::default();
+            return Ok(Some(value));
+        }
+        if conn.buf.len() > MAX_RESPONSE_SIZE {
+            let msg = format!("response larger than {} bytes", MAX_RESPONSE_SIZE);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg).into());
+        }
+        let n = conn.stream.read(&mut chunk).await?;
+        if n == 0 {
+            if conn.buf.iter().all(u8::is_ascii_whitespace) {
+                return Ok(None);
+            }
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        conn.buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// The state of scanning a buffer for the end of the JSON value it starts with.
+#[derive(Debug, Default)]
+struct Scan {
+    /// The number of bytes scanned.
+    pos: usize,
+    /// Whether the value started.
+    started: bool,
+    /// The number of objects and arrays open.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Scan {
+    /// Scans the new bytes of `buf`, returning the end of the value if it is complete.
+    fn advance(&mut self, buf: &[u8]) -> Option<usize> {
+        while self.pos < buf.len() {
+            let byte = buf[self.pos];
+            self.pos += 1;
+            if !self.started {
+                if byte.is_ascii_whitespace() {
+                    continue;
+                }
+                // Responses are objects or arrays, leave anything else to the JSON parser.
+                if byte != b'{' && byte != b'[' {
+                    return Some(buf.len());
+                }
+                self.started = true;
+                self.depth = 1;
+                continue;
+            }
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        return Some(self.pos);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}